@@ -0,0 +1,42 @@
+//! Optopodi gathers metrics about GitHub organizations and their repositories.
+//!
+//! Each metric is a [`metrics::Producer`], which streams rows of data over a channel
+//! to a [`metrics::Consumer`] such as [`metrics::Print`], which writes them as CSV.
+//! A [`report::Report`] runs all of the metrics for an organization and writes
+//! the results to a directory.
+//!
+//! Producers can also be run on their own:
+//!
+//! ```no_run
+//! use optopodi::metrics::{
+//!     self, Graphql, Producer, RepoParticipants, DEFAULT_MAX_ATTEMPTS, DEFAULT_TIMEOUT,
+//! };
+//!
+//! # async fn example() -> stable_eyre::eyre::Result<()> {
+//! let auth = optopodi::token::github_auth()?;
+//! optopodi::token::initialise_octocrab(auth, DEFAULT_TIMEOUT).await?;
+//!
+//! let graphql = Graphql::new("graphql".into(), false, DEFAULT_MAX_ATTEMPTS, false);
+//! let producer = RepoParticipants::new(
+//!     graphql,
+//!     "rust-lang".to_string(),
+//!     vec!["rust".to_string()],
+//!     "2021-07-01".parse()?,
+//!     "2021-08-01".parse()?,
+//! );
+//! let (column_names, mut rx, handle) =
+//!     metrics::run_producer(producer, tokio_util::sync::CancellationToken::new());
+//! println!("{:?}", column_names);
+//! while let Some(row) = rx.recv().await {
+//!     println!("{:?}", row);
+//! }
+//! handle.await??;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod metrics;
+pub mod progress;
+pub mod report;
+pub mod token;
+mod util;
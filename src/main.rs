@@ -1,14 +1,17 @@
 use clap::{AppSettings, Parser};
 use fehler::throws;
 use stable_eyre::eyre::{Error, WrapErr};
+use std::io::Write;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::EnvFilter;
 
-mod metrics;
-mod report;
-mod token;
-mod util;
-
-use crate::report::Report;
+use optopodi::metrics::{self, Graphql, DEFAULT_MAX_ATTEMPTS};
+use optopodi::progress;
+use optopodi::report::{MetricsFailed, OutputFormat, Report};
+use optopodi::token;
 
 #[derive(Parser, Debug, PartialEq)]
 #[clap(setting = AppSettings::ColoredHelp)]
@@ -18,6 +21,18 @@ struct OctoCli {
     #[clap(long)]
     replay_graphql: bool,
 
+    /// Save the results of graphql queries gzip-compressed.
+    #[clap(long)]
+    compress_replay: bool,
+
+    /// Don't draw progress bars on stderr.
+    #[clap(long)]
+    no_progress: bool,
+
+    /// Give up on each attempt at a GitHub query after this many seconds.
+    #[clap(long, value_name = "SECONDS", default_value = "30")]
+    timeout: u64,
+
     /// the sub-command to run
     #[clap(subcommand)]
     cmd: Cmd,
@@ -25,34 +40,192 @@ struct OctoCli {
 
 #[derive(Parser, Debug, PartialEq)]
 enum Cmd {
-    Report { directory: String },
+    Report {
+        directory: String,
+
+        /// Read the configuration from this file instead of `report.toml` in the directory,
+        /// or from stdin if this is `-`. The `REPORT_TOML` environment variable can also
+        /// hold the configuration itself.
+        #[clap(long)]
+        config: Option<PathBuf>,
+
+        /// Analyze this organization instead of the one in `report.toml`.
+        /// All of its repositories are analyzed, ignoring the configured `repos`.
+        #[clap(long)]
+        org: Option<String>,
+
+        /// Analyze only this repository instead of the ones in `report.toml`.
+        /// May be given more than once.
+        #[clap(long = "repo", value_name = "REPO", multiple_occurrences = true)]
+        repos: Vec<String>,
+
+        /// Write the input files, and the outputs that come straight from GitHub data,
+        /// in this format: csv, json or ndjson.
+        #[clap(long, default_value = "csv")]
+        format: OutputFormat,
+
+        /// Append the rows of the outputs that come straight from GitHub data to the
+        /// files of earlier runs, with the date of this run, rather than overwriting them.
+        #[clap(long)]
+        append: bool,
+
+        /// Also write the outputs that come straight from GitHub data to an Excel
+        /// workbook, `output/report.xlsx`, with a worksheet for each.
+        #[clap(long)]
+        xlsx: bool,
+
+        /// Analyze only the first this many repositories, for a quick trial run.
+        #[clap(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Check `report.toml` in a report directory without generating the report.
+    Validate {
+        directory: String,
+
+        /// Check this file instead of `report.toml` in the directory, or stdin if this is `-`.
+        #[clap(long)]
+        config: Option<PathBuf>,
+    },
+    /// Print the names of all repositories in an organization.
+    ListRepos {
+        org: String,
+
+        /// Print the repositories as CSV (with a header row) rather than one per line.
+        #[clap(long)]
+        csv: bool,
+    },
 }
 
-#[throws]
+/// The exit code when the report was only partly produced, because some metrics failed.
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
 #[tokio::main]
-async fn main() {
-    stable_eyre::install().wrap_err("Failed to install `stable_eyre`")?;
-    env_logger::init();
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            if error.chain().any(|e| e.is::<MetricsFailed>()) {
+                ExitCode::from(EXIT_PARTIAL_FAILURE)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
 
-    let token = token::github_token().wrap_err("Failed to initialize GitHub Token")?;
+#[throws]
+async fn run() {
+    stable_eyre::install().wrap_err("Failed to install `stable_eyre`")?;
+    // `RUST_LOG` selects what is logged, e.g. `RUST_LOG=optopodi=debug`.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
 
-    // initialize static octocrab API -- call `octocrab::instance()` anywhere to retrieve instance
-    octocrab::initialise(octocrab::Octocrab::builder().personal_token(token))
-        .wrap_err("Failed to initialize static instance of Octocrab")?;
+    let auth = token::github_auth().wrap_err("Failed to initialize GitHub Token")?;
 
     let cli = OctoCli::parse();
+    progress::set_enabled(!cli.no_progress);
+    let timeout = Duration::from_secs(cli.timeout);
+
+    // initialize static octocrab API -- call `octocrab::instance()` anywhere to retrieve instance
+    token::initialise_octocrab(auth, timeout).await?;
 
     match cli.cmd {
-        Cmd::Report { directory } => {
-            Report::new(PathBuf::from(&directory), cli.replay_graphql)
-                .run()
+        Cmd::Report {
+            directory,
+            config,
+            org,
+            repos,
+            format,
+            append,
+            xlsx,
+            limit,
+        } => {
+            // On Ctrl-C, stop producing and flush what has been produced so far.
+            let cancel = CancellationToken::new();
+            let on_ctrl_c = cancel.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    eprintln!("Interrupted; finishing the current output...");
+                    on_ctrl_c.cancel();
+                }
+            });
+
+            Report::new(
+                PathBuf::from(&directory),
+                cli.replay_graphql,
+                cli.compress_replay,
+            )
+            .with_config_path(config)
+            .with_org(org)
+            .with_repos(repos)
+            .with_limit(limit)
+            .with_cancellation(cancel)
+            .with_timeout(timeout)
+            .with_format(format)
+            .with_append(append)
+            .with_xlsx(xlsx)
+            .run()
+            .await
+            .wrap_err_with(|| {
+                format!(
+                    "Failed to generate new report from directory {}",
+                    &directory
+                )
+            })?;
+        }
+        Cmd::Validate { directory, config } => {
+            let config = Report::new(
+                PathBuf::from(&directory),
+                cli.replay_graphql,
+                cli.compress_replay,
+            )
+            .with_config_path(config)
+            .with_timeout(timeout)
+            .validate()
+            .await
+            .wrap_err_with(|| format!("Invalid report configuration in {}", &directory))?;
+            println!(
+                "{}: OK ({} repositories in {})",
+                directory,
+                config.repos().len(),
+                config.org()
+            );
+        }
+        Cmd::ListRepos { org, csv } => {
+            // There is no report directory, so saved responses go to a scratch directory.
+            let graphql_dir = std::env::temp_dir().join("optopodi").join("list-repos");
+            let mut graphql = Graphql::new(
+                graphql_dir,
+                cli.replay_graphql,
+                DEFAULT_MAX_ATTEMPTS,
+                cli.compress_replay,
+            )
+            .with_timeout(timeout);
+            let repos = metrics::all_repos(&mut graphql, &org)
                 .await
-                .wrap_err_with(|| {
-                    format!(
-                        "Failed to generate new report from directory {}",
-                        &directory
-                    )
-                })?;
+                .wrap_err_with(|| format!("Failed to list repositories for {}", org))?;
+            print_repos(&mut std::io::stdout().lock(), &repos, csv)
+                .wrap_err("Failed to print repositories")?;
+        }
+    }
+}
+
+/// Writes `repos` to `out`, either one per line or as a single-column CSV.
+#[throws]
+fn print_repos(out: &mut impl Write, repos: &[String], csv: bool) {
+    if csv {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["Repository"])?;
+        for repo in repos {
+            writer.write_record([repo])?;
+        }
+        writer.flush()?;
+    } else {
+        for repo in repos {
+            writeln!(out, "{}", repo)?;
         }
     }
 }
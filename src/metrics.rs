@@ -1,12 +1,53 @@
 use async_trait::async_trait;
 use stable_eyre::eyre;
 use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
+mod approval_counts;
+mod approval_to_merge;
+mod author_diversity;
+mod closed_without_pr;
+mod comment_export;
+mod commit_counts;
+mod cross_repo_references;
+mod draft_pr_usage;
+mod file_hotspots;
+mod first_response_time;
+mod github_context;
 mod gql;
+mod issue_reactions;
+mod issue_trend;
+mod json;
+mod label_churn;
+mod label_cooccurrence;
+mod labeled_pr_counts;
 mod list_repos;
+mod merge_methods;
+mod merge_throughput;
+mod ndjson;
+mod off_hours_activity;
+mod open_issue_ages;
+mod pr_discussion;
+mod pr_outcomes;
+mod pr_sizes;
 mod print;
+mod pull_request_list;
+mod queue_depth;
+mod ramp_time;
+mod reopens;
 mod repo_participants;
+mod review_comment_volume;
+mod review_request_gap;
+mod review_sla;
+mod reviewer_latency;
+mod self_merges;
+mod tee;
+mod time_to_label;
+mod triage_latency;
 mod util;
+mod warnings;
+mod xlsx;
 
 #[async_trait]
 pub trait Producer {
@@ -27,25 +68,249 @@ pub trait Consumer {
     ) -> eyre::Result<()>;
 }
 
-pub use gql::Graphql;
+pub use approval_counts::ApprovalCounts;
+pub use approval_to_merge::ApprovalToMerge;
+pub use author_diversity::AuthorDiversity;
+pub use closed_without_pr::ClosedWithoutPr;
+pub use comment_export::CommentExport;
+pub use commit_counts::CommitCounts;
+pub use cross_repo_references::CrossRepoReferences;
+pub use draft_pr_usage::DraftPrUsage;
+pub use file_hotspots::FileHotspots;
+pub use first_response_time::FirstResponseTime;
+pub use github_context::GithubContext;
+pub use gql::{
+    load_query_overrides, Graphql, TimedOut, DEFAULT_MAX_ATTEMPTS, DEFAULT_TIMEOUT, MAX_PAGE_SIZE,
+};
+pub use issue_reactions::IssueReactions;
+pub use issue_trend::IssueTrend;
+pub use json::Json;
+pub use label_churn::LabelChurn;
+pub use label_cooccurrence::LabelCooccurrence;
+pub use labeled_pr_counts::LabeledPrCounts;
 pub use list_repos::ListReposForOrg;
-pub use print::Print;
+pub use merge_methods::MergeMethods;
+pub use merge_throughput::MergeThroughput;
+pub use ndjson::NdJson;
+pub use off_hours_activity::OffHoursActivity;
+pub use open_issue_ages::OpenIssueAges;
+pub use pr_discussion::PrDiscussion;
+pub use pr_outcomes::PrOutcomes;
+pub use pr_sizes::PrSizes;
+pub use print::{is_identifier_column, CsvOptions, Print};
+pub use pull_request_list::PullRequestList;
+pub use queue_depth::QueueDepth;
+pub use ramp_time::RampTime;
+pub use reopens::Reopens;
 pub use repo_participants::RepoParticipants;
-pub use util::all_repos;
+pub use review_comment_volume::ReviewCommentVolume;
+pub use review_request_gap::ReviewRequestGap;
+pub use review_sla::ReviewSla;
+pub use reviewer_latency::ReviewerLatency;
+pub use self_merges::SelfMerges;
+pub use tee::Tee;
+pub use time_to_label::TimeToLabel;
+pub use triage_latency::TriageLatency;
+pub use util::{all_repos, LabelMatch, TimeBucket};
+pub use warnings::{Warning, Warnings};
+pub use xlsx::{Workbook, Xlsx};
+
+/// A span for the work a producer does on one repository, so that
+/// log output can be attributed to it.
+fn repo_span(org_name: &str, repo_name: &str) -> tracing::Span {
+    tracing::info_span!("repo", org = %org_name, repo = %repo_name)
+}
+
+/// How many rows may be waiting between a producer and its consumer by default.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 400;
 
 /// Spawns a task running a producer and returns the column names
 /// that it will produce along with
-/// a receiver for the actual columns.
+/// a receiver for the actual columns, and a handle to the task.
+///
+/// The handle resolves to the producer's result once it has finished, so a caller can
+/// tell a complete set of rows from one cut short by an error.
+///
+/// If `cancel` is cancelled, the producer is stopped and the receiver is closed
+/// once the rows produced so far have been received, so consumers can finish cleanly.
 pub fn run_producer(
     producer: impl Producer + Send + 'static,
-) -> (Vec<String>, Receiver<Vec<String>>) {
-    let (tx, rx) = mpsc::channel::<Vec<String>>(400);
+    cancel: CancellationToken,
+) -> (
+    Vec<String>,
+    Receiver<Vec<String>>,
+    JoinHandle<eyre::Result<()>>,
+) {
+    run_producer_with_capacity(producer, DEFAULT_CHANNEL_CAPACITY, cancel)
+}
+
+/// Like `run_producer`, but buffering at most `capacity` rows (which must be non-zero).
+///
+/// The buffer is what lets a producer run ahead of its consumer: once `capacity` rows
+/// are waiting, the producer blocks on sending until the consumer catches up. So a slow
+/// consumer throttles its producer (and thereby its GitHub queries) rather than letting
+/// rows pile up in memory; a larger capacity smooths out bursts at the cost of memory.
+pub fn run_producer_with_capacity(
+    producer: impl Producer + Send + 'static,
+    capacity: usize,
+    cancel: CancellationToken,
+) -> (
+    Vec<String>,
+    Receiver<Vec<String>>,
+    JoinHandle<eyre::Result<()>>,
+) {
+    let (tx, rx) = mpsc::channel::<Vec<String>>(capacity);
     let column_names = producer.column_names();
-    tokio::spawn(async move {
-        if let Err(e) = producer.producer_task(tx).await {
-            println!("Encountered an error while collecting data: {}", e);
+    let handle = tokio::spawn(async move {
+        tokio::select! {
+            result = producer.producer_task(tx) => {
+                if let Err(e) = &result {
+                    tracing::error!("Encountered an error while collecting data: {}", e);
+                }
+                result
+            }
+            () = cancel.cancelled() => Ok(()),
         }
     });
 
-    (column_names, rx)
+    (column_names, rx, handle)
+}
+
+/// Helpers for running producers against canned GitHub responses.
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    /// A `Graphql` that replays `responses`, in order, rather than querying GitHub.
+    pub(crate) fn replaying(name: &str, responses: &[serde_json::Value]) -> Graphql {
+        let dir = std::env::temp_dir().join(format!("optopodi-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, response) in responses.iter().enumerate() {
+            std::fs::write(dir.join(format!("{}.json", i)), response.to_string()).unwrap();
+        }
+        Graphql::new(dir, true, 1, false)
+    }
+
+    /// Runs `producer` to completion, returning the rows it produced.
+    pub(crate) async fn rows(producer: impl Producer) -> Vec<Vec<String>> {
+        let (tx, mut rx) = mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
+        producer.producer_task(tx).await.unwrap();
+        let mut rows = vec![];
+        while let Some(row) = rx.recv().await {
+            rows.push(row);
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Sends `rows` numbered rows, counting each as it is sent, and then never finishes.
+    struct Endless {
+        rows: usize,
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Producer for Endless {
+        fn column_names(&self) -> Vec<String> {
+            vec!["Row".to_string()]
+        }
+
+        async fn producer_task(self, tx: Sender<Vec<String>>) -> eyre::Result<()> {
+            for i in 0..self.rows {
+                tx.send(vec![i.to_string()]).await?;
+                self.sent.fetch_add(1, Ordering::SeqCst);
+            }
+            std::future::pending().await
+        }
+    }
+
+    /// Waits until `sent` stops changing, i.e. the producer has done all it can for now.
+    async fn settle(sent: &AtomicUsize) -> usize {
+        loop {
+            let before = sent.load(Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            if sent.load(Ordering::SeqCst) == before {
+                return before;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_keeps_the_rows_produced_so_far() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+        let producer = Endless {
+            rows: 3,
+            sent: sent.clone(),
+        };
+        let (column_names, mut rx, handle) = run_producer(producer, cancel.clone());
+        assert_eq!(column_names, ["Row"]);
+        assert_eq!(settle(&sent).await, 3);
+
+        cancel.cancel();
+        handle.await.unwrap().unwrap();
+        let mut rows = vec![];
+        while let Some(row) = rx.recv().await {
+            rows.push(row);
+        }
+        assert_eq!(rows, [["0"], ["1"], ["2"]]);
+    }
+
+    /// Collects the log output of a test.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_within_a_repo_span_name_the_repo() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            repo_span("rust-lang", "cargo").in_scope(|| tracing::info!("counting commits"));
+        });
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.contains("repo{org=rust-lang repo=cargo}: "),
+            "{}",
+            logs
+        );
+        assert!(logs.contains("counting commits"), "{}", logs);
+    }
+
+    #[tokio::test]
+    async fn producers_run_ahead_by_at_most_the_capacity() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let producer = Endless {
+            rows: 10,
+            sent: sent.clone(),
+        };
+        let (_, mut rx, handle) = run_producer_with_capacity(producer, 3, CancellationToken::new());
+        assert_eq!(settle(&sent).await, 3);
+
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        assert_eq!(settle(&sent).await, 5);
+        handle.abort();
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Reports, for each PR merged in the given time period, how many distinct reviewers
+/// other than its author approved it, e.g. to audit a "2 approvals required" policy.
+///
+/// Approvals that were dismissed don't count.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out, and approvals
+/// by bots don't count.
+pub struct ApprovalCounts {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl ApprovalCounts {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/approval_counts.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ApprovalCountsQuery;
+use approval_counts_query as acq;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/approval_counts.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ApprovalCountsReviews;
+use approval_counts_reviews as acr;
+
+/// Who approved a PR, and whether they are a GitHub App.
+type Approver = (String, bool);
+
+#[async_trait]
+impl Producer for ApprovalCounts {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR number"),
+            String::from("Approvals"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Approval counts", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(ApprovalCountsQuery)
+                        .execute_checked(acq::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:merged merged:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            acq::ApprovalCountsQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, acq::ApprovalCountsQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        let author = pr.author.map(|a| a.login);
+
+                        let mut approvers = vec![];
+                        if let Some(reviews) = pr.reviews {
+                            let nodes = reviews.nodes.into_iter().flatten().flatten();
+                            approvers.extend(nodes.filter_map(search_approver));
+                            if reviews.page_info.has_next_page {
+                                approvers.extend(
+                                    remaining_approvers(
+                                        &mut self.graphql,
+                                        &self.org_name,
+                                        repo_name,
+                                        pr.number,
+                                        reviews.page_info.end_cursor,
+                                    )
+                                    .await?,
+                                );
+                            }
+                        }
+                        let approvers: HashSet<String> = approvers
+                            .into_iter()
+                            .filter(|(login, is_app)| !is_bot(login, *is_app, &self.bots))
+                            .map(|(login, _)| login)
+                            .filter(|login| author.as_ref() != Some(login))
+                            .collect();
+
+                        tx.send(vec![
+                            repo_name.clone(),
+                            pr.number.to_string(),
+                            approvers.len().to_string(),
+                        ])
+                        .await?;
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists who approved the PR, starting at `after_cursor` in its reviews.
+async fn remaining_approvers(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> eyre::Result<Vec<Approver>> {
+    let mut approvers = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(ApprovalCountsReviews)
+            .execute_checked(acr::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let reviews = match response_data
+            .repository
+            .and_then(|r| r.pull_request)
+            .and_then(|pr| pr.reviews)
+        {
+            Some(reviews) => reviews,
+            None => break,
+        };
+
+        let nodes = reviews.nodes.into_iter().flatten().flatten();
+        approvers.extend(nodes.filter_map(remaining_approver));
+
+        if reviews.page_info.has_next_page {
+            after_cursor = reviews.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    Ok(approvers)
+}
+
+/// Who left the review, if it is a live approval.
+///
+/// The query only asks for live approvals, but check the state anyway so that a change
+/// to the query can't skew the numbers.
+fn search_approver(
+    review: acq::ApprovalCountsQuerySearchNodesOnPullRequestReviewsNodes,
+) -> Option<Approver> {
+    use acq::ApprovalCountsQuerySearchNodesOnPullRequestReviewsNodesAuthorOn as AuthorOn;
+
+    if !matches!(review.state, acq::PullRequestReviewState::APPROVED) {
+        return None;
+    }
+    let author = review.author?;
+    Some((author.login, matches!(author.on, AuthorOn::Bot)))
+}
+
+/// Same as `search_approver`, for the reviews of `ApprovalCountsReviews`.
+fn remaining_approver(
+    review: acr::ApprovalCountsReviewsRepositoryPullRequestReviewsNodes,
+) -> Option<Approver> {
+    use acr::ApprovalCountsReviewsRepositoryPullRequestReviewsNodesAuthorOn as AuthorOn;
+
+    if !matches!(review.state, acr::PullRequestReviewState::APPROVED) {
+        return None;
+    }
+    let author = review.author?;
+    Some((author.login, matches!(author.on, AuthorOn::Bot)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn review(state: &str, login: &str) -> serde_json::Value {
+        serde_json::json!({
+            "state": state,
+            "author": { "__typename": "User", "login": login },
+        })
+    }
+
+    #[tokio::test]
+    async fn dismissed_approvals_are_not_counted() {
+        let graphql = replaying(
+            "approval-counts",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [{
+                    "__typename": "PullRequest",
+                    "number": 1,
+                    "author": { "__typename": "User", "login": "alice" },
+                    "reviews": {
+                        "pageInfo": { "hasNextPage": false, "endCursor": null },
+                        "nodes": [review("APPROVED", "bob"), review("DISMISSED", "carol")],
+                    },
+                }],
+            } } })],
+        );
+        let producer = ApprovalCounts::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "1", "1"]]);
+    }
+}
@@ -0,0 +1,281 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{hours_between, is_bot};
+
+/// Reports, for each PR merged in the given time period, how many hours passed between
+/// its last approving review and its merge. PRs merged without an approval are skipped.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct ApprovalToMerge {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl ApprovalToMerge {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/approval_to_merge.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ApprovalToMergeQuery;
+use approval_to_merge_query as atm;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/approval_to_merge.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ApprovalToMergeReviews;
+use approval_to_merge_reviews as atmr;
+
+#[async_trait]
+impl Producer for ApprovalToMerge {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR number"),
+            String::from("Hours approved-to-merge"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Approval to merge", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(ApprovalToMergeQuery)
+                        .execute_checked(atm::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:merged merged:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            atm::ApprovalToMergeQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, atm::ApprovalToMergeQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        let merged_at = match pr.merged_at {
+                            Some(merged_at) => merged_at,
+                            None => continue,
+                        };
+
+                        // The query only asks for approving reviews, but check the state
+                        // anyway so that a change to the query can't skew the numbers.
+                        let mut approvals = vec![];
+                        if let Some(reviews) = pr.reviews {
+                            approvals.extend(
+                                reviews
+                                    .nodes
+                                    .into_iter()
+                                    .flatten()
+                                    .flatten()
+                                    .filter(|r| {
+                                        matches!(r.state, atm::PullRequestReviewState::APPROVED)
+                                    })
+                                    .filter_map(|r| r.submitted_at),
+                            );
+                            if reviews.page_info.has_next_page {
+                                approvals.extend(
+                                    remaining_approvals(
+                                        &mut self.graphql,
+                                        &self.org_name,
+                                        repo_name,
+                                        pr.number,
+                                        reviews.page_info.end_cursor,
+                                    )
+                                    .await?,
+                                );
+                            }
+                        }
+                        let last_approval = match approvals.into_iter().max() {
+                            Some(last_approval) => last_approval,
+                            None => continue,
+                        };
+
+                        tx.send(vec![
+                            repo_name.clone(),
+                            pr.number.to_string(),
+                            format!("{:.2}", hours_between(last_approval, merged_at)),
+                        ])
+                        .await?;
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists when the PR's approving reviews were submitted, starting at `after_cursor`.
+async fn remaining_approvals(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> eyre::Result<Vec<DateTime>> {
+    let mut approvals = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(ApprovalToMergeReviews)
+            .execute_checked(atmr::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let reviews = match response_data
+            .repository
+            .and_then(|r| r.pull_request)
+            .and_then(|pr| pr.reviews)
+        {
+            Some(reviews) => reviews,
+            None => break,
+        };
+
+        approvals.extend(
+            reviews
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|r| matches!(r.state, atmr::PullRequestReviewState::APPROVED))
+                .filter_map(|r| r.submitted_at),
+        );
+
+        if reviews.page_info.has_next_page {
+            after_cursor = reviews.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    Ok(approvals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn approved_at(time: &str) -> serde_json::Value {
+        serde_json::json!({ "state": "APPROVED", "submittedAt": time })
+    }
+
+    fn pr(number: i64, merged_at: &str, approvals: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "mergedAt": merged_at,
+            "reviews": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": approvals,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn measures_from_the_last_approval() {
+        let graphql = replaying(
+            "approval-to-merge",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(
+                        1,
+                        "2024-01-03T12:00:00Z",
+                        vec![
+                            approved_at("2024-01-02T09:00:00Z"),
+                            approved_at("2024-01-03T09:00:00Z"),
+                        ],
+                    ),
+                    pr(2, "2024-01-05T10:15:00Z", vec![approved_at("2024-01-05T10:00:00Z")]),
+                    // merged without an approval
+                    pr(3, "2024-01-06T10:00:00Z", vec![]),
+                ],
+            } } })],
+        );
+        let producer = ApprovalToMerge::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust", "1", "3.00"], ["rust", "2", "0.25"]]
+        );
+    }
+}
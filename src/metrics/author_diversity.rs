@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer, TimeBucket};
+use crate::progress::Progress;
+use crate::util::{is_bot, timestamp};
+
+/// Counts the distinct authors of the PRs opened in each calendar month of the
+/// given time period, to show whether the contributor base is growing.
+///
+/// Bots (GitHub Apps, or any of the given `bots`) don't count as authors.
+pub struct AuthorDiversity {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl AuthorDiversity {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/author_diversity.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct AuthorDiversityQuery;
+use author_diversity_query as ad;
+
+#[async_trait]
+impl Producer for AuthorDiversity {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Month"),
+            String::from("Unique authors"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let buckets = TimeBucket::Month.split(
+            timestamp(&self.start_date)?.date_naive(),
+            timestamp(&self.end_date)?.date_naive(),
+        );
+
+        let mut progress = Progress::new("Author diversity", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                for &(first_day, last_day) in &buckets {
+                    let authors = unique_authors(
+                        &mut self.graphql,
+                        &self.org_name,
+                        repo_name,
+                        first_day,
+                        last_day,
+                        &self.bots,
+                    )
+                    .await?;
+                    tx.send(vec![
+                        repo_name.clone(),
+                        TimeBucket::Month.label(first_day),
+                        authors.to_string(),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the distinct authors of the PRs opened in `repo_name` between
+/// `first_day` and `last_day`, inclusive, other than `bots` and GitHub Apps.
+#[throws]
+async fn unique_authors(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    bots: &[String],
+) -> usize {
+    let mut authors = HashSet::new();
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(100);
+        let response_data = graphql
+            .query(AuthorDiversityQuery)
+            .execute_checked(ad::Variables {
+                page_size,
+                query_string: format!(
+                    r#"repo:{org_name}/{repo_name} is:pr created:{first_day}..{last_day}"#,
+                    org_name = org_name,
+                    repo_name = repo_name,
+                    first_day = first_day,
+                    last_day = last_day,
+                ),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            if let ad::AuthorDiversityQuerySearchNodes::PullRequest(pr) = node {
+                // PRs by deleted accounts have no author
+                if let Some(author) = pr.author {
+                    let is_app = matches!(
+                        author.on,
+                        ad::AuthorDiversityQuerySearchNodesOnPullRequestAuthorOn::Bot
+                    );
+                    if !is_bot(&author.login, is_app, bots) {
+                        authors.insert(author.login);
+                    }
+                }
+            }
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    authors.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn prs(authors: &[Option<(&str, &str)>]) -> serde_json::Value {
+        let nodes: Vec<_> = authors
+            .iter()
+            .map(|author| {
+                serde_json::json!({
+                    "__typename": "PullRequest",
+                    "author": author.map(|(typename, login)| {
+                        serde_json::json!({ "__typename": typename, "login": login })
+                    }),
+                })
+            })
+            .collect();
+        serde_json::json!({ "data": { "search": {
+            "pageInfo": { "hasNextPage": false, "endCursor": null },
+            "nodes": nodes,
+        } } })
+    }
+
+    #[tokio::test]
+    async fn counts_unique_authors_per_month() {
+        let graphql = replaying(
+            "author-diversity",
+            &[
+                prs(&[
+                    Some(("User", "alice")),
+                    Some(("User", "bob")),
+                    Some(("User", "alice")),
+                    // a deleted account
+                    None,
+                    Some(("Bot", "dependabot")),
+                ]),
+                prs(&[]),
+                prs(&[Some(("User", "carol"))]),
+            ],
+        );
+        let producer = AuthorDiversity::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-15".parse().unwrap(),
+            "2024-03-10".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "2024-01", "2"],
+                ["rust", "2024-02", "0"],
+                ["rust", "2024-03", "1"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Lists the issues closed in the given time period that were not closed by,
+/// or linked as being closed by, a pull request.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct ClosedWithoutPr {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl ClosedWithoutPr {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = String;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/closed_without_pr.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ClosedIssues;
+use closed_issues as ci;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/closed_without_pr.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ClosedIssueTimeline;
+use closed_issue_timeline as cit;
+
+#[async_trait]
+impl Producer for ClosedWithoutPr {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            String::from("Closed date"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Issues closed without a PR", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(ClosedIssues)
+                        .execute_checked(ci::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue closed:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let issue = match node {
+                            ci::ClosedIssuesSearchNodes::Issue(issue) => issue,
+                            _ => continue,
+                        };
+                        let by_bot = issue.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, ci::ClosedIssuesSearchNodesOnIssueAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+
+                        let timeline = issue.timeline_items;
+                        let mut linked = timeline
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .any(search_item_links_pr);
+
+                        // Only walk the rest of a long timeline if the first page didn't settle it.
+                        if !linked && timeline.page_info.has_next_page {
+                            linked = remaining_timeline_links_pr(
+                                &mut self.graphql,
+                                &self.org_name,
+                                repo_name,
+                                issue.number,
+                                timeline.page_info.end_cursor,
+                            )
+                            .await?;
+                        }
+
+                        if !linked {
+                            tx.send(vec![
+                                repo_name.clone(),
+                                issue.number.to_string(),
+                                issue.closed_at.unwrap_or_default(),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Walks the issue's timeline starting at `after_cursor`, returning whether
+/// any remaining event ties the issue to a closing pull request.
+async fn remaining_timeline_links_pr(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> eyre::Result<bool> {
+    loop {
+        let response_data = graphql
+            .query(ClosedIssueTimeline)
+            .execute_checked(cit::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let timeline = match response_data.repository.and_then(|r| r.issue) {
+            Some(issue) => issue.timeline_items,
+            None => return Ok(false),
+        };
+
+        let linked = timeline
+            .nodes
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(timeline_item_links_pr);
+        if linked {
+            return Ok(true);
+        }
+
+        if timeline.page_info.has_next_page {
+            after_cursor = timeline.page_info.end_cursor;
+        } else {
+            return Ok(false);
+        }
+    }
+}
+
+/// Whether a timeline event ties the issue to a pull request that closed it.
+fn search_item_links_pr(item: ci::ClosedIssuesSearchNodesOnIssueTimelineItemsNodes) -> bool {
+    use ci::ClosedIssuesSearchNodesOnIssueTimelineItemsNodes as Item;
+    use ci::ClosedIssuesSearchNodesOnIssueTimelineItemsNodesOnClosedEventCloser as Closer;
+    use ci::ClosedIssuesSearchNodesOnIssueTimelineItemsNodesOnCrossReferencedEventSource as Source;
+
+    match item {
+        Item::ClosedEvent(e) => matches!(e.closer, Some(Closer::PullRequest)),
+        Item::CrossReferencedEvent(e) => {
+            e.will_close_target && matches!(e.source, Source::PullRequest)
+        }
+        _ => false,
+    }
+}
+
+/// Same as `search_item_links_pr`, for the events of `ClosedIssueTimeline`.
+fn timeline_item_links_pr(item: cit::ClosedIssueTimelineRepositoryIssueTimelineItemsNodes) -> bool {
+    use cit::ClosedIssueTimelineRepositoryIssueTimelineItemsNodes as Item;
+    use cit::ClosedIssueTimelineRepositoryIssueTimelineItemsNodesOnClosedEventCloser as Closer;
+    use cit::ClosedIssueTimelineRepositoryIssueTimelineItemsNodesOnCrossReferencedEventSource as Source;
+
+    match item {
+        Item::ClosedEvent(e) => matches!(e.closer, Some(Closer::PullRequest)),
+        Item::CrossReferencedEvent(e) => {
+            e.will_close_target && matches!(e.source, Source::PullRequest)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn closed_by(closer: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "ClosedEvent",
+            "closer": closer.map(|typename| serde_json::json!({ "__typename": typename })),
+        })
+    }
+
+    fn closing_reference() -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "CrossReferencedEvent",
+            "willCloseTarget": true,
+            "source": { "__typename": "PullRequest" },
+        })
+    }
+
+    fn timeline(events: Vec<serde_json::Value>, end_cursor: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+            "nodes": events,
+        })
+    }
+
+    fn issue(number: u64, timeline: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "Issue",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "closedAt": format!("2024-01-{:02}T12:00:00Z", number),
+            "timelineItems": timeline,
+        })
+    }
+
+    #[tokio::test]
+    async fn lists_only_issues_closed_by_hand() {
+        let graphql = replaying(
+            "closed-without-pr",
+            &[
+                serde_json::json!({ "data": { "search": {
+                    "pageInfo": { "hasNextPage": false, "endCursor": null },
+                    "nodes": [
+                        issue(1, timeline(vec![closed_by(Some("PullRequest"))], None)),
+                        issue(2, timeline(vec![closed_by(None)], None)),
+                        // the PR that closed it is only on the next page of its timeline
+                        issue(3, timeline(vec![closed_by(None)], Some("events-2"))),
+                    ],
+                } } }),
+                serde_json::json!({ "data": { "repository": { "issue": {
+                    "timelineItems": timeline(vec![closing_reference()], None),
+                } } } }),
+            ],
+        );
+        let producer = ClosedWithoutPr::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust", "2", "2024-01-02T12:00:00Z"]]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre::{self, Error};
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, timestamp};
+
+/// Exports every comment made on an issue or PR in the given time period, one row
+/// each with its full text, e.g. for sentiment analysis. Nothing is aggregated or
+/// redacted.
+///
+/// Comments by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct CommentExport {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl CommentExport {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/comments.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct CommentsQuery;
+use comments_query as cq;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/comments.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct CommentsPage;
+use comments_page as cp;
+
+/// A comment, from either query.
+struct Comment {
+    created_at: DateTime,
+    /// `None` for a deleted account.
+    author: Option<String>,
+    by_bot: bool,
+    body: String,
+}
+
+impl From<cq::CommentFields> for Comment {
+    fn from(comment: cq::CommentFields) -> Self {
+        Comment {
+            created_at: comment.created_at,
+            by_bot: comment
+                .author
+                .as_ref()
+                .is_some_and(|a| matches!(a.on, cq::CommentFieldsAuthorOn::Bot)),
+            author: comment.author.map(|a| a.login),
+            body: comment.body,
+        }
+    }
+}
+
+impl From<cp::CommentFields> for Comment {
+    fn from(comment: cp::CommentFields) -> Self {
+        Comment {
+            created_at: comment.created_at,
+            by_bot: comment
+                .author
+                .as_ref()
+                .is_some_and(|a| matches!(a.on, cp::CommentFieldsAuthorOn::Bot)),
+            author: comment.author.map(|a| a.login),
+            body: comment.body,
+        }
+    }
+}
+
+#[async_trait]
+impl Producer for CommentExport {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Number"),
+            String::from("Kind"),
+            String::from("Author"),
+            String::from("Created"),
+            String::from("Body"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let start = timestamp(&self.start_date)?.date_naive();
+        let end = timestamp(&self.end_date)?.date_naive();
+
+        let mut progress = Progress::new("Comment export", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    // Anything commented on in the time period was updated since it started.
+                    let response_data = self
+                        .graphql
+                        .query(CommentsQuery)
+                        .execute_checked(cq::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} created:<={end_date} updated:>={start_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let (number, kind, comments) = match node {
+                            cq::CommentsQuerySearchNodes::Issue(issue) => {
+                                (issue.number, "issue", issue.comments)
+                            }
+                            cq::CommentsQuerySearchNodes::PullRequest(pr) => {
+                                (pr.number, "pr", pr.comments)
+                            }
+                            _ => continue,
+                        };
+
+                        let mut all_comments: Vec<Comment> = comments
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .map(Comment::from)
+                            .collect();
+                        if comments.page_info.has_next_page {
+                            all_comments.extend(
+                                remaining_comments(
+                                    &mut self.graphql,
+                                    &self.org_name,
+                                    repo_name,
+                                    number,
+                                    comments.page_info.end_cursor,
+                                )
+                                .await?,
+                            );
+                        }
+
+                        for comment in all_comments {
+                            let created = comment.created_at.date_naive();
+                            if created < start || created > end {
+                                continue;
+                            }
+                            let author = comment.author.unwrap_or_default();
+                            if is_bot(&author, comment.by_bot, &self.bots) {
+                                continue;
+                            }
+                            tx.send(vec![
+                                repo_name.clone(),
+                                number.to_string(),
+                                kind.to_string(),
+                                author,
+                                comment.created_at.to_rfc3339(),
+                                comment.body,
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the comments on the issue or PR, starting at `after_cursor` in its comments.
+#[throws]
+async fn remaining_comments(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> Vec<Comment> {
+    let mut comments = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(CommentsPage)
+            .execute_checked(cp::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let page = match response_data
+            .repository
+            .and_then(|r| r.issue_or_pull_request)
+        {
+            Some(cp::CommentsPageRepositoryIssueOrPullRequest::Issue(issue)) => issue.comments,
+            Some(cp::CommentsPageRepositoryIssueOrPullRequest::PullRequest(pr)) => pr.comments,
+            None => break,
+        };
+
+        comments.extend(
+            page.nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(Comment::from),
+        );
+
+        if page.page_info.has_next_page {
+            after_cursor = page.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn comments(comments: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "pageInfo": { "hasNextPage": false, "endCursor": null },
+            "nodes": comments,
+        })
+    }
+
+    fn comment(login: &str, created_at: &str, body: &str) -> serde_json::Value {
+        serde_json::json!({
+            "createdAt": created_at,
+            "body": body,
+            "author": { "__typename": "User", "login": login },
+        })
+    }
+
+    #[tokio::test]
+    async fn exports_each_comment_in_the_time_period() {
+        let graphql = replaying(
+            "comment-export",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    {
+                        "__typename": "Issue",
+                        "number": 3,
+                        "comments": comments(vec![
+                            // before the time period
+                            comment("alice", "2023-12-31T23:00:00Z", "Happy new year"),
+                            comment("bob", "2024-01-02T10:00:00Z", "Can't reproduce"),
+                        ]),
+                    },
+                    {
+                        "__typename": "PullRequest",
+                        "number": 4,
+                        "comments": comments(vec![comment(
+                            "carol",
+                            "2024-01-05T08:00:00Z",
+                            "r? @alice\nThanks, \"LGTM\"",
+                        )]),
+                    },
+                ],
+            } } })],
+        );
+        let producer = CommentExport::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                [
+                    "rust",
+                    "3",
+                    "issue",
+                    "bob",
+                    "2024-01-02T10:00:00+00:00",
+                    "Can't reproduce",
+                ],
+                [
+                    "rust",
+                    "4",
+                    "pr",
+                    "carol",
+                    "2024-01-05T08:00:00+00:00",
+                    "r? @alice\nThanks, \"LGTM\"",
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{util, Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, ranking};
+
+pub struct CommitCounts {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    /// Branch to count commits on, per repository; the default branch is used
+    /// for repositories not listed here.
+    branches: HashMap<String, String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    /// Authors whose commits aren't counted, besides GitHub Apps.
+    bots: Vec<String>,
+}
+
+impl CommitCounts {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        branches: HashMap<String, String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            branches,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[async_trait]
+impl Producer for CommitCounts {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Author"),
+            String::from("Commits"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Commit counts", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let counts = commit_counts(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    self.branches.get(repo_name).map(|b| b.as_str()),
+                    &self.start_date,
+                    &self.end_date,
+                    &self.bots,
+                )
+                .await?;
+
+                for (author, commits) in counts {
+                    tx.send(vec![repo_name.clone(), author, commits.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+type GitTimestamp = String;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/commits.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct Commits;
+use commits as c;
+
+/// Count the commits on `branch` (or the default branch, if `None`) of the given repository
+/// in the given time period, per author.
+///
+/// Commits are attributed to the GitHub login of their author; commits whose author
+/// is not associated with a GitHub user are attributed to the raw git author name.
+/// Commits by `bots`, or by GitHub Apps (whose git author names end in `[bot]`), are
+/// left out.
+///
+/// Returns `(author, commits)` pairs, most commits first.
+#[throws]
+async fn commit_counts(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    branch: Option<&str>,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    bots: &[String],
+) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut after_cursor = None;
+
+    loop {
+        let response_data = graphql
+            .query(Commits)
+            .execute_checked(c::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                // `HEAD` resolves to the tip of the default branch.
+                expression: branch.unwrap_or("HEAD").to_string(),
+                since: util::git_timestamp(start_date),
+                until: util::git_end_timestamp(end_date),
+                after_cursor,
+            })
+            .await?;
+
+        // The repository may be empty, or the branch may not exist.
+        let history = match response_data.repository.and_then(|r| r.object) {
+            Some(c::CommitsRepositoryObject::Commit(commit)) => commit.history,
+            _ => break,
+        };
+
+        for author in history
+            .nodes
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|n| n.author)
+        {
+            let name = match (author.user, author.name) {
+                (Some(user), _) => user.login,
+                (None, Some(name)) => name,
+                (None, None) => continue,
+            };
+            if is_bot(&name, false, bots) {
+                continue;
+            }
+            *counts.entry(name).or_default() += 1;
+        }
+
+        if history.page_info.has_next_page {
+            after_cursor = history.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by_key(|(author, commits)| ranking(*commits, author.clone()));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn page(authors: &[(&str, Option<&str>)], end_cursor: Option<&str>) -> serde_json::Value {
+        let nodes: Vec<_> = authors
+            .iter()
+            .map(|(name, login)| {
+                serde_json::json!({
+                    "author": { "name": name, "user": login.map(|login| serde_json::json!({ "login": login })) }
+                })
+            })
+            .collect();
+        serde_json::json!({ "data": { "repository": { "object": {
+            "__typename": "Commit",
+            "history": {
+                "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+                "nodes": nodes,
+            },
+        } } } })
+    }
+
+    #[tokio::test]
+    async fn counts_commits_over_several_pages() {
+        let graphql = replaying(
+            "commit-counts",
+            &[
+                page(
+                    &[
+                        ("Alice", Some("alice")),
+                        ("Jane Doe", None),
+                        ("bors", Some("bors")),
+                    ],
+                    Some("page-2"),
+                ),
+                page(
+                    &[("Alice Smith", Some("alice")), ("dependabot[bot]", None)],
+                    None,
+                ),
+            ],
+        );
+        let producer = CommitCounts::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            HashMap::new(),
+            "2024-01-01".parse().unwrap(),
+            "2024-02-01".parse().unwrap(),
+            vec!["bors".to_string()],
+        );
+
+        assert_eq!(
+            rows(producer).await,
+            vec![vec!["rust", "alice", "2"], vec!["rust", "Jane Doe", "1"]]
+        );
+    }
+
+    #[test]
+    fn the_last_day_is_included() {
+        assert_eq!(
+            util::git_timestamp(&"2024-02-01".parse().unwrap()),
+            "2024-02-01T00:00:00Z"
+        );
+        assert_eq!(
+            util::git_end_timestamp(&"2024-02-01".parse().unwrap()),
+            "2024-02-02T00:00:00Z"
+        );
+        assert_eq!(
+            util::git_end_timestamp(&"2024-12-31".parse().unwrap()),
+            "2025-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            util::git_end_timestamp(&"2024-02-01T12:30:00Z".parse().unwrap()),
+            "2024-02-01T12:30:00Z"
+        );
+    }
+}
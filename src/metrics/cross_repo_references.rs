@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, ranking};
+
+/// Counts, for the PRs opened in the given time period, the references to them
+/// from issues and PRs in other repositories, to show which repositories depend
+/// on each other's work.
+///
+/// Each row is a source repository (where the reference was made, given with its
+/// owner as it may be outside the organization) and a target repository (where the
+/// referenced PR is). References within a repository are not counted.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct CrossRepoReferences {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl CrossRepoReferences {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/cross_refs.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct CrossRefsQuery;
+use cross_refs_query as cr;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/cross_refs.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct CrossRefsTimeline;
+use cross_refs_timeline as crt;
+
+#[async_trait]
+impl Producer for CrossRepoReferences {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Source repo"),
+            String::from("Target repo"),
+            String::from("Reference count"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Cross-repo references", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let target = format!("{}/{}", self.org_name, repo_name);
+                let mut counts: HashMap<String, u64> = HashMap::new();
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(CrossRefsQuery)
+                        .execute_checked(cr::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            cr::CrossRefsQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, cr::CrossRefsQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        let timeline = pr.timeline_items;
+                        let mut sources: Vec<String> = timeline
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .filter_map(search_source)
+                            .collect();
+                        if timeline.page_info.has_next_page {
+                            sources.extend(
+                                remaining_sources(
+                                    &mut self.graphql,
+                                    &self.org_name,
+                                    repo_name,
+                                    pr.number,
+                                    timeline.page_info.end_cursor,
+                                )
+                                .await?,
+                            );
+                        }
+
+                        for source in sources {
+                            // `isCrossRepository` should rule these out, but make sure
+                            if !source.eq_ignore_ascii_case(&target) {
+                                *counts.entry(source).or_insert(0) += 1;
+                            }
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                let mut counts: Vec<_> = counts.into_iter().collect();
+                counts.sort_by_key(|(source, count)| ranking(*count, source.clone()));
+                for (source, count) in counts {
+                    tx.send(vec![source, target.clone(), count.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the repositories of the cross-references in the PR's timeline, starting at
+/// `after_cursor`.
+async fn remaining_sources(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> eyre::Result<Vec<String>> {
+    let mut sources = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(CrossRefsTimeline)
+            .execute_checked(crt::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let timeline = match response_data.repository.and_then(|r| r.pull_request) {
+            Some(pr) => pr.timeline_items,
+            None => break,
+        };
+
+        let nodes = timeline.nodes.into_iter().flatten().flatten();
+        sources.extend(nodes.filter_map(remaining_source));
+
+        if timeline.page_info.has_next_page {
+            after_cursor = timeline.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    Ok(sources)
+}
+
+/// The repository (`owner/name`) a timeline item refers to the PR from, if it is a
+/// cross-repository reference.
+fn search_source(
+    item: cr::CrossRefsQuerySearchNodesOnPullRequestTimelineItemsNodes,
+) -> Option<String> {
+    use cr::CrossRefsQuerySearchNodesOnPullRequestTimelineItemsNodes as Item;
+    use cr::CrossRefsQuerySearchNodesOnPullRequestTimelineItemsNodesOnCrossReferencedEventSource as Source;
+
+    match item {
+        Item::CrossReferencedEvent(event) if event.is_cross_repository => match event.source {
+            Source::Issue(issue) => Some(issue.repository.name_with_owner),
+            Source::PullRequest(pr) => Some(pr.repository.name_with_owner),
+        },
+        _ => None,
+    }
+}
+
+/// Same as `search_source`, for the timeline items of `CrossRefsTimeline`.
+fn remaining_source(
+    item: crt::CrossRefsTimelineRepositoryPullRequestTimelineItemsNodes,
+) -> Option<String> {
+    use crt::CrossRefsTimelineRepositoryPullRequestTimelineItemsNodes as Item;
+    use crt::CrossRefsTimelineRepositoryPullRequestTimelineItemsNodesOnCrossReferencedEventSource as Source;
+
+    match item {
+        Item::CrossReferencedEvent(event) if event.is_cross_repository => match event.source {
+            Source::Issue(issue) => Some(issue.repository.name_with_owner),
+            Source::PullRequest(pr) => Some(pr.repository.name_with_owner),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn reference(cross_repository: bool, repo: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "CrossReferencedEvent",
+            "isCrossRepository": cross_repository,
+            "source": {
+                "__typename": "Issue",
+                "repository": { "nameWithOwner": repo },
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_a_reference_from_another_repo() {
+        let graphql = replaying(
+            "cross-repo-references",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [{
+                    "__typename": "PullRequest",
+                    "author": { "__typename": "User", "login": "alice" },
+                    "number": 1,
+                    "timelineItems": {
+                        "pageInfo": { "hasNextPage": false, "endCursor": null },
+                        "nodes": [
+                            reference(true, "rust-lang/cargo"),
+                            reference(false, "rust-lang/rust"),
+                        ],
+                    },
+                }],
+            } } })],
+        );
+        let producer = CrossRepoReferences::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust-lang/cargo", "rust-lang/rust", "1"]]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{hours_between, is_bot, median, percentage};
+
+/// Reports how many of the PRs opened in the given time period were opened as drafts,
+/// and the median hours they spent as drafts before being marked ready for review.
+///
+/// A PR counts as opened as a draft if it was later marked ready, or is still a draft.
+/// PRs that are still drafts are left out of the median.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct DraftPrUsage {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl DraftPrUsage {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/draft_prs.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct DraftPrsQuery;
+use draft_prs_query as dpq;
+
+#[async_trait]
+impl Producer for DraftPrUsage {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Total PRs"),
+            String::from("Opened as draft"),
+            String::from("Draft %"),
+            String::from("Median hours in draft"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Draft PR usage", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut total = 0;
+                let mut drafts = 0;
+                let mut hours_in_draft = vec![];
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(DraftPrsQuery)
+                        .execute_checked(dpq::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            dpq::DraftPrsQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, dpq::DraftPrsQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        total += 1;
+
+                        let ready_at = pr
+                            .timeline_items
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .find_map(|item| match item {
+                                dpq::DraftPrsQuerySearchNodesOnPullRequestTimelineItemsNodes::ReadyForReviewEvent(e) => Some(e.created_at),
+                                _ => None,
+                            });
+                        match ready_at {
+                            Some(ready_at) => {
+                                drafts += 1;
+                                hours_in_draft.push(hours_between(pr.created_at, ready_at));
+                            }
+                            None if pr.is_draft => drafts += 1,
+                            None => {}
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                tx.send(vec![
+                    repo_name.clone(),
+                    total.to_string(),
+                    drafts.to_string(),
+                    percentage(drafts, total).to_string(),
+                    median(hours_in_draft)
+                        .map(|hours| format!("{:.1}", hours))
+                        .unwrap_or_default(),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    #[tokio::test]
+    async fn measures_the_time_drafts_took_to_become_ready() {
+        let graphql = replaying(
+            "draft-pr-usage",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    {
+                        "__typename": "PullRequest",
+                        "author": { "__typename": "User", "login": "alice" },
+                        "createdAt": "2024-01-02T10:00:00Z",
+                        "isDraft": false,
+                        "timelineItems": { "nodes": [{
+                            "__typename": "ReadyForReviewEvent",
+                            "createdAt": "2024-01-03T16:00:00Z",
+                        }] },
+                    },
+                    {
+                        "__typename": "PullRequest",
+                        "author": { "__typename": "User", "login": "bob" },
+                        "createdAt": "2024-01-04T10:00:00Z",
+                        "isDraft": false,
+                        "timelineItems": { "nodes": [] },
+                    },
+                ],
+            } } })],
+        );
+        let producer = DraftPrUsage::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "2", "1", "50", "30.0"]]);
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, ranking};
+
+/// Lists the files touched by the most PRs opened in the given time period,
+/// to find code that churns constantly.
+///
+/// PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct FileHotspots {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    top_n: usize,
+    bots: Vec<String>,
+}
+
+impl FileHotspots {
+    /// Lists the `top_n` most-touched files of each repository.
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        top_n: usize,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            top_n,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_files.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrFiles;
+use pr_files as pf;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_files.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrFilesPage;
+use pr_files_page as pfp;
+
+#[async_trait]
+impl Producer for FileHotspots {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Path"),
+            String::from("PRs touching it"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("File hotspots", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut counts: Vec<_> = path_counts(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    &self.bots,
+                )
+                .await?
+                .into_iter()
+                .collect();
+                counts.sort_by_key(|(path, prs)| ranking(*prs, path.clone()));
+                counts.truncate(self.top_n);
+
+                for (path, prs) in counts {
+                    tx.send(vec![repo_name.clone(), path, prs.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts, for each path, how many of the PRs opened in the time period changed it.
+#[throws]
+async fn path_counts(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    bots: &[String],
+) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(50);
+        let response_data = graphql
+            .query(PrFiles)
+            .execute_checked(pf::Variables {
+                page_size,
+                query_string: format!(
+                    r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                    org_name = org_name,
+                    repo_name = repo_name,
+                    start_date = start_date,
+                    end_date = end_date,
+                ),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            let pr = match node {
+                pf::PrFilesSearchNodes::PullRequest(pr) => pr,
+                _ => continue,
+            };
+            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                is_bot(
+                    &a.login,
+                    matches!(a.on, pf::PrFilesSearchNodesOnPullRequestAuthorOn::Bot),
+                    bots,
+                )
+            });
+            if by_bot {
+                continue;
+            }
+            let files = match pr.files {
+                Some(files) => files,
+                None => continue,
+            };
+
+            let mut paths: HashSet<String> = files
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|f| f.path)
+                .collect();
+            if files.page_info.has_next_page {
+                paths.extend(
+                    remaining_paths(
+                        graphql,
+                        org_name,
+                        repo_name,
+                        pr.number,
+                        files.page_info.end_cursor,
+                    )
+                    .await?,
+                );
+            }
+
+            for path in paths {
+                *counts.entry(path).or_insert(0) += 1;
+            }
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    counts
+}
+
+/// Lists the paths changed by the PR, starting at `after_cursor` in its file list.
+#[throws]
+async fn remaining_paths(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> Vec<String> {
+    let mut paths = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(PrFilesPage)
+            .execute_checked(pfp::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let files = match response_data
+            .repository
+            .and_then(|r| r.pull_request)
+            .and_then(|pr| pr.files)
+        {
+            Some(files) => files,
+            None => break,
+        };
+
+        paths.extend(files.nodes.into_iter().flatten().flatten().map(|f| f.path));
+
+        if files.page_info.has_next_page {
+            after_cursor = files.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(number: i64, paths: &[&str]) -> serde_json::Value {
+        let nodes: Vec<_> = paths
+            .iter()
+            .map(|path| serde_json::json!({ "path": path }))
+            .collect();
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "files": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn a_file_changed_by_several_prs_ranks_first() {
+        let graphql = replaying(
+            "file-hotspots",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(1, &["README.md", "src/lib.rs"]),
+                    pr(2, &["src/lib.rs"]),
+                    pr(3, &["src/lib.rs", "Cargo.toml"]),
+                ],
+            } } })],
+        );
+        let producer = FileHotspots::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            2,
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust", "src/lib.rs", "3"], ["rust", "Cargo.toml", "1"]]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{business_hours_between, hours_between, is_bot, BusinessCalendar};
+
+/// Lists, for each issue opened in the given time period, how many hours passed
+/// before someone other than its author first commented on it.
+///
+/// Comments by bots (GitHub Apps, or any of the given `bots`) don't count as responses.
+/// Only the first 100 comments of an issue are considered; issues without a response
+/// among them are skipped.
+///
+/// With `with_business_hours`, only the business hours are counted.
+pub struct FirstResponseTime {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+    business_hours: Option<BusinessCalendar>,
+}
+
+impl FirstResponseTime {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+            business_hours: None,
+        }
+    }
+
+    /// Count only the business hours of `calendar`, leaving out nights, weekends and holidays.
+    pub fn with_business_hours(mut self, calendar: BusinessCalendar) -> Self {
+        self.business_hours = Some(calendar);
+        self
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/first_response.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct FirstResponseQuery;
+use first_response_query as fr;
+
+#[async_trait]
+impl Producer for FirstResponseTime {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            match self.business_hours {
+                Some(_) => String::from("Business hours to first response"),
+                None => String::from("Hours to first response"),
+            },
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("First response time", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(FirstResponseQuery)
+                        .execute_checked(fr::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let issue = match node {
+                            fr::FirstResponseQuerySearchNodes::Issue(issue) => issue,
+                            _ => continue,
+                        };
+                        let issue_author = issue.author.map(|a| a.login);
+
+                        let first_response = issue
+                            .comments
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .find(|comment| match &comment.author {
+                                Some(author) => {
+                                    !is_bot(&author.login, matches!(author.on, fr::FirstResponseQuerySearchNodesOnIssueCommentsNodesAuthorOn::Bot), &self.bots)
+                                        && issue_author.as_deref() != Some(author.login.as_str())
+                                }
+                                // deleted accounts can't be told apart from bots
+                                None => false,
+                            });
+
+                        if let Some(comment) = first_response {
+                            let hours = match &self.business_hours {
+                                Some(calendar) => business_hours_between(
+                                    issue.created_at,
+                                    comment.created_at,
+                                    calendar,
+                                ),
+                                None => hours_between(issue.created_at, comment.created_at),
+                            };
+                            tx.send(vec![
+                                repo_name.clone(),
+                                issue.number.to_string(),
+                                format!("{:.1}", hours),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn comment(typename: &str, login: &str, created_at: &str) -> serde_json::Value {
+        serde_json::json!({
+            "createdAt": created_at,
+            "author": { "__typename": typename, "login": login },
+        })
+    }
+
+    #[tokio::test]
+    async fn bot_comments_are_not_a_response() {
+        let graphql = replaying(
+            "first-response-time",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [{
+                    "__typename": "Issue",
+                    "number": 7,
+                    "createdAt": "2024-01-02T10:00:00Z",
+                    "author": { "__typename": "User", "login": "alice" },
+                    "comments": { "nodes": [
+                        comment("Bot", "triagebot", "2024-01-02T10:01:00Z"),
+                        comment("User", "rustbot", "2024-01-02T10:30:00Z"),
+                        comment("User", "alice", "2024-01-02T11:00:00Z"),
+                        comment("User", "bob", "2024-01-02T13:30:00Z"),
+                    ] },
+                }],
+            } } })],
+        );
+        let producer = FirstResponseTime::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec!["rustbot".to_string()],
+        );
+        assert_eq!(rows(producer).await, [["rust", "7", "3.5"]]);
+    }
+}
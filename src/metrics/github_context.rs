@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre::Error;
+
+use super::Graphql;
+use crate::util::is_bot;
+
+/// Information about who is running the report and what they are running it
+/// against, fetched once and shared (via `Arc`) with any producer that needs it.
+#[derive(Debug)]
+pub struct GithubContext {
+    /// The login of the authenticated user (or app).
+    pub viewer_login: String,
+    /// The organization being analyzed, if the configured login is an organization.
+    pub org: Option<OrgInfo>,
+    /// The logins of the organization's members, if they were fetched.
+    pub members: Option<HashSet<String>>,
+}
+
+#[derive(Debug)]
+pub struct OrgInfo {
+    pub login: String,
+    pub name: Option<String>,
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/github_context.graphql",
+    response_derives = "Serialize,Debug"
+)]
+struct GithubContextQuery;
+use github_context_query as gcq;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/github_context.graphql",
+    response_derives = "Serialize,Debug"
+)]
+struct OrgMembersQuery;
+use org_members_query as omq;
+
+impl GithubContext {
+    /// Queries the viewer and the organization `org_name` in a single request, and
+    /// then the organization's members if `fetch_members`.
+    #[throws]
+    pub async fn load(
+        graphql: &mut Graphql,
+        org_name: &str,
+        fetch_members: bool,
+    ) -> Arc<GithubContext> {
+        let response_data = graphql
+            .query(GithubContextQuery)
+            .execute_checked(gcq::Variables {
+                org_name: org_name.to_string(),
+            })
+            .await?;
+
+        let org = response_data.organization.map(|o| OrgInfo {
+            login: o.login,
+            name: o.name,
+        });
+        let members = match &org {
+            Some(_) if fetch_members => Some(org_members(graphql, org_name).await?),
+            Some(_) => None,
+            None => {
+                if fetch_members {
+                    tracing::warn!(
+                        "{} is not an organization, so it has no members to fetch",
+                        org_name
+                    );
+                }
+                None
+            }
+        };
+
+        Arc::new(GithubContext {
+            viewer_login: response_data.viewer.login,
+            org,
+            members,
+        })
+    }
+
+    /// Whether `login` is `"Internal"` (a member of the organization) or `"External"`;
+    /// blank for `bots`, and for everyone if the members weren't fetched.
+    pub fn affiliation(&self, login: &str, bots: &[String]) -> &'static str {
+        match &self.members {
+            _ if is_bot(login, false, bots) => "",
+            Some(members) if members.contains(login) => "Internal",
+            Some(_) => "External",
+            None => "",
+        }
+    }
+}
+
+/// Lists the logins of the members of the organization `org_name`.
+#[throws]
+async fn org_members(graphql: &mut Graphql, org_name: &str) -> HashSet<String> {
+    let mut members = HashSet::new();
+    let mut after_cursor = None;
+
+    loop {
+        let response_data = graphql
+            .query(OrgMembersQuery)
+            .execute_checked(omq::Variables {
+                org_name: org_name.to_string(),
+                after_cursor,
+            })
+            .await?;
+        let members_with_role = match response_data.organization {
+            Some(org) => org.members_with_role,
+            None => break,
+        };
+
+        members.extend(
+            members_with_role
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|m| m.login),
+        );
+
+        if members_with_role.page_info.has_next_page {
+            after_cursor = members_with_role.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    members
+}
@@ -1,32 +1,201 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use fehler::throws;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::future::{BoxFuture, FutureExt, Shared};
 use graphql_client::{GraphQLQuery, Response};
-use serde::Serialize;
-use stable_eyre::eyre::Error;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use stable_eyre::eyre::{self, Error, WrapErr};
+
+use super::Warnings;
+
+/// The number of attempts made at each query by default, including the first.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// How long each attempt at a query may take by default, from connecting
+/// to reading the whole response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The most results GitHub returns per page of a connection.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// The delay before the first retry; it doubles with each subsequent attempt.
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest we will ever wait between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// A query being sent to GitHub, which every concurrent identical query waits on.
+type InFlight = Shared<BoxFuture<'static, Result<Arc<serde_json::Value>, Arc<Error>>>>;
+
+/// Queries currently being sent to GitHub, keyed by their serialized body,
+/// each with a unique id telling apart successive sends of the same query.
+static IN_FLIGHT: LazyLock<Mutex<HashMap<String, (u64, InFlight)>>> =
+    LazyLock::new(Default::default);
+
+static NEXT_IN_FLIGHT_ID: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Clone, Debug)]
 pub struct Graphql {
     graphql_dir: PathBuf,
-    counter: usize,
+    /// Numbers the saved responses; shared by clones, which save to the same directory.
+    counter: Arc<AtomicUsize>,
     replay: bool,
+    max_attempts: u32,
+    timeout: Duration,
+    compress_replay: bool,
+    cache: Option<ResponseCache>,
+    /// Query documents to send instead of the compiled-in ones, by operation name.
+    query_overrides: Arc<HashMap<String, String>>,
+    warnings: Warnings,
+    /// How many results to fetch per page of a search, if not each query's own default.
+    page_size: Option<u32>,
+}
+
+/// The error for an attempt at a query that GitHub didn't answer in time.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GitHub did not respond within {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// The error of a query, as shared by every identical query that waited on it.
+///
+/// It reads the same as the original, and has the same chain of sources, so that
+/// e.g. a `TimedOut` among them can still be recognized.
+#[derive(Debug)]
+struct SharedError(Arc<Error>);
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Responses saved across runs, so that queries whose answers can't have changed
+/// since are not sent to GitHub again.
+#[derive(Clone, Debug)]
+struct ResponseCache {
+    cache_dir: PathBuf,
+    /// Cached responses saved before this are stale.
+    fresh_after: DateTime<Utc>,
+}
+
+/// A cached response, along with the query it answers and when it was saved.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    query: serde_json::Value,
+    saved_at: DateTime<Utc>,
+    response: serde_json::Value,
 }
 
 impl Graphql {
-    pub fn new(graphql_dir: PathBuf, replay: bool) -> Self {
+    /// # Arguments
+    /// - `graphql_dir` — Where responses are saved to (or replayed from)
+    /// - `replay` — Whether to load saved responses instead of querying GitHub
+    /// - `max_attempts` — How many times to attempt each query when it fails transiently
+    /// - `compress_replay` — Whether to gzip the saved responses; replaying reads
+    ///   either form regardless
+    pub fn new(
+        graphql_dir: PathBuf,
+        replay: bool,
+        max_attempts: u32,
+        compress_replay: bool,
+    ) -> Self {
         Self {
             graphql_dir,
             replay,
-            counter: 0,
+            counter: Default::default(),
+            max_attempts,
+            timeout: DEFAULT_TIMEOUT,
+            compress_replay,
+            cache: None,
+            query_overrides: Default::default(),
+            warnings: Warnings::new(),
+            page_size: None,
         }
     }
 
+    /// Don't use or fill the response cache, e.g. for queries about the present
+    /// rather than about the time span being analyzed.
+    pub fn without_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    /// Give up on each attempt at a query after `timeout`, rather than `DEFAULT_TIMEOUT`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reuse responses cached in `cache_dir` by earlier runs, as long as they were saved
+    /// after `fresh_after` (e.g. the end of the time span being analyzed), and cache new
+    /// ones there. Has no effect when replaying.
+    pub fn with_cache(mut self, cache_dir: PathBuf, fresh_after: DateTime<Utc>) -> Self {
+        self.cache = Some(ResponseCache {
+            cache_dir,
+            fresh_after,
+        });
+        self
+    }
+
+    /// Send the query documents in `query_overrides` (see `load_query_overrides`),
+    /// by operation name, instead of the ones compiled into the binary.
+    pub fn with_query_overrides(mut self, query_overrides: Arc<HashMap<String, String>>) -> Self {
+        self.query_overrides = query_overrides;
+        self
+    }
+
+    /// Record the warnings of producers using this in `warnings`.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Fetch `page_size` results per page of a search, rather than each query's own
+    /// default (e.g. fewer for a short time span); at most `MAX_PAGE_SIZE`.
+    pub fn with_page_size(mut self, page_size: Option<u32>) -> Self {
+        self.page_size = page_size.map(|page_size| page_size.clamp(1, MAX_PAGE_SIZE));
+        self
+    }
+
+    /// How many results to fetch per page of a search whose own default is `default`.
+    pub fn page_size(&self, default: i64) -> i64 {
+        self.page_size.map_or(default, i64::from)
+    }
+
+    /// Where producers using this record anything that makes their output incomplete.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
     /// Used to execute a named query. The `query` argument
     /// should be some struct that has a `[derive(GraphQLQuery)]`
     /// attached to it.
     ///
     /// ```ignore
-    /// config.query(QueryStruct).execute(query_struct::Variables { ... })
+    /// config.query(QueryStruct).execute_checked(query_struct::Variables { ... })
     /// ```
     pub fn query<Q>(&mut self, query: Q) -> GraphqlAttached<'_, Q>
     where
@@ -51,35 +220,693 @@ impl<'me, Q> GraphqlAttached<'me, Q>
 where
     Q: GraphQLQuery,
 {
+    /// Executes the query and returns its data, failing with the messages
+    /// GitHub reported if the response carries any errors or no data at all.
+    #[throws]
+    pub async fn execute_checked(self, variables: Q::Variables) -> Q::ResponseData
+    where
+        Q::ResponseData: Serialize,
+    {
+        response_data(self.execute(variables).await?)?
+    }
+
+    /// Executes the query and returns the raw response, which may contain errors.
     #[throws]
     pub async fn execute(self, variables: Q::Variables) -> Response<Q::ResponseData>
     where
         Q::ResponseData: Serialize,
     {
         let body = Q::build_query(variables);
+        let body = match self.config.query_overrides.get(body.operation_name) {
+            Some(query) => {
+                let mut body = serde_json::to_value(&body)?;
+                body["query"] = serde_json::Value::String(query.clone());
+                body
+            }
+            None => serde_json::to_value(&body)?,
+        };
 
         // get a unique integer for this particular request
-        let count = self.config.counter;
-        self.config.counter += 1;
+        let count = self.config.counter.fetch_add(1, Ordering::SeqCst);
 
         // create the directory and a file within it
         tokio::fs::create_dir_all(&self.config.graphql_dir).await?;
         let path = self.config.graphql_dir.join(format!("{}.json", count));
+        let gz_path = self.config.graphql_dir.join(format!("{}.json.gz", count));
 
         if !self.config.replay {
             // execute query and save the data to the file
-            let response = octocrab::instance().post("graphql", Some(&body)).await?;
-            let response_json = serde_json::to_string(&response)?;
-            tokio::fs::write(&path, response_json.as_bytes()).await?;
+            let response = match &self.config.cache {
+                Some(cache) => {
+                    cache
+                        .get_or_post(&body, self.config.max_attempts, self.config.timeout)
+                        .await?
+                }
+                None => {
+                    post_single_flight(&body, self.config.max_attempts, self.config.timeout).await?
+                }
+            };
+            let response_json = serde_json::to_vec(&response)?;
+            let response = serde_json::from_value(response)?;
+            if self.config.compress_replay {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&response_json)?;
+                tokio::fs::write(&gz_path, encoder.finish()?).await?;
+            } else {
+                tokio::fs::write(&path, response_json).await?;
+            }
             response
-        } else {
+        } else if tokio::fs::metadata(&path).await.is_ok() {
             // if replaying, load the data form the file
-            log::info!(
+            tracing::info!(
                 "loading response data from `{}` rather than github",
                 path.display()
             );
             let response_json = tokio::fs::read(&path).await?;
             serde_json::from_slice(&response_json)?
+        } else {
+            // ...which may have been saved compressed
+            tracing::info!(
+                "loading response data from `{}` rather than github",
+                gz_path.display()
+            );
+            let compressed = tokio::fs::read(&gz_path)
+                .await
+                .wrap_err_with(|| format!("No saved response at {:?} or {:?}", path, gz_path))?;
+            let mut response_json = Vec::new();
+            GzDecoder::new(&compressed[..]).read_to_end(&mut response_json)?;
+            serde_json::from_slice(&response_json)?
+        }
+    }
+}
+
+impl ResponseCache {
+    /// Returns the cached response to `body` if there is a fresh one, and otherwise
+    /// posts it like `post_single_flight`, caching the response if it was successful.
+    async fn get_or_post<B>(
+        &self,
+        body: &B,
+        max_attempts: u32,
+        timeout: Duration,
+    ) -> eyre::Result<serde_json::Value>
+    where
+        B: Serialize,
+    {
+        let key = serde_json::to_string(body)?;
+        let query: serde_json::Value = serde_json::from_str(&key)?;
+        let path = self.cache_dir.join(format!("{:016x}.json", fnv1a(&key)));
+
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            match serde_json::from_slice::<CacheEntry>(&bytes) {
+                // the query is compared as well, in case of a hash collision
+                Ok(entry) if entry.query == query && entry.saved_at > self.fresh_after => {
+                    tracing::debug!("using cached response from `{}`", path.display());
+                    return Ok(entry.response);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(
+                    "ignoring unreadable cached response `{}`: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        let response = post_single_flight(body, max_attempts, timeout).await?;
+        let has_errors = response
+            .get("errors")
+            .is_some_and(|errors| !errors.is_null());
+        if !has_errors {
+            let entry = CacheEntry {
+                query,
+                saved_at: Utc::now(),
+                response,
+            };
+            tokio::fs::create_dir_all(&self.cache_dir).await?;
+            tokio::fs::write(&path, serde_json::to_vec(&entry)?).await?;
+            return Ok(entry.response);
+        }
+        Ok(response)
+    }
+}
+
+/// Loads the `.graphql` files in `dir` (if it exists), by the name of the operation
+/// they define, to be sent instead of the queries compiled into the binary.
+///
+/// A copy of one of the files in the repository's `gql/` directory can be tweaked this way
+/// without rebuilding, but it has to keep the fields the compiled-in query selects.
+#[throws]
+pub fn load_query_overrides(dir: &Path) -> HashMap<String, String> {
+    let mut overrides = HashMap::new();
+    if !dir.exists() {
+        return overrides;
+    }
+    let entries =
+        std::fs::read_dir(dir).wrap_err_with(|| format!("Failed to read directory {:?}", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("graphql") {
+            continue;
+        }
+        let query = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read query from {:?}", path))?;
+        let name = operation_name(&query)
+            .ok_or_else(|| eyre::eyre!("No `query` operation found in {:?}", path))?;
+        tracing::info!("using the query in {:?} for {}", path, name);
+        if overrides.insert(name.to_string(), query.clone()).is_some() {
+            eyre::bail!("More than one query for {} in {:?}", name, dir);
+        }
+    }
+    overrides
+}
+
+/// The name of the (first) `query` operation defined in `document`.
+fn operation_name(document: &str) -> Option<&str> {
+    document.lines().find_map(|line| {
+        let rest = line.trim_start().strip_prefix("query")?;
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        Some(&rest[..end]).filter(|name| !name.is_empty())
+    })
+}
+
+/// The 64-bit FNV-1a hash of `s`, which (unlike `std`'s hashers) is stable across
+/// builds, so it can name cache files.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Posts `body` to GitHub's GraphQL endpoint like `post_with_retry`, except that if an
+/// identical query is already in flight (e.g. from another metric), this waits for
+/// and shares its response rather than sending the query again.
+async fn post_single_flight<B>(
+    body: &B,
+    max_attempts: u32,
+    timeout: Duration,
+) -> eyre::Result<serde_json::Value>
+where
+    B: Serialize,
+{
+    let key = serde_json::to_string(body)?;
+    let (id, in_flight) = {
+        let mut queries = IN_FLIGHT.lock().expect("in-flight query map poisoned");
+        match queries.get(&key) {
+            Some(entry) => entry.clone(),
+            None => {
+                let body: serde_json::Value = serde_json::from_str(&key)?;
+                let in_flight = async move {
+                    post_with_retry(&body, max_attempts, timeout)
+                        .await
+                        .map(Arc::new)
+                        .map_err(Arc::new)
+                }
+                .boxed()
+                .shared();
+                let entry = (NEXT_IN_FLIGHT_ID.fetch_add(1, Ordering::Relaxed), in_flight);
+                queries.insert(key.clone(), entry.clone());
+                entry
+            }
+        }
+    };
+
+    let result = in_flight.await;
+
+    // Once answered, the query is no longer in flight; a later identical query
+    // (e.g. re-fetching the same page) should go to GitHub again.
+    let mut queries = IN_FLIGHT.lock().expect("in-flight query map poisoned");
+    if queries
+        .get(&key)
+        .is_some_and(|(entry_id, _)| *entry_id == id)
+    {
+        queries.remove(&key);
+    }
+    drop(queries);
+
+    match result {
+        Ok(response) => Ok(response.as_ref().clone()),
+        Err(error) => Err(eyre::Error::new(SharedError(error))),
+    }
+}
+
+/// Posts `body` to GitHub's GraphQL endpoint, retrying transient failures
+/// (connection errors, timeouts, 5xx and 429 responses) up to `max_attempts` times
+/// with capped exponential backoff and full jitter.
+///
+/// Each attempt is given up after `timeout`, failing with a `TimedOut` error.
+/// Any other error, such as a 4xx response, fails immediately.
+async fn post_with_retry<B, R>(body: &B, max_attempts: u32, timeout: Duration) -> eyre::Result<R>
+where
+    B: Serialize,
+    R: DeserializeOwned,
+{
+    let octocrab = octocrab::instance();
+    let url = octocrab
+        .absolute_url("graphql")
+        .wrap_err("Failed to build GraphQL URL")?;
+
+    let mut attempt = 1;
+    loop {
+        let error =
+            match tokio::time::timeout(timeout, post_once(&octocrab, url.as_str(), body)).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(Failure::Fatal(error))) => return Err(error),
+                Ok(Err(Failure::Transient(error))) => error,
+                Err(_) => eyre::Error::new(TimedOut { timeout }),
+            };
+
+        if attempt >= max_attempts {
+            return Err(error.wrap_err(format!("GraphQL query failed after {} attempts", attempt)));
+        }
+
+        let delay = retry_delay(attempt);
+        tracing::warn!(
+            "GraphQL query failed (attempt {} of {}), retrying in {:?}: {}",
+            attempt,
+            max_attempts,
+            delay,
+            error
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Why an attempt at posting a query failed.
+enum Failure {
+    /// Worth retrying, e.g. a connection error or a 5xx response.
+    Transient(Error),
+    /// Would fail again, e.g. a 4xx response.
+    Fatal(Error),
+}
+
+/// Makes a single attempt at posting `body` and reading the response.
+async fn post_once<B, R>(octocrab: &octocrab::Octocrab, url: &str, body: &B) -> Result<R, Failure>
+where
+    B: Serialize,
+    R: DeserializeOwned,
+{
+    match octocrab._post(url, Some(body)).await {
+        Ok(response) => {
+            let status = response.status();
+            if status.is_success() {
+                return response
+                    .json()
+                    .await
+                    .wrap_err("Failed to decode GraphQL response")
+                    .map_err(Failure::Fatal);
+            }
+
+            let text = response.text().await.unwrap_or_default();
+            let error = eyre::eyre!("GitHub responded with {}: {}", status, text);
+            if status.is_server_error() || status.as_u16() == 429 {
+                Err(Failure::Transient(error))
+            } else {
+                Err(Failure::Fatal(error))
+            }
+        }
+        Err(octocrab::Error::Http { source, .. })
+            if source.is_connect() || source.is_timeout() || source.is_request() =>
+        {
+            Err(Failure::Transient(eyre::Error::new(source)))
+        }
+        Err(e) => Err(Failure::Fatal(e.into())),
+    }
+}
+
+/// A random delay between zero and the capped exponential backoff for `attempt`.
+fn retry_delay(attempt: u32) -> Duration {
+    let backoff = BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_RETRY_DELAY);
+    backoff.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+/// Extracts the data from a GraphQL response, turning any errors GitHub
+/// reported (or missing data) into an `eyre` error.
+#[throws]
+fn response_data<T>(response: Response<T>) -> T {
+    let errors = response.errors.unwrap_or_default();
+    match response.data {
+        Some(data) if errors.is_empty() => data,
+        data => {
+            let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            eyre::bail!(
+                "GraphQL query failed{}: {}",
+                if data.is_none() { " with no data" } else { "" },
+                if messages.is_empty() {
+                    "no error messages returned".to_string()
+                } else {
+                    messages.join("; ")
+                }
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    /// A stand-in for GitHub's GraphQL endpoint, which answers each query according to
+    /// how it starts (see `respond`) and counts how many times it has been posted.
+    struct StubGithub {
+        posts: Mutex<HashMap<String, u32>>,
+    }
+
+    impl StubGithub {
+        /// Starts the stub, the first time, and points the static octocrab instance at it.
+        fn get() -> &'static StubGithub {
+            static STUB: OnceLock<&'static StubGithub> = OnceLock::new();
+            STUB.get_or_init(|| {
+                let stub: &'static StubGithub = Box::leak(Box::new(StubGithub {
+                    posts: Default::default(),
+                }));
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let url = format!("http://{}/", listener.local_addr().unwrap());
+                std::thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        let stream = stream.unwrap();
+                        std::thread::spawn(move || stub.serve(stream));
+                    }
+                });
+                octocrab::initialise(octocrab::Octocrab::builder().base_url(url).unwrap()).unwrap();
+                stub
+            })
+        }
+
+        /// How many times `query` has been posted.
+        fn posts(&self, query: &str) -> u32 {
+            self.posts.lock().unwrap().get(query).copied().unwrap_or(0)
+        }
+
+        fn serve(&self, stream: std::net::TcpStream) {
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let query = body["query"].as_str().unwrap().to_string();
+
+            let post = {
+                let mut posts = self.posts.lock().unwrap();
+                let post = posts.entry(query.clone()).or_default();
+                *post += 1;
+                *post
+            };
+            let (status, body) = respond(&query, post);
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    }
+
+    /// The response to the `post`th posting of `query`:
+    /// - `fatal ...` always gets a 404
+    /// - `down ...` always gets a 502
+    /// - `flaky ...` gets a 502 the first two times, and then succeeds
+    /// - `hang ...` gets no response for a minute
+    /// - `# respond <json>` (a comment, ahead of a query) gets `<json>` as its response
+    /// - anything else succeeds, after a moment, with data saying which posting it was
+    fn respond(query: &str, post: u32) -> (&'static str, String) {
+        if query.starts_with("hang") {
+            std::thread::sleep(Duration::from_secs(60));
+        }
+        if query.starts_with("fatal") {
+            return ("404 Not Found", r#"{"message":"Not Found"}"#.to_string());
         }
+        if query.starts_with("down") || (query.starts_with("flaky") && post <= 2) {
+            return (
+                "502 Bad Gateway",
+                r#"{"message":"Bad Gateway"}"#.to_string(),
+            );
+        }
+        if let Some(rest) = query.strip_prefix("# respond ") {
+            let response = rest.lines().next().unwrap_or_default();
+            return ("200 OK", response.to_string());
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        let data = serde_json::json!({ "data": { "post": post } });
+        ("200 OK", data.to_string())
+    }
+
+    fn query(query: &str) -> serde_json::Value {
+        serde_json::json!({ "query": query })
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let stub = StubGithub::get();
+        let response: serde_json::Value =
+            post_with_retry(&query("flaky retried"), 5, DEFAULT_TIMEOUT)
+                .await
+                .unwrap();
+        assert_eq!(response["data"]["post"], 3);
+        assert_eq!(stub.posts("flaky retried"), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let stub = StubGithub::get();
+        let error = post_with_retry::<_, serde_json::Value>(&query("down"), 3, DEFAULT_TIMEOUT)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("failed after 3 attempts"));
+        assert_eq!(stub.posts("down"), 3);
+    }
+
+    #[tokio::test]
+    async fn fatal_failures_are_not_retried() {
+        let stub = StubGithub::get();
+        let error = post_with_retry::<_, serde_json::Value>(&query("fatal"), 5, DEFAULT_TIMEOUT)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("404"));
+        assert_eq!(stub.posts("fatal"), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_identical_queries_are_sent_once() {
+        let stub = StubGithub::get();
+        let body = query("single flight");
+        let (a, b, c) = tokio::join!(
+            post_single_flight(&body, 1, DEFAULT_TIMEOUT),
+            post_single_flight(&body, 1, DEFAULT_TIMEOUT),
+            post_single_flight(&body, 1, DEFAULT_TIMEOUT),
+        );
+        assert_eq!(a.unwrap()["data"]["post"], 1);
+        assert_eq!(b.unwrap()["data"]["post"], 1);
+        assert_eq!(c.unwrap()["data"]["post"], 1);
+        assert_eq!(stub.posts("single flight"), 1);
+
+        // once answered, the query is sent again
+        let d = post_single_flight(&body, 1, DEFAULT_TIMEOUT).await.unwrap();
+        assert_eq!(d["data"]["post"], 2);
+        assert_eq!(stub.posts("single flight"), 2);
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("optopodi-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn fresh_cached_responses_are_reused() {
+        let stub = StubGithub::get();
+        let cache = ResponseCache {
+            cache_dir: cache_dir("fresh-cache"),
+            fresh_after: Utc::now() - chrono::Duration::days(1),
+        };
+        let body = query("cached fresh");
+        let first = cache.get_or_post(&body, 1, DEFAULT_TIMEOUT).await.unwrap();
+        let second = cache.get_or_post(&body, 1, DEFAULT_TIMEOUT).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(stub.posts("cached fresh"), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_cached_responses_are_refetched() {
+        let stub = StubGithub::get();
+        let cache_dir = cache_dir("stale-cache");
+        let body = query("cached stale");
+        let cache = ResponseCache {
+            cache_dir: cache_dir.clone(),
+            fresh_after: Utc::now() - chrono::Duration::days(1),
+        };
+        let first = cache.get_or_post(&body, 1, DEFAULT_TIMEOUT).await.unwrap();
+        assert_eq!(first["data"]["post"], 1);
+
+        let cache = ResponseCache {
+            cache_dir,
+            fresh_after: Utc::now(),
+        };
+        let second = cache.get_or_post(&body, 1, DEFAULT_TIMEOUT).await.unwrap();
+        assert_eq!(second["data"]["post"], 2);
+        assert_eq!(stub.posts("cached stale"), 2);
+    }
+
+    #[test]
+    fn fnv1a_is_stable() {
+        // the reference values of 64-bit FNV-1a, which name existing cache files
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a("foobar"), 0x85944171f73967e8);
+    }
+
+    #[tokio::test]
+    async fn unanswered_queries_time_out() {
+        let stub = StubGithub::get();
+        let timeout = Duration::from_millis(200);
+        let error = post_single_flight(&query("hang"), 2, timeout)
+            .await
+            .unwrap_err();
+        let timed_out = error
+            .chain()
+            .find_map(|e| e.downcast_ref::<TimedOut>())
+            .expect("a TimedOut error");
+        assert_eq!(timed_out.timeout, timeout);
+        assert!(format!("{:#}", error).contains("failed after 2 attempts"));
+        assert_eq!(stub.posts("hang"), 2);
+    }
+
+    /// A `Graphql` saving to (or replaying from) a fresh directory, which sends
+    /// `# respond <response>` instead of the `CountIssues` query, so that the stub
+    /// answers it with `response`.
+    fn responding(name: &str, replay: bool, compress: bool, response: &str) -> Graphql {
+        let dir = std::env::temp_dir().join(format!("optopodi-{}-{}", name, std::process::id()));
+        if !replay {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let overrides =
+            HashMap::from([("CountIssues".to_string(), format!("# respond {}", response))]);
+        Graphql::new(dir, replay, 1, compress).with_query_overrides(Arc::new(overrides))
+    }
+
+    #[tokio::test]
+    async fn compressed_responses_replay() {
+        let stub = StubGithub::get();
+        let response = r#"{"data":{"search":{"issueCount":7}}}"#;
+        let mut graphql = responding("gzip-replay", false, true, response);
+        let count = super::super::util::CountIssues::query(&mut graphql, "is:pr".to_string())
+            .await
+            .unwrap();
+        assert_eq!(count, 7);
+        assert!(graphql.graphql_dir.join("0.json.gz").exists());
+        assert!(!graphql.graphql_dir.join("0.json").exists());
+
+        let mut graphql = responding("gzip-replay", true, false, response);
+        let count = super::super::util::CountIssues::query(&mut graphql, "is:pr".to_string())
+            .await
+            .unwrap();
+        assert_eq!(count, 7);
+        assert_eq!(stub.posts(&format!("# respond {}", response)), 1);
+    }
+
+    #[tokio::test]
+    async fn graphql_errors_are_errors() {
+        StubGithub::get();
+        let response =
+            r#"{"data":null,"errors":[{"message":"Could not resolve to a Repository"}]}"#;
+        let mut graphql = responding("graphql-errors", false, false, response);
+        let error = super::super::util::CountIssues::query(&mut graphql, "is:pr".to_string())
+            .await
+            .unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.starts_with("GraphQL query failed with no data: "),
+            "{}",
+            message
+        );
+        assert!(
+            message.ends_with("Could not resolve to a Repository"),
+            "{}",
+            message
+        );
+
+        // errors alongside (partial) data are errors too
+        let response =
+            r#"{"data":{"search":{"issueCount":1}},"errors":[{"message":"rate limited"}]}"#;
+        let mut graphql = responding("graphql-partial-errors", false, false, response);
+        let error = super::super::util::CountIssues::query(&mut graphql, "is:pr".to_string())
+            .await
+            .unwrap_err();
+        let message = error.to_string();
+        assert!(message.starts_with("GraphQL query failed: "), "{}", message);
+        assert!(message.ends_with("rate limited"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn query_overrides_replace_the_compiled_in_queries() {
+        let stub = StubGithub::get();
+        let dir = cache_dir("query-overrides");
+        std::fs::create_dir_all(&dir).unwrap();
+        let query = "# respond {\"data\":{\"search\":{\"issueCount\":3}}}\n\
+                     query CountIssues($query_string: String!) {\n\
+                     \x20 search(query: $query_string, type: ISSUE, first: 1) { issueCount }\n\
+                     }\n";
+        std::fs::write(dir.join("count_issues.graphql"), query).unwrap();
+        std::fs::write(dir.join("README.md"), "not a query").unwrap();
+
+        let overrides = load_query_overrides(&dir).unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides["CountIssues"], query);
+
+        let mut graphql = Graphql::new(dir.join("responses"), false, 1, false)
+            .with_query_overrides(Arc::new(overrides));
+        let count = super::super::util::CountIssues::query(&mut graphql, "is:pr".to_string())
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(stub.posts(query), 1);
+    }
+
+    #[test]
+    fn operation_names() {
+        assert_eq!(
+            operation_name("# tweaked\nquery CountIssues($q: String!) {"),
+            Some("CountIssues")
+        );
+        assert_eq!(operation_name("queryX Y {"), None);
+        assert_eq!(operation_name("mutation AddStar {"), None);
+    }
+
+    #[test]
+    fn page_sizes_are_clamped() {
+        let graphql =
+            |page_size| Graphql::new(PathBuf::new(), true, 1, false).with_page_size(page_size);
+        assert_eq!(graphql(None).page_size(50), 50);
+        assert_eq!(graphql(Some(25)).page_size(50), 25);
+        assert_eq!(graphql(Some(100)).page_size(50), 100);
+        assert_eq!(graphql(Some(250)).page_size(50), i64::from(MAX_PAGE_SIZE));
     }
 }
@@ -0,0 +1,257 @@
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Reports the reactions on each issue opened in the given time period,
+/// most-reacted issues first.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct IssueReactions {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl IssueReactions {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/issue_reactions.graphql",
+    response_derives = "Serialize,Debug,PartialEq"
+)]
+pub struct IssueReactionsQuery;
+use issue_reactions_query as irq;
+
+/// The reaction kinds, in the order their columns are emitted.
+const CONTENTS: &[(irq::ReactionContent, &str)] = &[
+    (irq::ReactionContent::THUMBS_UP, "👍"),
+    (irq::ReactionContent::THUMBS_DOWN, "👎"),
+    (irq::ReactionContent::LAUGH, "😄"),
+    (irq::ReactionContent::HOORAY, "🎉"),
+    (irq::ReactionContent::CONFUSED, "😕"),
+    (irq::ReactionContent::HEART, "❤️"),
+    (irq::ReactionContent::ROCKET, "🚀"),
+    (irq::ReactionContent::EYES, "👀"),
+];
+
+struct IssueReactionCounts {
+    number: i64,
+    title: String,
+    total: i64,
+    /// Counts per entry of `CONTENTS`.
+    by_content: Vec<i64>,
+}
+
+#[async_trait]
+impl Producer for IssueReactions {
+    fn column_names(&self) -> Vec<String> {
+        let mut columns = vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            String::from("Title"),
+            String::from("Total reactions"),
+        ];
+        columns.extend(CONTENTS.iter().map(|(_, name)| name.to_string()));
+        columns
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Issue reactions", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let issues = issue_reactions(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    &self.bots,
+                )
+                .await?;
+
+                for issue in issues {
+                    let mut row = vec![
+                        repo_name.clone(),
+                        issue.number.to_string(),
+                        issue.title,
+                        issue.total.to_string(),
+                    ];
+                    row.extend(issue.by_content.iter().map(|c| c.to_string()));
+                    tx.send(row).await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the reactions on every issue opened in the given repository and time period,
+/// sorted by total reactions, descending.
+#[throws]
+async fn issue_reactions(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    bots: &[String],
+) -> Vec<IssueReactionCounts> {
+    let mut issues = vec![];
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(100);
+        let response_data = graphql
+            .query(IssueReactionsQuery)
+            .execute_checked(irq::Variables {
+                page_size,
+                query_string: format!(
+                    r#"repo:{org_name}/{repo_name} is:issue created:{start_date}..{end_date}"#,
+                    org_name = org_name,
+                    repo_name = repo_name,
+                    start_date = start_date,
+                    end_date = end_date,
+                ),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            let issue = match node {
+                irq::IssueReactionsQuerySearchNodes::Issue(issue) => issue,
+                _ => continue,
+            };
+            let by_bot = issue.author.as_ref().is_some_and(|a| {
+                is_bot(
+                    &a.login,
+                    matches!(
+                        a.on,
+                        irq::IssueReactionsQuerySearchNodesOnIssueAuthorOn::Bot
+                    ),
+                    bots,
+                )
+            });
+            if by_bot {
+                continue;
+            }
+
+            let groups = issue.reaction_groups.unwrap_or_default();
+            let by_content = CONTENTS
+                .iter()
+                .map(|(content, _)| {
+                    groups
+                        .iter()
+                        .filter(|g| g.content == *content)
+                        .map(|g| g.users.total_count)
+                        .sum()
+                })
+                .collect();
+
+            issues.push(IssueReactionCounts {
+                number: issue.number,
+                title: issue.title,
+                total: issue.reactions.total_count,
+                by_content,
+            });
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    issues.sort_by_key(|i| (std::cmp::Reverse(i.total), i.number));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn issue(number: u64, groups: &[(&str, u64)]) -> serde_json::Value {
+        let total: u64 = groups.iter().map(|(_, count)| count).sum();
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|(content, count)| {
+                serde_json::json!({ "content": content, "users": { "totalCount": count } })
+            })
+            .collect();
+        serde_json::json!({
+            "__typename": "Issue",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "title": format!("Issue {}", number),
+            "reactions": { "totalCount": total },
+            "reactionGroups": groups,
+        })
+    }
+
+    #[tokio::test]
+    async fn totals_per_issue_most_reacted_first() {
+        let graphql = replaying(
+            "issue-reactions",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    issue(1, &[("THUMBS_UP", 2), ("EYES", 1)]),
+                    issue(2, &[("THUMBS_UP", 5), ("HEART", 3), ("THUMBS_DOWN", 1)]),
+                ],
+            } } })],
+        );
+        let producer = IssueReactions::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "2", "Issue 2", "9", "5", "1", "0", "0", "0", "3", "0", "0"],
+                ["rust", "1", "Issue 1", "3", "2", "0", "0", "0", "0", "0", "0", "1"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use fehler::throws;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{util, Graphql, Producer, TimeBucket};
+use crate::progress::Progress;
+use crate::util::timestamp;
+
+/// Counts the issues opened and closed in each week or month of the given time period,
+/// to show whether a repository is keeping up with incoming issues.
+///
+/// Each issue is counted as opened in the bucket it was created in, and as closed in
+/// the bucket it was closed in, independently of each other: an issue closed in a bucket
+/// may have been opened in an earlier bucket, or before the time period entirely.
+/// The ratio is therefore closed/opened *activity* within a bucket, and may exceed 1.
+pub struct IssueTrend {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bucket: TimeBucket,
+}
+
+impl IssueTrend {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bucket: TimeBucket,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bucket,
+        }
+    }
+}
+
+#[async_trait]
+impl Producer for IssueTrend {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Bucket"),
+            String::from("Opened"),
+            String::from("Closed"),
+            String::from("Ratio"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let buckets = self.bucket.split(
+            timestamp(&self.start_date)?.date_naive(),
+            timestamp(&self.end_date)?.date_naive(),
+        );
+
+        let mut progress = Progress::new("Issue trend", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                for &(first_day, last_day) in &buckets {
+                    let (opened, closed) = count_opened_closed(
+                        &mut self.graphql,
+                        &self.org_name,
+                        repo_name,
+                        first_day,
+                        last_day,
+                    )
+                    .await?;
+                    tx.send(vec![
+                        repo_name.clone(),
+                        self.bucket.label(first_day),
+                        opened.to_string(),
+                        closed.to_string(),
+                        ratio(closed, opened),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the issues created, and separately the issues closed,
+/// between `first_day` and `last_day` inclusive.
+#[throws]
+async fn count_opened_closed(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+) -> (usize, usize) {
+    let first_day: Datetime = first_day.to_string().parse()?;
+    let last_day: Datetime = last_day.to_string().parse()?;
+    let opened = util::count_issues(
+        graphql, org_name, repo_name, &first_day, &last_day, "created",
+    )
+    .await?;
+    let closed = util::count_issues(
+        graphql, org_name, repo_name, &first_day, &last_day, "closed",
+    )
+    .await?;
+    (opened, closed)
+}
+
+/// `closed / opened` to two decimal places, or empty if nothing was opened.
+fn ratio(closed: usize, opened: usize) -> String {
+    if opened == 0 {
+        String::new()
+    } else {
+        format!("{:.2}", closed as f64 / opened as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn count(issue_count: usize) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": { "issueCount": issue_count } } })
+    }
+
+    #[test]
+    fn ratio_is_empty_when_nothing_was_opened() {
+        assert_eq!(ratio(3, 4), "0.75");
+        assert_eq!(ratio(5, 2), "2.50");
+        assert_eq!(ratio(0, 3), "0.00");
+        assert_eq!(ratio(2, 0), "");
+    }
+
+    #[tokio::test]
+    async fn counts_opened_and_closed_per_week() {
+        // 2024-01-03 is a Wednesday, so the first and last weeks are cut short.
+        let graphql = replaying(
+            "issue-trend",
+            &[count(4), count(3), count(0), count(2), count(2), count(2)],
+        );
+        let producer = IssueTrend::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-03".parse().unwrap(),
+            "2024-01-16".parse().unwrap(),
+            TimeBucket::Week,
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "2024-01-03", "4", "3", "0.75"],
+                ["rust", "2024-01-08", "0", "2", ""],
+                ["rust", "2024-01-15", "2", "2", "1.00"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use async_trait::async_trait;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Receiver;
+
+use super::Consumer;
+
+/// Writes all the rows as a single JSON array of objects, keyed by column name in
+/// column order, once the producer has finished.
+pub struct Json<T: 'static + Write + Send> {
+    writer: T,
+}
+
+impl<T: 'static + Write + Send> Json<T> {
+    pub fn new(writer: T) -> Self {
+        Self { writer }
+    }
+}
+
+#[async_trait]
+impl<T: Write + Send> Consumer for Json<T> {
+    async fn consume(
+        mut self,
+        rx: &mut Receiver<Vec<String>>,
+        column_names: Vec<String>,
+    ) -> eyre::Result<()> {
+        let mut rows = vec![];
+        while let Some(entry) = rx.recv().await {
+            let object: serde_json::Map<_, _> = column_names
+                .iter()
+                .cloned()
+                .zip(entry.into_iter().map(serde_json::Value::String))
+                .collect();
+            rows.push(serde_json::Value::Object(object));
+        }
+
+        tokio::task::spawn_blocking(move || {
+            serde_json::to_writer_pretty(&mut self.writer, &rows)?;
+            self.writer.write_all(b"\n")?;
+            self.writer.flush()?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_an_array_of_objects_in_column_order() {
+        let (tx, mut rx) = mpsc::channel(10);
+        for row in [["rust", "2", "bob"], ["cargo", "1", "alice"]] {
+            tx.send(row.iter().map(|v| v.to_string()).collect())
+                .await
+                .unwrap();
+        }
+        drop(tx);
+        let path = std::env::temp_dir().join(format!("optopodi-json-{}", std::process::id()));
+        let column_names = vec![
+            "Repository".to_string(),
+            "PR number".to_string(),
+            "Author".to_string(),
+        ];
+        Json::new(std::fs::File::create(&path).unwrap())
+            .consume(&mut rx, column_names.clone())
+            .await
+            .unwrap();
+
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(
+                row.keys().collect::<Vec<_>>(),
+                column_names.iter().collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(rows[0]["Repository"], "rust");
+        assert_eq!(rows[1]["Author"], "alice");
+    }
+}
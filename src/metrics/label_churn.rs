@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre::{self, Error};
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, timestamp};
+
+/// Counts, for each issue, how many times in the given time period a label was added
+/// and then removed again, a sign of noisy triage. Issues whose labels didn't flap
+/// are left out.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct LabelChurn {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl LabelChurn {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/label_churn.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct LabelChurnQuery;
+use label_churn_query as lc;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/label_churn.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct LabelChurnTimeline;
+use label_churn_timeline as lct;
+
+/// A label being added (`true`) to or removed (`false`) from an issue.
+type LabelEvent = (DateTime, String, bool);
+
+#[async_trait]
+impl Producer for LabelChurn {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            String::from("Add/remove events"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let since = timestamp(&self.start_date)?;
+        let end = timestamp(&self.end_date)?.date_naive();
+
+        let mut progress = Progress::new("Label churn", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    // Anything labeled in the time period was updated since it started.
+                    let response_data = self
+                        .graphql
+                        .query(LabelChurnQuery)
+                        .execute_checked(lc::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue created:<={end_date} updated:>={start_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                            since: Some(since),
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let issue = match node {
+                            lc::LabelChurnQuerySearchNodes::Issue(issue) => issue,
+                            _ => continue,
+                        };
+                        let by_bot = issue.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, lc::LabelChurnQuerySearchNodesOnIssueAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+
+                        let timeline = issue.timeline_items;
+                        let mut events: Vec<LabelEvent> = timeline
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .filter_map(search_label_event)
+                            .collect();
+                        if timeline.page_info.has_next_page {
+                            events.extend(
+                                remaining_label_events(
+                                    &mut self.graphql,
+                                    &self.org_name,
+                                    repo_name,
+                                    issue.number,
+                                    timeline.page_info.end_cursor,
+                                    since,
+                                )
+                                .await?,
+                            );
+                        }
+                        events.retain(|(created_at, _, _)| created_at.date_naive() <= end);
+
+                        let flaps = count_flaps(&events);
+                        if flaps > 0 {
+                            tx.send(vec![
+                                repo_name.clone(),
+                                issue.number.to_string(),
+                                flaps.to_string(),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the label events in the issue's timeline, starting at `after_cursor`.
+#[throws]
+async fn remaining_label_events(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+    since: DateTime,
+) -> Vec<LabelEvent> {
+    let mut events = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(LabelChurnTimeline)
+            .execute_checked(lct::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+                since: Some(since),
+            })
+            .await?;
+        let timeline = match response_data.repository.and_then(|r| r.issue) {
+            Some(issue) => issue.timeline_items,
+            None => break,
+        };
+
+        events.extend(
+            timeline
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(timeline_label_event),
+        );
+
+        if timeline.page_info.has_next_page {
+            after_cursor = timeline.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    events
+}
+
+/// How many times one of the labels was removed after being added, of `events` in
+/// the order they happened.
+fn count_flaps(events: &[LabelEvent]) -> u64 {
+    let mut added: HashMap<&str, bool> = HashMap::new();
+    let mut flaps = 0;
+    for (_, label, is_added) in events {
+        if *is_added {
+            added.insert(label, true);
+        } else if added.insert(label, false) == Some(true) {
+            flaps += 1;
+        }
+    }
+    flaps
+}
+
+/// The label added to or removed from the issue by a timeline event.
+fn search_label_event(
+    item: lc::LabelChurnQuerySearchNodesOnIssueTimelineItemsNodes,
+) -> Option<LabelEvent> {
+    use lc::LabelChurnQuerySearchNodesOnIssueTimelineItemsNodes as Item;
+
+    match item {
+        Item::LabeledEvent(e) => Some((e.created_at, e.label.name, true)),
+        Item::UnlabeledEvent(e) => Some((e.created_at, e.label.name, false)),
+        _ => None,
+    }
+}
+
+/// Same as `search_label_event`, for the events of `LabelChurnTimeline`.
+fn timeline_label_event(
+    item: lct::LabelChurnTimelineRepositoryIssueTimelineItemsNodes,
+) -> Option<LabelEvent> {
+    use lct::LabelChurnTimelineRepositoryIssueTimelineItemsNodes as Item;
+
+    match item {
+        Item::LabeledEvent(e) => Some((e.created_at, e.label.name, true)),
+        Item::UnlabeledEvent(e) => Some((e.created_at, e.label.name, false)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn event(typename: &str, created_at: &str, label: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": typename,
+            "createdAt": created_at,
+            "label": { "name": label },
+        })
+    }
+
+    fn issue(number: i64, timeline: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "Issue",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "timelineItems": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": timeline,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn a_label_added_then_removed_is_churn() {
+        let graphql = replaying(
+            "label-churn",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    issue(
+                        1,
+                        vec![
+                            event("LabeledEvent", "2024-01-02T10:00:00Z", "C-bug"),
+                            event("UnlabeledEvent", "2024-01-03T10:00:00Z", "C-bug"),
+                        ],
+                    ),
+                    // only ever added
+                    issue(
+                        2,
+                        vec![event("LabeledEvent", "2024-01-02T10:00:00Z", "C-bug")],
+                    ),
+                ],
+            } } })],
+        );
+        let producer = LabelChurn::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "1", "1"]]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, ranking};
+
+/// The most labels per issue or PR whose pairs are counted; an item with `n` labels
+/// has `n * (n - 1) / 2` pairs, so the rest are ignored (with a warning).
+const MAX_LABELS_PER_ITEM: usize = 20;
+
+/// Counts, per repository, how often each pair of labels appears together on
+/// the issues and PRs opened in the given time period.
+///
+/// Issues and PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct LabelCooccurrence {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl LabelCooccurrence {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/issue_labels.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct IssueLabelsQuery;
+use issue_labels_query as il;
+
+#[async_trait]
+impl Producer for LabelCooccurrence {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Label A"),
+            String::from("Label B"),
+            String::from("Count"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Label co-occurrence", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut pairs: HashMap<(String, String), u64> = HashMap::new();
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(IssueLabelsQuery)
+                        .execute_checked(il::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        // Issues and PRs have the same fields, but separate types.
+                        let (number, by_bot, labels) = match node {
+                            il::IssueLabelsQuerySearchNodes::Issue(issue) => (
+                                issue.number,
+                                issue.author.is_some_and(|a| {
+                                    let is_app = matches!(
+                                        a.on,
+                                        il::IssueLabelsQuerySearchNodesOnIssueAuthorOn::Bot
+                                    );
+                                    is_bot(&a.login, is_app, &self.bots)
+                                }),
+                                issue.labels.map(|l| {
+                                    let names = l.nodes.into_iter().flatten().flatten();
+                                    (l.page_info.has_next_page, names.map(|l| l.name).collect())
+                                }),
+                            ),
+                            il::IssueLabelsQuerySearchNodes::PullRequest(pr) => (
+                                pr.number,
+                                pr.author.is_some_and(|a| {
+                                    let is_app = matches!(
+                                        a.on,
+                                        il::IssueLabelsQuerySearchNodesOnPullRequestAuthorOn::Bot
+                                    );
+                                    is_bot(&a.login, is_app, &self.bots)
+                                }),
+                                pr.labels.map(|l| {
+                                    let names = l.nodes.into_iter().flatten().flatten();
+                                    (l.page_info.has_next_page, names.map(|l| l.name).collect())
+                                }),
+                            ),
+                            _ => continue,
+                        };
+                        if by_bot {
+                            continue;
+                        }
+                        let (has_next_page, labels): (bool, Vec<String>) = match labels {
+                            Some(labels) => labels,
+                            None => continue,
+                        };
+                        // A page of labels is more than `MAX_LABELS_PER_ITEM`, so the rest
+                        // would be left out anyway.
+                        count_pairs(&mut pairs, labels, |len| {
+                            let len = if has_next_page {
+                                format!("more than {}", len)
+                            } else {
+                                len.to_string()
+                            };
+                            self.graphql.warnings().warn(
+                                Some(repo_name),
+                                format!(
+                                    "#{} has {} labels; only counting pairs of the first {}",
+                                    number, len, MAX_LABELS_PER_ITEM
+                                ),
+                            )
+                        });
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                let mut pairs: Vec<_> = pairs.into_iter().collect();
+                pairs.sort_by_key(|(pair, count)| ranking(*count, pair.clone()));
+                for ((label_a, label_b), count) in pairs {
+                    tx.send(vec![repo_name.clone(), label_a, label_b, count.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds each unordered pair of `labels` to `pairs`, with the labels of each pair
+/// in alphabetical order. Calls `too_many` with the number of labels if there are
+/// more than `MAX_LABELS_PER_ITEM`.
+fn count_pairs(
+    pairs: &mut HashMap<(String, String), u64>,
+    mut labels: Vec<String>,
+    too_many: impl FnOnce(usize),
+) {
+    labels.sort();
+    labels.dedup();
+    if labels.len() > MAX_LABELS_PER_ITEM {
+        too_many(labels.len());
+        labels.truncate(MAX_LABELS_PER_ITEM);
+    }
+    for (i, label_a) in labels.iter().enumerate() {
+        for label_b in &labels[i + 1..] {
+            *pairs.entry((label_a.clone(), label_b.clone())).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn labeled(typename: &str, number: i64, labels: &[&str]) -> serde_json::Value {
+        let nodes: Vec<_> = labels
+            .iter()
+            .map(|name| serde_json::json!({ "name": name }))
+            .collect();
+        serde_json::json!({
+            "__typename": typename,
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "labels": { "pageInfo": { "hasNextPage": false }, "nodes": nodes },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_each_pair_of_labels_once_per_item() {
+        let graphql = replaying(
+            "label-cooccurrence",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    labeled("Issue", 1, &["T-compiler", "C-bug", "P-high"]),
+                    labeled("PullRequest", 2, &["C-bug", "T-compiler"]),
+                    labeled("Issue", 3, &["C-bug"]),
+                ],
+            } } })],
+        );
+        let producer = LabelCooccurrence::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "C-bug", "T-compiler", "2"],
+                ["rust", "C-bug", "P-high", "1"],
+                ["rust", "P-high", "T-compiler", "1"],
+            ]
+        );
+    }
+}
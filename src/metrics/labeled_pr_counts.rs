@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{util, Graphql, LabelMatch, Producer};
+use crate::progress::Progress;
+
+/// Counts the PRs opened in the given time period that carry particular labels
+/// (e.g. "good first issue").
+///
+/// There is one row per label, and, if there are several labels, a final row
+/// counting the PRs with any (or all, per `label_match`) of them.
+pub struct LabeledPrCounts {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    labels: Vec<String>,
+    label_match: LabelMatch,
+}
+
+impl LabeledPrCounts {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        labels: Vec<String>,
+        label_match: LabelMatch,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            labels,
+            label_match,
+        }
+    }
+}
+
+#[async_trait]
+impl Producer for LabeledPrCounts {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Label"),
+            String::from("PR count"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut label_sets: Vec<(String, Vec<String>)> = self
+            .labels
+            .iter()
+            .map(|label| (label.clone(), vec![label.clone()]))
+            .collect();
+        if self.labels.len() > 1 {
+            let separator = match self.label_match {
+                LabelMatch::Any => " OR ",
+                LabelMatch::All => " AND ",
+            };
+            label_sets.push((self.labels.join(separator), self.labels.clone()));
+        }
+
+        let mut progress = Progress::new("Labeled PR counts", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                for (name, labels) in &label_sets {
+                    let count = util::count_labeled_pull_requests(
+                        &mut self.graphql,
+                        &self.org_name,
+                        repo_name,
+                        &self.start_date,
+                        &self.end_date,
+                        labels,
+                        self.label_match,
+                    )
+                    .await?;
+                    tx.send(vec![repo_name.clone(), name.clone(), count.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn count(issue_count: usize) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": { "issueCount": issue_count } } })
+    }
+
+    #[tokio::test]
+    async fn counts_each_label_and_then_the_combination() {
+        let graphql = replaying("labeled-pr-counts", &[count(5), count(3), count(2)]);
+        let producer = LabeledPrCounts::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec!["E-easy".to_string(), "A-docs".to_string()],
+            LabelMatch::All,
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "E-easy", "5"],
+                ["rust", "A-docs", "3"],
+                ["rust", "E-easy AND A-docs", "2"],
+            ]
+        );
+    }
+}
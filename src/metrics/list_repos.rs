@@ -1,12 +1,16 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use fehler::throws;
 use futures::future::try_join;
-use log::debug;
 use stable_eyre::eyre::{self, Error};
 use tokio::sync::mpsc::Sender;
 use toml::value::Datetime;
+use tracing::debug;
+use tracing::Instrument;
 
 use super::{util, Graphql, Producer};
+use crate::progress::Progress;
 
 #[derive(Debug)]
 pub struct ListReposForOrg {
@@ -15,6 +19,7 @@ pub struct ListReposForOrg {
     repo_names: Vec<String>,
     start_date: Datetime,
     end_date: Datetime,
+    org_wide: bool,
 }
 
 impl ListReposForOrg {
@@ -31,8 +36,18 @@ impl ListReposForOrg {
             repo_names,
             start_date,
             end_date,
+            org_wide: false,
         }
     }
+
+    /// Count with a few searches across the whole organization, rather than a few
+    /// per repository, which for an organization with many repositories is far fewer
+    /// queries. Searches matching more results than GitHub will return fall back to
+    /// counting per repository.
+    pub fn org_wide(mut self) -> Self {
+        self.org_wide = true;
+        self
+    }
 }
 
 impl ListReposForOrg {
@@ -56,33 +71,122 @@ impl Producer for ListReposForOrg {
             String::from("PRs Opened"),
             String::from("Issues Opened"),
             String::from("Issues Closed"),
+            String::from("Stale PRs"),
             String::from("Start Date"),
             String::from("End Date"),
         ]
     }
 
     async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Repo infos", self.repo_names.len());
+
+        if self.org_wide {
+            if let Some(counts) = self.org_wide_counts().await? {
+                for repo_name in &self.repo_names {
+                    let count = |counts: &HashMap<String, usize>| {
+                        counts.get(repo_name).copied().unwrap_or(0).to_string()
+                    };
+                    tx.send(vec![
+                        self.org_name.clone(),
+                        repo_name.to_owned(),
+                        count(&counts.prs),
+                        count(&counts.opened),
+                        count(&counts.closed),
+                        count(&counts.stale),
+                        self.start_date.to_string(),
+                        self.end_date.to_string(),
+                    ])
+                    .await?;
+                    progress.inc(repo_name);
+                }
+                return Ok(());
+            }
+            tracing::info!(
+                "too many results to count across {}; counting per repository",
+                self.org_name
+            );
+        }
+
         for repo_name in &self.repo_names {
-            let mut repo = self.to_repo(repo_name);
-            let count_prs = repo.count_pulls().await?;
-            let count_issues = repo.count_issue_closures().await?;
-
-            tx.send(vec![
-                self.org_name.clone(),
-                repo_name.to_owned(),
-                count_prs.to_string(),
-                count_issues.opened.to_string(),
-                count_issues.closed.to_string(),
-                self.start_date.to_string(),
-                self.end_date.to_string(),
-            ])
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut repo = self.to_repo(repo_name);
+                let count_prs = repo.count_pulls().await?;
+                let count_issues = repo.count_issue_closures().await?;
+                let count_stale = repo.count_stale_pulls().await?;
+
+                tx.send(vec![
+                    self.org_name.clone(),
+                    repo_name.to_owned(),
+                    count_prs.to_string(),
+                    count_issues.opened.to_string(),
+                    count_issues.closed.to_string(),
+                    count_stale.to_string(),
+                    self.start_date.to_string(),
+                    self.end_date.to_string(),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
             .await?;
+            progress.inc(repo_name);
         }
 
         Ok(())
     }
 }
 
+impl ListReposForOrg {
+    /// Counts PRs opened, stale PRs, and issues opened and closed in each repository of
+    /// the organization, or `None` if any of the searches matches too many results.
+    #[throws]
+    async fn org_wide_counts(&mut self) -> Option<OrgWideCounts> {
+        let query_string = |kind: &str, state: &str| {
+            format!(
+                r#"org:{org_name} {kind} {state}:{start_date}..{end_date}"#,
+                org_name = self.org_name,
+                kind = kind,
+                state = state,
+                start_date = self.start_date,
+                end_date = self.end_date,
+            )
+        };
+        let (prs, opened, closed) = (
+            query_string("is:pr", "created"),
+            query_string("is:issue", "created"),
+            query_string("is:issue", "closed"),
+        );
+        let stale = format!(
+            "{} updated:<={}",
+            query_string("is:pr is:open", "created"),
+            self.end_date
+        );
+
+        let prs = util::count_by_repo(&mut self.graphql, prs).await?;
+        let opened = util::count_by_repo(&mut self.graphql, opened).await?;
+        let closed = util::count_by_repo(&mut self.graphql, closed).await?;
+        let stale = util::count_by_repo(&mut self.graphql, stale).await?;
+        match (prs, opened, closed, stale) {
+            (Some(prs), Some(opened), Some(closed), Some(stale)) => Some(OrgWideCounts {
+                prs,
+                opened,
+                closed,
+                stale,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Counts per repository name, for a whole organization.
+struct OrgWideCounts {
+    prs: HashMap<String, usize>,
+    opened: HashMap<String, usize>,
+    closed: HashMap<String, usize>,
+    stale: HashMap<String, usize>,
+}
+
 #[derive(Default, Debug)]
 struct IssueClosuresCount {
     opened: usize,
@@ -130,6 +234,19 @@ impl Repo {
         .await?
     }
 
+    #[inline]
+    #[throws]
+    async fn count_stale_pulls(&mut self) -> usize {
+        util::count_stale_pull_requests(
+            &mut self.graphql,
+            &self.org_name,
+            &self.repo_name,
+            &self.start_date,
+            &self.end_date,
+        )
+        .await?
+    }
+
     #[inline]
     #[throws]
     async fn count_issues(&mut self, state: &str) -> usize {
@@ -144,3 +261,101 @@ impl Repo {
         .await?
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn by_repo(issue_count: i64, repos: &[&str], end_cursor: Option<&str>) -> serde_json::Value {
+        let nodes: Vec<_> = repos
+            .iter()
+            .map(
+                |name| serde_json::json!({ "__typename": "Issue", "repository": { "name": name } }),
+            )
+            .collect();
+        serde_json::json!({ "data": { "search": {
+            "issueCount": issue_count,
+            "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+            "nodes": nodes,
+        } } })
+    }
+
+    fn count(issue_count: i64) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": { "issueCount": issue_count } } })
+    }
+
+    fn producer(graphql: Graphql, repos: &[&str]) -> ListReposForOrg {
+        ListReposForOrg::new(
+            graphql,
+            "rust-lang".to_string(),
+            repos.iter().map(|r| r.to_string()).collect(),
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+        )
+        .org_wide()
+    }
+
+    fn row(repo: &str, counts: [&str; 4]) -> Vec<String> {
+        let mut row = vec!["rust-lang".to_string(), repo.to_string()];
+        row.extend(counts.iter().map(|c| c.to_string()));
+        row.extend(["2024-01-01".to_string(), "2024-01-31".to_string()]);
+        row
+    }
+
+    #[tokio::test]
+    async fn counts_org_wide_searches_per_repository() {
+        let graphql = replaying(
+            "list-repos-org-wide",
+            &[
+                // PRs opened, over two pages
+                by_repo(3, &["rust", "cargo"], Some("page-2")),
+                by_repo(3, &["rust"], None),
+                // issues opened, including a repository not being analyzed
+                by_repo(2, &["cargo", "rustup"], None),
+                // issues closed
+                by_repo(1, &["rust"], None),
+                // stale PRs
+                by_repo(0, &[], None),
+            ],
+        );
+        assert_eq!(
+            rows(producer(graphql, &["rust", "cargo"])).await,
+            vec![
+                row("rust", ["2", "0", "1", "0"]),
+                row("cargo", ["1", "1", "0", "0"]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_per_repo_searches_past_the_result_limit() {
+        let graphql = replaying(
+            "list-repos-fallback",
+            &[
+                // too many PRs opened to list, so the org-wide counts are abandoned
+                by_repo(1001, &["rust"], Some("page-2")),
+                by_repo(0, &[], None),
+                by_repo(0, &[], None),
+                by_repo(0, &[], None),
+                // rust: PRs, issues opened, issues closed, stale PRs
+                count(1200),
+                count(40),
+                count(30),
+                count(5),
+                // cargo
+                count(80),
+                count(7),
+                count(6),
+                count(1),
+            ],
+        );
+        assert_eq!(
+            rows(producer(graphql, &["rust", "cargo"])).await,
+            vec![
+                row("rust", ["1200", "40", "30", "5"]),
+                row("cargo", ["80", "7", "6", "1"]),
+            ]
+        );
+    }
+}
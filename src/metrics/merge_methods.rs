@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Counts how the PRs merged in the given time period were merged:
+/// with a merge commit, squashed, or rebased.
+///
+/// GitHub doesn't record the method, so it is inferred from the resulting commit:
+/// - a commit with two parents is a merge commit;
+/// - otherwise, if the PR had several commits, a commit with the same message as the
+///   PR's last commit is that commit rebased, and any other commit is a squash;
+/// - a single-commit PR looks the same whether squashed or rebased, so it is "unknown",
+///   as is any PR whose merge commit is unavailable.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct MergeMethods {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl MergeMethods {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/merge_methods.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct MergeMethodsQuery;
+use merge_methods_query as mm;
+
+#[async_trait]
+impl Producer for MergeMethods {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Method"),
+            String::from("Count"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Merge methods", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut counts: BTreeMap<&'static str, u64> =
+                    ["merge", "squash", "rebase", "unknown"]
+                        .into_iter()
+                        .map(|method| (method, 0))
+                        .collect();
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(MergeMethodsQuery)
+                        .execute_checked(mm::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:merged merged:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        if let mm::MergeMethodsQuerySearchNodes::PullRequest(pr) = node {
+                            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                                let is_app = matches!(a.on, mm::MergeMethodsQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                                is_bot(&a.login, is_app, &self.bots)
+                            });
+                            if by_bot {
+                                continue;
+                            }
+                            *counts.entry(merge_method(pr)).or_insert(0) += 1;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                for (method, count) in counts {
+                    tx.send(vec![repo_name.clone(), method.to_string(), count.to_string()])
+                        .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Infers how `pr` was merged; see `MergeMethods`.
+fn merge_method(pr: mm::MergeMethodsQuerySearchNodesOnPullRequest) -> &'static str {
+    let merge_commit = match pr.merge_commit {
+        Some(commit) => commit,
+        None => return "unknown",
+    };
+    if merge_commit.parents.total_count > 1 {
+        return "merge";
+    }
+    if pr.commits.total_count < 2 {
+        return "unknown";
+    }
+
+    let last_headline = pr
+        .commits
+        .nodes
+        .into_iter()
+        .flatten()
+        .flatten()
+        .next()
+        .map(|c| c.commit.message_headline);
+    match last_headline {
+        Some(headline) if headline == merge_commit.message_headline => "rebase",
+        Some(_) => "squash",
+        None => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(
+        commits: u64,
+        last_headline: &str,
+        merge_headline: &str,
+        parents: u64,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": "alice" },
+            "commits": {
+                "totalCount": commits,
+                "nodes": [{ "commit": { "messageHeadline": last_headline } }],
+            },
+            "mergeCommit": {
+                "messageHeadline": merge_headline,
+                "parents": { "totalCount": parents },
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn tells_a_squash_from_a_merge_commit() {
+        let graphql = replaying(
+            "merge-methods",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(3, "fix typo", "Add the frobnicator (#12)", 1),
+                    pr(2, "address review", "Merge pull request #13", 2),
+                ],
+            } } })],
+        );
+        let producer = MergeMethods::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "merge", "1"],
+                ["rust", "rebase", "0"],
+                ["rust", "squash", "1"],
+                ["rust", "unknown", "0"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{util, Graphql, Producer, TimeBucket};
+use crate::progress::Progress;
+use crate::util::timestamp;
+
+/// Counts the PRs merged in each ISO week (Monday to Sunday) of the given time period.
+///
+/// The first and last weeks only count the days within the time period, so they may be
+/// partial; the week is labeled by its ISO year and number, e.g. `2021-W52`.
+pub struct MergeThroughput {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+}
+
+impl MergeThroughput {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+        }
+    }
+}
+
+#[async_trait]
+impl Producer for MergeThroughput {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Week"),
+            String::from("Merged PRs"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let weeks = TimeBucket::Week.split(
+            timestamp(&self.start_date)?.date_naive(),
+            timestamp(&self.end_date)?.date_naive(),
+        );
+
+        let mut progress = Progress::new("Merge throughput", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                for &(first_day, last_day) in &weeks {
+                    let merged = util::count_merged_pull_requests(
+                        &mut self.graphql,
+                        &self.org_name,
+                        repo_name,
+                        &first_day.to_string().parse()?,
+                        &last_day.to_string().parse()?,
+                    )
+                    .await?;
+                    tx.send(vec![
+                        repo_name.clone(),
+                        util::iso_week_label(first_day),
+                        merged.to_string(),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn count(issue_count: usize) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": { "issueCount": issue_count } } })
+    }
+
+    #[tokio::test]
+    async fn counts_merged_prs_per_iso_week() {
+        let graphql = replaying("merge-throughput", &[count(3), count(0), count(5)]);
+        let producer = MergeThroughput::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2020-12-30".parse().unwrap(),
+            "2021-01-12".parse().unwrap(),
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "2020-W53", "3"],
+                ["rust", "2021-W01", "0"],
+                ["rust", "2021-W02", "5"],
+            ]
+        );
+    }
+}
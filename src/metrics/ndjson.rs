@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use async_trait::async_trait;
+use stable_eyre::eyre;
+use stable_eyre::eyre::WrapErr;
+use tokio::sync::mpsc::Receiver;
+
+use super::Consumer;
+
+/// Writes each row as a single-line JSON object, keyed by column name in column order,
+/// as soon as it is received (JSON Lines), so that output can be piped into other tools
+/// while a producer is still running.
+pub struct NdJson<T: 'static + Write + Send> {
+    writer: T,
+}
+
+impl<T: 'static + Write + Send> NdJson<T> {
+    pub fn new(writer: T) -> Self {
+        Self { writer }
+    }
+}
+
+#[async_trait]
+impl<T: Write + Send> Consumer for NdJson<T> {
+    async fn consume(
+        mut self,
+        rx: &mut Receiver<Vec<String>>,
+        column_names: Vec<String>,
+    ) -> eyre::Result<()> {
+        let mut row_index: usize = 1;
+
+        while let Some(entry) = rx.recv().await {
+            let object: serde_json::Map<_, _> = column_names
+                .iter()
+                .cloned()
+                .zip(entry.into_iter().map(serde_json::Value::String))
+                .collect();
+            self.writer = write_line_not_blocking(self.writer, object)
+                .await
+                .wrap_err(format!("Failed to output {}-th entry", row_index))?;
+            row_index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `object` and a newline to `writer` and flushes it, on a blocking thread.
+async fn write_line_not_blocking<T>(
+    mut writer: T,
+    object: serde_json::Map<String, serde_json::Value>,
+) -> eyre::Result<T>
+where
+    T: 'static + Write + Send,
+{
+    tokio::task::spawn_blocking(move || {
+        serde_json::to_writer(&mut writer, &object)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(writer)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_one_object_per_line_in_column_order() {
+        let (tx, mut rx) = mpsc::channel(10);
+        for row in [["rust", "2", "bob"], ["cargo", "1", "alice"]] {
+            tx.send(row.iter().map(|v| v.to_string()).collect())
+                .await
+                .unwrap();
+        }
+        drop(tx);
+        let path = std::env::temp_dir().join(format!("optopodi-ndjson-{}", std::process::id()));
+        let column_names = vec![
+            "Repository".to_string(),
+            "PR number".to_string(),
+            "Author".to_string(),
+        ];
+        NdJson::new(std::fs::File::create(&path).unwrap())
+            .consume(&mut rx, column_names.clone())
+            .await
+            .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Map<String, serde_json::Value>> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(
+                line.keys().collect::<Vec<_>>(),
+                column_names.iter().collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(lines[1]["Author"], "alice");
+        assert_eq!(
+            text.lines().next().unwrap(),
+            r#"{"Repository":"rust","PR number":"2","Author":"bob"}"#
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use chrono::{Datelike, Timelike, Weekday};
+use chrono_tz::Tz;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, percentage, WORKING_HOURS};
+
+/// Measures how much of the PRs opened in the given time period were opened
+/// outside working hours, as an indicator of people working late or at weekends.
+///
+/// A PR is off-hours if it was opened at a weekend, or on a weekday before 9am or
+/// from 5pm on, in the configured timezone (taking daylight saving time into account).
+///
+/// PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct OffHoursActivity {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    timezone: Tz,
+    bots: Vec<String>,
+}
+
+impl OffHoursActivity {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        timezone: Tz,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            timezone,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_created_at.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrCreatedAtQuery;
+use pr_created_at_query as pca;
+
+#[async_trait]
+impl Producer for OffHoursActivity {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR count"),
+            String::from("Off-hours %"),
+            String::from("Weekend %"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Off-hours activity", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut prs = 0;
+                let mut off_hours = 0;
+                let mut weekend = 0;
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(PrCreatedAtQuery)
+                        .execute_checked(pca::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        if let pca::PrCreatedAtQuerySearchNodes::PullRequest(pr) = node {
+                            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                                let is_app = matches!(a.on, pca::PrCreatedAtQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                                is_bot(&a.login, is_app, &self.bots)
+                            });
+                            if by_bot {
+                                continue;
+                            }
+                            let hours = classify(pr.created_at, self.timezone);
+                            prs += 1;
+                            if hours != Hours::Working {
+                                off_hours += 1;
+                            }
+                            if hours == Hours::Weekend {
+                                weekend += 1;
+                            }
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                tx.send(vec![
+                    repo_name.clone(),
+                    prs.to_string(),
+                    percentage(off_hours, prs).to_string(),
+                    percentage(weekend, prs).to_string(),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Hours {
+    Working,
+    /// A weekday, outside working hours.
+    Evening,
+    Weekend,
+}
+
+/// When `time` falls, in the local time of `timezone`.
+fn classify(time: DateTime, timezone: Tz) -> Hours {
+    let local = time.with_timezone(&timezone);
+    match local.weekday() {
+        Weekday::Sat | Weekday::Sun => Hours::Weekend,
+        _ if WORKING_HOURS.contains(&local.hour()) => Hours::Working,
+        _ => Hours::Evening,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn at(time: &str) -> DateTime {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn working_hours_follow_daylight_saving_time() {
+        let berlin = chrono_tz::Europe::Berlin;
+        // Berlin moves from UTC+1 to UTC+2 on 2024-03-31, so the same UTC time
+        // is 8:30 local on the Friday before and 9:30 local on the Monday after.
+        assert_eq!(classify(at("2024-03-29T07:30:00Z"), berlin), Hours::Evening);
+        assert_eq!(classify(at("2024-04-01T07:30:00Z"), berlin), Hours::Working);
+        // 15:30 UTC is 16:30 local before and 17:30 local after.
+        assert_eq!(classify(at("2024-03-29T15:30:00Z"), berlin), Hours::Working);
+        assert_eq!(classify(at("2024-04-01T15:30:00Z"), berlin), Hours::Evening);
+        // 23:30 UTC on a Friday is already Saturday in Berlin.
+        assert_eq!(classify(at("2024-03-29T23:30:00Z"), berlin), Hours::Weekend);
+    }
+
+    #[tokio::test]
+    async fn counts_off_hours_and_weekend_prs() {
+        let opened_at = |time: &str| {
+            serde_json::json!({
+                "__typename": "PullRequest",
+                "author": { "__typename": "User", "login": "alice" },
+                "createdAt": time,
+            })
+        };
+        let graphql = replaying(
+            "off-hours-activity",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    opened_at("2024-04-01T07:30:00Z"),
+                    opened_at("2024-04-01T15:30:00Z"),
+                    opened_at("2024-04-02T10:00:00Z"),
+                    opened_at("2024-04-06T10:00:00Z"),
+                ],
+            } } })],
+        );
+        let producer = OffHoursActivity::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-04-01".parse().unwrap(),
+            "2024-04-30".parse().unwrap(),
+            chrono_tz::Europe::Berlin,
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "4", "50", "25"]]);
+    }
+}
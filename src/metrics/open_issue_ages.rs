@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// The age buckets, in order, each with the age up to which an issue falls in it.
+const BUCKETS: &[(&str, Option<i64>)] = &[
+    ("<1w", Some(7)),
+    ("1-4w", Some(28)),
+    ("1-6mo", Some(182)),
+    (">6mo", None),
+];
+
+/// Counts the issues that are open now by how long they have been open,
+/// to show the age profile of each repository's backlog.
+///
+/// Unlike most metrics this doesn't depend on the report's time period:
+/// ages are measured from when the report is run.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct OpenIssueAges {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    bots: Vec<String>,
+}
+
+impl OpenIssueAges {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/open_issues.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct OpenIssuesQuery;
+use open_issues_query as oi;
+
+#[async_trait]
+impl Producer for OpenIssueAges {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Bucket"),
+            String::from("Count"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let now = Utc::now();
+        let mut progress = Progress::new("Open issue ages", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut counts = vec![0u64; BUCKETS.len()];
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(OpenIssuesQuery)
+                        .execute_checked(oi::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue is:open"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        if let oi::OpenIssuesQuerySearchNodes::Issue(issue) = node {
+                            let by_bot = issue.author.as_ref().is_some_and(|a| {
+                                is_bot(
+                                    &a.login,
+                                    matches!(
+                                        a.on,
+                                        oi::OpenIssuesQuerySearchNodesOnIssueAuthorOn::Bot
+                                    ),
+                                    &self.bots,
+                                )
+                            });
+                            if by_bot {
+                                continue;
+                            }
+                            counts[age_bucket(now - issue.created_at)] += 1;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                for ((bucket, _), count) in BUCKETS.iter().zip(counts) {
+                    tx.send(vec![
+                        repo_name.clone(),
+                        bucket.to_string(),
+                        count.to_string(),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// The index in `BUCKETS` of the bucket for an issue of the given age.
+fn age_bucket(age: Duration) -> usize {
+    BUCKETS
+        .iter()
+        .position(|(_, max_days)| match max_days {
+            Some(days) => age < Duration::days(*days),
+            None => true,
+        })
+        .expect("the last bucket has no upper bound")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    #[test]
+    fn ages_fall_in_the_bucket_they_are_below_the_bound_of() {
+        assert_eq!(age_bucket(Duration::hours(1)), 0);
+        assert_eq!(age_bucket(Duration::days(7) - Duration::seconds(1)), 0);
+        assert_eq!(age_bucket(Duration::days(7)), 1);
+        assert_eq!(age_bucket(Duration::days(28)), 2);
+        assert_eq!(age_bucket(Duration::days(182)), 3);
+        assert_eq!(age_bucket(Duration::days(3650)), 3);
+    }
+
+    #[tokio::test]
+    async fn counts_open_issues_by_age() {
+        let opened_days_ago = |days: i64| {
+            serde_json::json!({
+                "__typename": "Issue",
+                "author": { "__typename": "User", "login": "alice" },
+                "createdAt": (Utc::now() - Duration::days(days)).to_rfc3339(),
+            })
+        };
+        let graphql = replaying(
+            "open-issue-ages",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    opened_days_ago(2),
+                    opened_days_ago(10),
+                    opened_days_ago(20),
+                    opened_days_ago(400),
+                ],
+            } } })],
+        );
+        let producer = OpenIssueAges::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                ["rust", "<1w", "1"],
+                ["rust", "1-4w", "2"],
+                ["rust", "1-6mo", "0"],
+                ["rust", ">6mo", "1"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::review_comment_volume::remaining_review_comments;
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Lists, for each PR merged in the given time period, how much discussion it had:
+/// its conversation comments and, separately, the inline comments in its review threads.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct PrDiscussion {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl PrDiscussion {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_discussion.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrDiscussionQuery;
+use pr_discussion_query as pd;
+
+#[async_trait]
+impl Producer for PrDiscussion {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR number"),
+            String::from("Comments"),
+            String::from("Review comments"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("PR discussion", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(PrDiscussionQuery)
+                        .execute_checked(pd::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:merged merged:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            pd::PrDiscussionQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, pd::PrDiscussionQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+
+                        let threads = pr.review_threads;
+                        let mut review_comments: u64 = threads
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .map(|thread| thread.comments.total_count as u64)
+                            .sum();
+                        if threads.page_info.has_next_page {
+                            review_comments += remaining_review_comments(
+                                &mut self.graphql,
+                                &self.org_name,
+                                repo_name,
+                                pr.number,
+                                threads.page_info.end_cursor,
+                            )
+                            .await?;
+                        }
+
+                        tx.send(vec![
+                            repo_name.clone(),
+                            pr.number.to_string(),
+                            pr.comments.total_count.to_string(),
+                            review_comments.to_string(),
+                        ])
+                        .await?;
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    #[tokio::test]
+    async fn counts_comments_and_review_comments_separately() {
+        let graphql = replaying(
+            "pr-discussion",
+            &[
+                serde_json::json!({ "data": { "search": {
+                    "pageInfo": { "hasNextPage": false, "endCursor": null },
+                    "nodes": [{
+                        "__typename": "PullRequest",
+                        "author": { "__typename": "User", "login": "alice" },
+                        "number": 12,
+                        "comments": { "totalCount": 4 },
+                        "reviewThreads": {
+                            "pageInfo": { "hasNextPage": true, "endCursor": "t1" },
+                            "nodes": [{ "comments": { "totalCount": 3 } }],
+                        },
+                    }],
+                } } }),
+                // the second page of review threads
+                serde_json::json!({ "data": { "repository": { "pullRequest": {
+                    "reviewThreads": {
+                        "pageInfo": { "hasNextPage": false, "endCursor": null },
+                        "nodes": [{ "comments": { "totalCount": 2 } }],
+                    },
+                } } } }),
+            ],
+        );
+        let producer = PrDiscussion::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "12", "4", "5"]]);
+    }
+}
@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Counts how the PRs opened in the given time period turned out: merged,
+/// closed without being merged, or still open.
+///
+/// The state is that of each PR when the report is run, not at the end of the
+/// time period, so a PR that was open then but has since been merged counts as merged.
+/// The abandonment rate is the share of the merged or closed PRs that were closed
+/// without being merged; it is blank if there are none.
+///
+/// PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct PrOutcomes {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl PrOutcomes {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_outcomes.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrOutcomesQuery;
+use pr_outcomes_query as po;
+
+#[async_trait]
+impl Producer for PrOutcomes {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Merged"),
+            String::from("Closed unmerged"),
+            String::from("Still open"),
+            String::from("Abandonment rate"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("PR outcomes", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut merged = 0;
+                let mut closed_unmerged = 0;
+                let mut still_open = 0;
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(PrOutcomesQuery)
+                        .execute_checked(po::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        if let po::PrOutcomesQuerySearchNodes::PullRequest(pr) = node {
+                            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                                let is_app = matches!(a.on, po::PrOutcomesQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                                is_bot(&a.login, is_app, &self.bots)
+                            });
+                            if by_bot {
+                                continue;
+                            }
+                            if pr.merged {
+                                merged += 1;
+                            } else if let po::PullRequestState::OPEN = pr.state {
+                                still_open += 1;
+                            } else {
+                                closed_unmerged += 1;
+                            }
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                let resolved = merged + closed_unmerged;
+                let abandonment_rate = if resolved == 0 {
+                    String::new()
+                } else {
+                    format!("{:.2}", closed_unmerged as f64 / resolved as f64)
+                };
+                tx.send(vec![
+                    repo_name.clone(),
+                    merged.to_string(),
+                    closed_unmerged.to_string(),
+                    still_open.to_string(),
+                    abandonment_rate,
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(typename: &str, login: &str, state: &str, merged: bool) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": typename, "login": login },
+            "state": state,
+            "merged": merged,
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_merged_closed_and_open_prs() {
+        let graphql = replaying(
+            "pr-outcomes",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr("User", "alice", "MERGED", true),
+                    pr("User", "bob", "CLOSED", false),
+                    pr("User", "carol", "OPEN", false),
+                ],
+            } } })],
+        );
+        let producer = PrOutcomes::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-02-01".parse().unwrap(),
+            vec![],
+        );
+
+        assert_eq!(
+            rows(producer).await,
+            vec![vec!["rust", "1", "1", "1", "0.50"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_out_prs_by_bots() {
+        let graphql = replaying(
+            "pr-outcomes-bots",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr("User", "alice", "MERGED", true),
+                    pr("Bot", "dependabot", "MERGED", true),
+                    pr("Bot", "renovate", "CLOSED", false),
+                    pr("User", "bors", "OPEN", false),
+                    pr("User", "bob", "CLOSED", false),
+                ],
+            } } })],
+        );
+        let producer = PrOutcomes::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-02-01".parse().unwrap(),
+            vec!["bors".to_string()],
+        );
+
+        assert_eq!(
+            rows(producer).await,
+            vec![vec!["rust", "1", "1", "0", "0.50"]]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+pub struct PrSizes {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    /// Authors left out, besides GitHub Apps.
+    bots: Vec<String>,
+}
+
+impl PrSizes {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_sizes.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrSizesQuery;
+use pr_sizes_query as ps;
+
+#[async_trait]
+impl Producer for PrSizes {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR number"),
+            String::from("Additions"),
+            String::from("Deletions"),
+            String::from("Changed files"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("PR sizes", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(PrSizesQuery)
+                        .execute_checked(ps::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            ps::PrSizesQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, ps::PrSizesQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        tx.send(vec![
+                            repo_name.clone(),
+                            pr.number.to_string(),
+                            pr.additions.to_string(),
+                            pr.deletions.to_string(),
+                            pr.changed_files.to_string(),
+                        ])
+                        .await?;
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(typename: &str, login: &str, number: u64, sizes: [u64; 3]) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": typename, "login": login },
+            "number": number,
+            "additions": sizes[0],
+            "deletions": sizes[1],
+            "changedFiles": sizes[2],
+        })
+    }
+
+    fn search(nodes: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": {
+            "pageInfo": { "hasNextPage": false, "endCursor": null },
+            "nodes": nodes,
+        } } })
+    }
+
+    fn producer(graphql: Graphql) -> PrSizes {
+        PrSizes::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-02-01".parse().unwrap(),
+            vec!["bors".to_string()],
+        )
+    }
+
+    #[tokio::test]
+    async fn extracts_the_size_of_each_pr() {
+        let graphql = replaying(
+            "pr-sizes",
+            &[search(vec![
+                pr("User", "alice", 7, [120, 30, 4]),
+                // e.g. a PR whose commits were all reverted
+                pr("User", "bob", 8, [0, 0, 0]),
+            ])],
+        );
+        assert_eq!(
+            rows(producer(graphql)).await,
+            [
+                ["rust", "7", "120", "30", "4"],
+                ["rust", "8", "0", "0", "0"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_out_prs_by_bots() {
+        let graphql = replaying(
+            "pr-sizes-bots",
+            &[search(vec![
+                pr("User", "alice", 1, [10, 2, 1]),
+                pr("Bot", "dependabot", 2, [10, 2, 1]),
+                pr("User", "bors", 3, [10, 2, 1]),
+                pr("User", "bob", 4, [10, 2, 1]),
+            ])],
+        );
+        let numbers: Vec<String> = rows(producer(graphql))
+            .await
+            .into_iter()
+            .map(|row| row[1].clone())
+            .collect();
+        assert_eq!(numbers, vec!["1", "4"]);
+    }
+}
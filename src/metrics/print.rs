@@ -1,6 +1,7 @@
 use std::io::Write;
 
 use async_trait::async_trait;
+use serde::Deserialize;
 use stable_eyre::eyre;
 use stable_eyre::eyre::WrapErr;
 
@@ -10,16 +11,241 @@ use super::Consumer;
 
 pub struct Print<T: 'static + Write + Send> {
     csv_writer: csv::Writer<T>,
+    options: CsvOptions,
+    /// Whether to write a "Total" row after the data.
+    totals: bool,
+    /// Whether to write the header row (and byte order mark) before the data.
+    header: bool,
+    /// If set, a "Run date" column with this value is prepended to every row.
+    run_date: Option<String>,
+    /// Which columns hold identifiers, if not those named like one (see `is_identifier_column`).
+    identifier_columns: Option<Vec<bool>>,
 }
 
 impl<T: 'static + Write + Send> Print<T> {
+    /// Writes standard, comma-separated CSV to `writer`.
     pub fn new(writer: T) -> Self {
+        Self::with_options(writer, &CsvOptions::default())
+    }
+
+    /// Writes CSV to `writer` using the given delimiter, quoting, line terminator
+    /// and number format.
+    pub fn with_options(writer: T, options: &CsvOptions) -> Self {
         Self {
-            csv_writer: csv::Writer::from_writer(writer),
+            csv_writer: options.writer_builder().from_writer(writer),
+            options: options.clone(),
+            totals: false,
+            header: true,
+            run_date: None,
+            identifier_columns: None,
+        }
+    }
+
+    /// Also writes a "Total" row after the data, summing each column whose values
+    /// are all numbers (or blank). Other columns are left blank in that row.
+    pub fn with_totals(mut self) -> Self {
+        self.totals = true;
+        self
+    }
+
+    /// Appends to a file holding the rows of earlier runs: prepends a "Run date" column
+    /// with `run_date` to every row, and writes the header row only if `new_file`.
+    pub fn appending(mut self, run_date: String, new_file: bool) -> Self {
+        self.run_date = Some(run_date);
+        self.header = new_file;
+        self
+    }
+
+    /// Writes the columns for which `identifier_columns` is true as identifiers (see
+    /// `is_identifier_column`), rather than deciding by their names, e.g. because they
+    /// have been relabeled.
+    pub fn with_identifier_columns(mut self, identifier_columns: Vec<bool>) -> Self {
+        self.identifier_columns = Some(identifier_columns);
+        self
+    }
+
+    /// `record`, after the run date if there is one.
+    fn with_run_date(&self, record: impl IntoIterator<Item = String>) -> Vec<String> {
+        self.run_date.iter().cloned().chain(record).collect()
+    }
+}
+
+/// Whether the values of `column` identify something, like a PR number, rather than
+/// count or measure it. They are written as they are, without grouping their digits
+/// (so they still read back as numbers), and aren't totaled.
+pub fn is_identifier_column(column: &str) -> bool {
+    let column = column.to_lowercase();
+    column == "#" || column == "number" || column.ends_with(" number")
+}
+
+/// The running total of one column.
+#[derive(Clone, Copy)]
+enum ColumnTotal {
+    Integer(i128),
+    Float(f64),
+    NotNumeric,
+}
+
+impl ColumnTotal {
+    /// Adds `value` to the total; blank values are skipped.
+    fn add(self, value: &str) -> Self {
+        if value.is_empty() {
+            return self;
+        }
+        match self {
+            ColumnTotal::Integer(total) => match value.parse::<i128>() {
+                Ok(v) => ColumnTotal::Integer(total + v),
+                Err(_) => ColumnTotal::Float(total as f64).add(value),
+            },
+            ColumnTotal::Float(total) => match value.parse::<f64>() {
+                Ok(v) if v.is_finite() => ColumnTotal::Float(total + v),
+                _ => ColumnTotal::NotNumeric,
+            },
+            ColumnTotal::NotNumeric => ColumnTotal::NotNumeric,
+        }
+    }
+
+    fn to_field(self) -> String {
+        match self {
+            ColumnTotal::Integer(total) => total.to_string(),
+            ColumnTotal::Float(total) => total.to_string(),
+            ColumnTotal::NotNumeric => String::new(),
+        }
+    }
+}
+
+/// The UTF-8 encoding of U+FEFF, which marks a file as UTF-8.
+const BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
+/// How the fields of a CSV file are delimited and quoted.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CsvOptions {
+    /// The field delimiter; must be an ASCII character.
+    pub delimiter: char,
+    pub quote_style: QuoteStyle,
+    pub terminator: Terminator,
+    /// Separates the integer and fractional parts of numbers, e.g. `,` for `1234,5`.
+    pub decimal_separator: char,
+    /// Groups the digits of numbers in thousands, e.g. `.` for `1.234`; no grouping if `None`.
+    /// Identifiers, such as PR numbers, are never grouped.
+    pub thousands_separator: Option<char>,
+    /// Starts each file with a UTF-8 byte order mark, which Excel needs to
+    /// read non-ASCII text (e.g. contributor names) correctly.
+    pub byte_order_mark: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStyle {
+    /// Quote only fields containing delimiters, quotes or line breaks.
+    Necessary,
+    Always,
+    /// Quote every field that doesn't look like a number.
+    NonNumeric,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Terminator {
+    Lf,
+    Crlf,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            quote_style: QuoteStyle::Necessary,
+            terminator: Terminator::Lf,
+            decimal_separator: '.',
+            thousands_separator: None,
+            byte_order_mark: false,
         }
     }
 }
 
+impl CsvOptions {
+    /// Checks that the options can actually be used to write CSV.
+    pub fn validate(&self) -> eyre::Result<()> {
+        if !self.delimiter.is_ascii() {
+            eyre::bail!(
+                "CSV delimiter {:?} is not an ASCII character",
+                self.delimiter
+            );
+        }
+        if self.thousands_separator == Some(self.decimal_separator) {
+            eyre::bail!(
+                "CSV thousands separator and decimal separator are both {:?}",
+                self.decimal_separator
+            );
+        }
+        Ok(())
+    }
+
+    /// Formats `field` with the configured decimal and thousands separators if it is
+    /// a plain number (e.g. `1234.5` or `-3`); any other field is returned unchanged.
+    pub fn format_number(&self, field: String) -> String {
+        if self.decimal_separator == '.' && self.thousands_separator.is_none() {
+            return field;
+        }
+
+        let (sign, digits) = match field.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", field.as_str()),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits, None),
+        };
+        let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if !is_digits(integer) || !fraction.is_none_or(is_digits) {
+            return field;
+        }
+
+        let mut formatted = String::from(sign);
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i).is_multiple_of(3) {
+                formatted.extend(self.thousands_separator);
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push(self.decimal_separator);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+
+    /// A `csv::Writer` configured with these options, writing to `out` after the
+    /// byte order mark, if there is to be one.
+    pub fn writer<W: Write>(&self, mut out: W) -> std::io::Result<csv::Writer<W>> {
+        if self.byte_order_mark {
+            out.write_all(BYTE_ORDER_MARK)?;
+        }
+        Ok(self.writer_builder().from_writer(out))
+    }
+
+    /// A `csv::WriterBuilder` configured with these options.
+    pub fn writer_builder(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter as u8)
+            .quote_style(match self.quote_style {
+                QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+                QuoteStyle::Always => csv::QuoteStyle::Always,
+                QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+                QuoteStyle::Never => csv::QuoteStyle::Never,
+            })
+            .terminator(match self.terminator {
+                Terminator::Lf => csv::Terminator::Any(b'\n'),
+                Terminator::Crlf => csv::Terminator::CRLF,
+            });
+        builder
+    }
+}
+
 #[async_trait]
 impl<T: Write + Send> Consumer for Print<T> {
     async fn consume(
@@ -27,31 +253,77 @@ impl<T: Write + Send> Consumer for Print<T> {
         rx: &mut Receiver<Vec<String>>,
         column_names: Vec<String>,
     ) -> eyre::Result<()> {
-        self.csv_writer = write_record_not_blocking(
-            self.csv_writer,
-            vec!["#".to_string()]
-                .into_iter()
-                .chain(column_names.into_iter())
-                .collect(),
-        )
-        .await
-        .wrap_err("Failed to output columns names")?;
+        let identifier_columns = self.identifier_columns.take().unwrap_or_else(|| {
+            column_names
+                .iter()
+                .map(|name| is_identifier_column(name))
+                .collect()
+        });
+        let mut totals: Vec<_> = (0..column_names.len())
+            .map(|i| match identifier_columns.get(i) {
+                Some(true) => ColumnTotal::NotNumeric,
+                _ => ColumnTotal::Integer(0),
+            })
+            .collect();
 
-        let mut row_index: usize = 1;
+        if self.header {
+            if self.options.byte_order_mark {
+                self.csv_writer =
+                    write_byte_order_mark_not_blocking(self.csv_writer, &self.options)
+                        .await
+                        .wrap_err("Failed to output byte order mark")?;
+            }
 
-        while let Some(entry) = rx.recv().await {
+            let run_date_column = self.run_date.as_ref().map(|_| "Run date".to_string());
             self.csv_writer = write_record_not_blocking(
                 self.csv_writer,
-                vec![row_index.to_string()]
+                run_date_column
                     .into_iter()
-                    .chain(entry.into_iter())
+                    .chain(Some("#".to_string()))
+                    .chain(column_names)
                     .collect(),
             )
             .await
-            .wrap_err(format!("Failed to output {}-th entry", row_index))?;
+            .wrap_err("Failed to output columns names")?;
+        }
+
+        let mut row_index: usize = 1;
+
+        while let Some(entry) = rx.recv().await {
+            if self.totals {
+                for (total, value) in totals.iter_mut().zip(&entry) {
+                    *total = total.add(value);
+                }
+            }
+            let record = self.with_run_date(
+                Some(row_index.to_string())
+                    .into_iter()
+                    .chain(entry.into_iter().enumerate().map(|(i, v)| {
+                        match identifier_columns.get(i) {
+                            Some(true) => v,
+                            _ => self.options.format_number(v),
+                        }
+                    })),
+            );
+            self.csv_writer = write_record_not_blocking(self.csv_writer, record)
+                .await
+                .wrap_err(format!("Failed to output {}-th entry", row_index))?;
             row_index += 1;
         }
 
+        if self.totals {
+            let record = self.with_run_date(
+                Some("Total".to_string()).into_iter().chain(
+                    totals
+                        .into_iter()
+                        .map(|t| self.options.format_number(t.to_field())),
+                ),
+            );
+            self.csv_writer = write_record_not_blocking(self.csv_writer, record)
+                .await
+                .wrap_err("Failed to output totals")?;
+        }
+
         self.csv_writer = flush_not_blocking(self.csv_writer).await?;
 
         Ok(())
@@ -72,6 +344,25 @@ where
     .await?
 }
 
+/// Writes the byte order mark to the file underneath `csv_writer`, which must
+/// not have been written to yet, returning a new writer with the same `options`.
+async fn write_byte_order_mark_not_blocking<T>(
+    csv_writer: csv::Writer<T>,
+    options: &CsvOptions,
+) -> eyre::Result<csv::Writer<T>>
+where
+    T: 'static + Write + Send,
+{
+    let options = options.clone();
+    tokio::task::spawn_blocking(move || {
+        let writer = csv_writer
+            .into_inner()
+            .map_err(|e| eyre::eyre!("{}", e.error()))?;
+        Ok(options.writer(writer)?)
+    })
+    .await?
+}
+
 async fn flush_not_blocking<T>(mut csv_writer: csv::Writer<T>) -> eyre::Result<csv::Writer<T>>
 where
     T: 'static + Write + Send,
@@ -82,3 +373,203 @@ where
     })
     .await?
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn identifiers_are_not_grouped() {
+        let options = CsvOptions {
+            thousands_separator: Some(','),
+            ..CsvOptions::default()
+        };
+        let (tx, mut rx) = mpsc::channel(10);
+        tx.send(vec![
+            "rust".to_string(),
+            "12345".to_string(),
+            "1234".to_string(),
+        ])
+        .await
+        .unwrap();
+        drop(tx);
+        let path = std::env::temp_dir().join(format!("optopodi-print-{}.csv", std::process::id()));
+        let column_names = vec![
+            "Repository".to_string(),
+            "PR number".to_string(),
+            "Additions".to_string(),
+        ];
+        Print::with_options(std::fs::File::create(&path).unwrap(), &options)
+            .with_totals()
+            .consume(&mut rx, column_names)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#,Repository,PR number,Additions\n1,rust,12345,\"1,234\"\nTotal,,,\"1,234\"\n"
+        );
+    }
+
+    /// A path for the output of the test `name`.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "optopodi-print-{}-{}.csv",
+            name,
+            std::process::id()
+        ))
+    }
+
+    /// Has `print` consume `rows` of `column_names`.
+    async fn print_rows(print: Print<std::fs::File>, column_names: &[&str], rows: &[&[&str]]) {
+        let (tx, mut rx) = mpsc::channel(rows.len().max(1));
+        for row in rows {
+            tx.send(row.iter().map(|v| v.to_string()).collect())
+                .await
+                .unwrap();
+        }
+        drop(tx);
+        print
+            .consume(
+                &mut rx,
+                column_names.iter().map(|c| c.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn appending_adds_the_run_date_and_a_single_header() {
+        let path = temp_path("append");
+        let _ = std::fs::remove_file(&path);
+        let open = || {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap()
+        };
+        let columns = ["Repository", "Commits"];
+        print_rows(
+            Print::new(open()).appending("2024-01-31".to_string(), true),
+            &columns,
+            &[&["rust", "3"]],
+        )
+        .await;
+        print_rows(
+            Print::new(open()).appending("2024-02-29".to_string(), false),
+            &columns,
+            &[&["rust", "5"], &["cargo", "1"]],
+        )
+        .await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Run date,#,Repository,Commits\n\
+             2024-01-31,1,rust,3\n\
+             2024-02-29,1,rust,5\n\
+             2024-02-29,2,cargo,1\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_delimiter_and_quoting() {
+        let columns = ["Repository", "Author", "Commits"];
+        let rows: &[&[&str]] = &[&["rust", "Doe; Jane", "3"]];
+
+        let path = temp_path("delimiter");
+        let options = CsvOptions {
+            delimiter: ';',
+            ..CsvOptions::default()
+        };
+        print_rows(
+            Print::with_options(std::fs::File::create(&path).unwrap(), &options),
+            &columns,
+            rows,
+        )
+        .await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#;Repository;Author;Commits\n1;rust;\"Doe; Jane\";3\n"
+        );
+
+        let path = temp_path("quote-always");
+        let options = CsvOptions {
+            quote_style: QuoteStyle::Always,
+            terminator: Terminator::Crlf,
+            ..CsvOptions::default()
+        };
+        print_rows(
+            Print::with_options(std::fs::File::create(&path).unwrap(), &options),
+            &columns,
+            rows,
+        )
+        .await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\"#\",\"Repository\",\"Author\",\"Commits\"\r\n\"1\",\"rust\",\"Doe; Jane\",\"3\"\r\n"
+        );
+
+        let path = temp_path("quote-non-numeric");
+        let options = CsvOptions {
+            quote_style: QuoteStyle::NonNumeric,
+            ..CsvOptions::default()
+        };
+        print_rows(
+            Print::with_options(std::fs::File::create(&path).unwrap(), &options),
+            &columns,
+            rows,
+        )
+        .await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\"#\",\"Repository\",\"Author\",\"Commits\"\n1,\"rust\",\"Doe; Jane\",3\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn totals_sum_the_numeric_columns_in_order() {
+        let path = temp_path("totals");
+        print_rows(
+            Print::new(std::fs::File::create(&path).unwrap()).with_totals(),
+            &["Repository", "PRs", "Median hours", "Notes", "Stale"],
+            &[
+                &["rust", "10", "1.5", "busy", ""],
+                &["cargo", "3", "2", "", "4"],
+            ],
+        )
+        .await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#,Repository,PRs,Median hours,Notes,Stale\n\
+             1,rust,10,1.5,busy,\n\
+             2,cargo,3,2,,4\n\
+             Total,,13,3.5,,4\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn byte_order_mark_only_when_asked_for() {
+        for byte_order_mark in [true, false] {
+            let path = temp_path(&format!("bom-{}", byte_order_mark));
+            let options = CsvOptions {
+                byte_order_mark,
+                ..CsvOptions::default()
+            };
+            print_rows(
+                Print::with_options(std::fs::File::create(&path).unwrap(), &options),
+                &["Author"],
+                &[&["Zoë"]],
+            )
+            .await;
+            let bytes = std::fs::read(&path).unwrap();
+            assert_eq!(bytes.starts_with(BYTE_ORDER_MARK), byte_order_mark);
+            let text = "#,Author\n1,Zoë\n".as_bytes();
+            assert!(bytes.ends_with(text));
+            assert_eq!(
+                bytes.len() - text.len(),
+                if byte_order_mark { 3 } else { 0 }
+            );
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Lists every PR opened in the given time period, one row each, so that the
+/// aggregate metrics can be checked against the PRs they were derived from.
+///
+/// The state is that of each PR when the report is run.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out, as they are
+/// from the other metrics.
+pub struct PullRequestList {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl PullRequestList {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pull_request_list.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PullRequestListQuery;
+use pull_request_list_query as prl;
+
+#[async_trait]
+impl Producer for PullRequestList {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Number"),
+            String::from("Title"),
+            String::from("Author"),
+            String::from("State"),
+            String::from("Created"),
+            String::from("Merged"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Pull request list", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(PullRequestListQuery)
+                        .execute_checked(prl::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        if let prl::PullRequestListQuerySearchNodes::PullRequest(pr) = node {
+                            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                                let is_app = matches!(a.on, prl::PullRequestListQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                                is_bot(&a.login, is_app, &self.bots)
+                            });
+                            if by_bot {
+                                continue;
+                            }
+                            let state = match pr.state {
+                                prl::PullRequestState::OPEN => "open".to_string(),
+                                prl::PullRequestState::CLOSED => "closed".to_string(),
+                                prl::PullRequestState::MERGED => "merged".to_string(),
+                                prl::PullRequestState::Other(state) => state.to_lowercase(),
+                            };
+                            tx.send(vec![
+                                repo_name.clone(),
+                                pr.number.to_string(),
+                                pr.title,
+                                pr.author.map(|a| a.login).unwrap_or_default(),
+                                state,
+                                pr.created_at.to_rfc3339(),
+                                pr.merged_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    #[tokio::test]
+    async fn one_row_per_pr() {
+        let graphql = replaying(
+            "pull-request-list",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    {
+                        "__typename": "PullRequest",
+                        "number": 1,
+                        "title": "Add the frobnicator",
+                        "author": { "__typename": "User", "login": "alice" },
+                        "state": "MERGED",
+                        "createdAt": "2024-01-02T10:00:00Z",
+                        "mergedAt": "2024-01-03T12:00:00Z",
+                    },
+                    {
+                        "__typename": "PullRequest",
+                        "number": 2,
+                        "title": "Fix a typo, again",
+                        "author": null,
+                        "state": "OPEN",
+                        "createdAt": "2024-01-04T09:30:00Z",
+                        "mergedAt": null,
+                    },
+                ],
+            } } })],
+        );
+        let producer = PullRequestList::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [
+                [
+                    "rust",
+                    "1",
+                    "Add the frobnicator",
+                    "alice",
+                    "merged",
+                    "2024-01-02T10:00:00+00:00",
+                    "2024-01-03T12:00:00+00:00",
+                ],
+                [
+                    "rust",
+                    "2",
+                    "Fix a typo, again",
+                    "",
+                    "open",
+                    "2024-01-04T09:30:00+00:00",
+                    "",
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre::{self, Error};
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{util, Graphql, Producer, TimeBucket};
+use crate::progress::Progress;
+use crate::util::{is_bot, timestamp};
+
+/// Counts the PRs that were open at the end of each ISO week (Monday to Sunday) of the
+/// given time period: those created by then and not closed (or merged) until after.
+///
+/// The last week is sampled at the end of the time period, so it may be partial; the
+/// week is labeled by its ISO year and number, e.g. `2021-W52`.
+///
+/// PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct QueueDepth {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl QueueDepth {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/pr_intervals.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct PrIntervalsQuery;
+use pr_intervals_query as pi;
+
+/// When a PR was open: from its creation until it was closed, if it has been.
+type Interval = (DateTime, Option<DateTime>);
+
+#[async_trait]
+impl Producer for QueueDepth {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Week"),
+            String::from("Open PRs"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let weeks = TimeBucket::Week.split(
+            timestamp(&self.start_date)?.date_naive(),
+            timestamp(&self.end_date)?.date_naive(),
+        );
+
+        let mut progress = Progress::new("Queue depth", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                // The PRs open at any point in the time period: those still open, and
+                // those closed since it started; either kind created before it ended.
+                let mut intervals = pr_intervals(
+                    &mut self.graphql,
+                    format!(
+                        r#"repo:{}/{} is:pr is:open created:<={}"#,
+                        self.org_name, repo_name, self.end_date,
+                    ),
+                    &self.bots,
+                )
+                .await?;
+                intervals.extend(
+                    pr_intervals(
+                        &mut self.graphql,
+                        format!(
+                            r#"repo:{}/{} is:pr is:closed created:<={} closed:>={}"#,
+                            self.org_name, repo_name, self.end_date, self.start_date,
+                        ),
+                        &self.bots,
+                    )
+                    .await?,
+                );
+
+                for &(first_day, last_day) in &weeks {
+                    let end_of_week = last_day
+                        .succ_opt()
+                        .expect("not the last day ever")
+                        .and_hms_opt(0, 0, 0)
+                        .expect("midnight is a valid time")
+                        .and_utc();
+                    tx.send(vec![
+                        repo_name.clone(),
+                        util::iso_week_label(first_day),
+                        open_at(&intervals, end_of_week).to_string(),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists when each of the PRs matching `query_string`, other than those by bots, was open.
+#[throws]
+async fn pr_intervals(
+    graphql: &mut Graphql,
+    query_string: String,
+    bots: &[String],
+) -> Vec<Interval> {
+    let mut intervals = vec![];
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(100);
+        let response_data = graphql
+            .query(PrIntervalsQuery)
+            .execute_checked(pi::Variables {
+                page_size,
+                query_string: query_string.clone(),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            if let pi::PrIntervalsQuerySearchNodes::PullRequest(pr) = node {
+                let by_bot = pr.author.as_ref().is_some_and(|a| {
+                    is_bot(
+                        &a.login,
+                        matches!(
+                            a.on,
+                            pi::PrIntervalsQuerySearchNodesOnPullRequestAuthorOn::Bot
+                        ),
+                        bots,
+                    )
+                });
+                if by_bot {
+                    continue;
+                }
+                intervals.push((pr.created_at, pr.closed_at));
+            }
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    intervals
+}
+
+/// How many of `intervals` include the moment `at`: those that started by then and
+/// hadn't ended yet.
+fn open_at(intervals: &[Interval], at: DateTime) -> usize {
+    intervals
+        .iter()
+        .filter(|(created, closed)| *created <= at && closed.is_none_or(|closed| closed > at))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn at(time: &str) -> DateTime {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn counts_the_intervals_spanning_a_moment() {
+        let intervals = [
+            (at("2024-01-01T00:00:00Z"), Some(at("2024-01-10T00:00:00Z"))),
+            (at("2024-01-05T00:00:00Z"), None),
+            (at("2024-01-08T00:00:00Z"), Some(at("2024-01-09T00:00:00Z"))),
+        ];
+        assert_eq!(open_at(&intervals, at("2023-12-31T00:00:00Z")), 0);
+        // created at exactly that moment counts
+        assert_eq!(open_at(&intervals, at("2024-01-05T00:00:00Z")), 2);
+        assert_eq!(open_at(&intervals, at("2024-01-08T12:00:00Z")), 3);
+        // closed at exactly that moment doesn't
+        assert_eq!(open_at(&intervals, at("2024-01-10T00:00:00Z")), 1);
+    }
+
+    #[tokio::test]
+    async fn counts_open_prs_at_the_end_of_each_week() {
+        let prs = |intervals: &[(&str, Option<&str>)]| {
+            let nodes: Vec<_> = intervals
+                .iter()
+                .map(|(created_at, closed_at)| {
+                    serde_json::json!({
+                        "__typename": "PullRequest",
+                        "author": { "__typename": "User", "login": "alice" },
+                        "createdAt": created_at,
+                        "closedAt": closed_at,
+                    })
+                })
+                .collect();
+            serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            } } })
+        };
+        let graphql = replaying(
+            "queue-depth",
+            &[
+                // still open
+                prs(&[("2023-12-20T10:00:00Z", None)]),
+                // closed since the time period started
+                prs(&[
+                    ("2024-01-02T10:00:00Z", Some("2024-01-09T10:00:00Z")),
+                    ("2024-01-03T10:00:00Z", Some("2024-01-04T10:00:00Z")),
+                ]),
+            ],
+        );
+        let producer = QueueDepth::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-14".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust", "2024-W01", "2"], ["rust", "2024-W02", "1"]]
+        );
+    }
+}
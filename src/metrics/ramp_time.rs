@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::util::CountIssues;
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{hours_between, is_bot};
+
+/// Reports, for each contributor who opened their first PR in a repository in the given
+/// time period, how many days passed between opening it and their first PR being merged
+/// (which need not be the same PR). Contributors with no merged PR yet are skipped,
+/// as are bots (GitHub Apps, or any of the given `bots`).
+pub struct RampTime {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl RampTime {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/ramp_time.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct RampTimeQuery;
+use ramp_time_query as rt;
+
+/// When a contributor first opened a PR, and when one of theirs was first merged.
+#[derive(Default)]
+struct FirstPrs {
+    opened: Option<DateTime>,
+    merged: Option<DateTime>,
+}
+
+#[async_trait]
+impl Producer for RampTime {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Contributor"),
+            String::from("Ramp days"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Ramp time", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut first_prs: BTreeMap<String, FirstPrs> = BTreeMap::new();
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(100);
+                    let response_data = self
+                        .graphql
+                        .query(RampTimeQuery)
+                        .execute_checked(rt::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            rt::RampTimeQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let author = match pr.author {
+                            Some(author) if !is_bot(&author.login, matches!(author.on, rt::RampTimeQuerySearchNodesOnPullRequestAuthorOn::Bot), &self.bots) => author.login,
+                            // deleted accounts can't be told apart from bots
+                            _ => continue,
+                        };
+
+                        let first = first_prs.entry(author).or_default();
+                        first.opened = Some(first.opened.map_or(pr.created_at, |t| t.min(pr.created_at)));
+                        if let Some(merged_at) = pr.merged_at {
+                            first.merged = Some(first.merged.map_or(merged_at, |t| t.min(merged_at)));
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                for (author, first) in first_prs {
+                    let (opened, merged) = match (first.opened, first.merged) {
+                        (Some(opened), Some(merged)) => (opened, merged),
+                        _ => continue,
+                    };
+
+                    // Only contributors who are new to the repository are ramping up.
+                    let earlier_prs = CountIssues::query(
+                        &mut self.graphql,
+                        format!(
+                            r#"repo:{}/{} is:pr author:{} created:<{}"#,
+                            self.org_name, repo_name, author, self.start_date
+                        ),
+                    )
+                    .await?;
+                    if earlier_prs > 0 {
+                        continue;
+                    }
+
+                    tx.send(vec![
+                        repo_name.clone(),
+                        author,
+                        format!("{:.1}", hours_between(opened, merged) / 24.0),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(login: &str, created_at: &str, merged_at: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": login },
+            "createdAt": created_at,
+            "mergedAt": merged_at,
+        })
+    }
+
+    fn count(issue_count: usize) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": { "issueCount": issue_count } } })
+    }
+
+    #[tokio::test]
+    async fn ramps_up_from_the_first_pr_to_the_first_merge() {
+        let graphql = replaying(
+            "ramp-time",
+            &[
+                serde_json::json!({ "data": { "search": {
+                    "pageInfo": { "hasNextPage": false, "endCursor": null },
+                    "nodes": [
+                        pr("alice", "2024-01-02T12:00:00Z", None),
+                        pr("alice", "2024-01-04T12:00:00Z", Some("2024-01-05T12:00:00Z")),
+                        pr("bob", "2024-01-03T12:00:00Z", Some("2024-01-03T18:00:00Z")),
+                    ],
+                } } }),
+                // alice's PRs before the time period
+                count(0),
+                // bob's: bob isn't new
+                count(2),
+            ],
+        );
+        let producer = RampTime::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "alice", "3.0"]]);
+    }
+}
@@ -0,0 +1,385 @@
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre::{self, Error};
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{is_bot, timestamp};
+
+/// Counts the issues and the PRs that were reopened in the given time period, as a
+/// sign of incomplete fixes. Each counts once, however often it was reopened.
+///
+/// Issues and PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct Reopens {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl Reopens {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/reopens.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReopensQuery;
+use reopens_query as rq;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/reopens.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReopensTimeline;
+use reopens_timeline as rt;
+
+/// A page of an issue's or PR's reopen events: when each happened, and the cursor to
+/// the next page, if any.
+type ReopenPage = (Vec<DateTime>, Option<String>);
+
+#[async_trait]
+impl Producer for Reopens {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Reopened issues"),
+            String::from("Reopened PRs"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let since = timestamp(&self.start_date)?;
+        let end = timestamp(&self.end_date)?.date_naive();
+        let in_period = |reopened_at: &DateTime| reopened_at.date_naive() <= end;
+
+        let mut progress = Progress::new("Reopens", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut reopened_issues = 0;
+                let mut reopened_prs = 0;
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    // Anything reopened in the time period was updated since it started.
+                    let response_data = self
+                        .graphql
+                        .query(ReopensQuery)
+                        .execute_checked(rq::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} created:<={end_date} updated:>={start_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                            since: Some(since),
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let (number, is_pr, by_bot, (reopens, next_page)) = match node {
+                            rq::ReopensQuerySearchNodes::Issue(issue) => (
+                                issue.number,
+                                false,
+                                issue.author.is_some_and(|a| {
+                                    let is_app = matches!(a.on, rq::ReopensQuerySearchNodesOnIssueAuthorOn::Bot);
+                                    is_bot(&a.login, is_app, &self.bots)
+                                }),
+                                search_issue_reopens(issue.timeline_items),
+                            ),
+                            rq::ReopensQuerySearchNodes::PullRequest(pr) => (
+                                pr.number,
+                                true,
+                                pr.author.is_some_and(|a| {
+                                    let is_app = matches!(a.on, rq::ReopensQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                                    is_bot(&a.login, is_app, &self.bots)
+                                }),
+                                search_pr_reopens(pr.timeline_items),
+                            ),
+                            _ => continue,
+                        };
+                        if by_bot {
+                            continue;
+                        }
+
+                        let mut reopened = reopens.iter().any(in_period);
+                        // Only walk the rest of a long timeline if the first page didn't settle it.
+                        if !reopened && next_page.is_some() {
+                            reopened = remaining_reopens(
+                                &mut self.graphql,
+                                &self.org_name,
+                                repo_name,
+                                number,
+                                next_page,
+                                since,
+                            )
+                            .await?
+                            .iter()
+                            .any(in_period);
+                        }
+
+                        if reopened {
+                            if is_pr {
+                                reopened_prs += 1;
+                            } else {
+                                reopened_issues += 1;
+                            }
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                tx.send(vec![
+                    repo_name.clone(),
+                    reopened_issues.to_string(),
+                    reopened_prs.to_string(),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists when the issue or PR was reopened, starting at `after_cursor` in its timeline.
+#[throws]
+async fn remaining_reopens(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+    since: DateTime,
+) -> Vec<DateTime> {
+    use rt::ReopensTimelineRepositoryIssueOrPullRequest as Item;
+
+    let mut reopens = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(ReopensTimeline)
+            .execute_checked(rt::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+                since: Some(since),
+            })
+            .await?;
+        let (page, next_page) = match response_data
+            .repository
+            .and_then(|r| r.issue_or_pull_request)
+        {
+            Some(Item::Issue(issue)) => timeline_issue_reopens(issue.timeline_items),
+            Some(Item::PullRequest(pr)) => timeline_pr_reopens(pr.timeline_items),
+            None => break,
+        };
+
+        reopens.extend(page);
+
+        match next_page {
+            Some(_) => after_cursor = next_page,
+            None => break,
+        }
+    }
+
+    reopens
+}
+
+/// The reopen events on a page of an issue's timeline.
+fn search_issue_reopens(timeline: rq::ReopensQuerySearchNodesOnIssueTimelineItems) -> ReopenPage {
+    use rq::ReopensQuerySearchNodesOnIssueTimelineItemsNodes as Item;
+
+    let reopens = timeline
+        .nodes
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|item| match item {
+            Item::ReopenedEvent(e) => Some(e.created_at),
+            _ => None,
+        })
+        .collect();
+    (
+        reopens,
+        next_page(
+            timeline.page_info.has_next_page,
+            timeline.page_info.end_cursor,
+        ),
+    )
+}
+
+/// Same as `search_issue_reopens`, for a PR's timeline.
+fn search_pr_reopens(
+    timeline: rq::ReopensQuerySearchNodesOnPullRequestTimelineItems,
+) -> ReopenPage {
+    use rq::ReopensQuerySearchNodesOnPullRequestTimelineItemsNodes as Item;
+
+    let reopens = timeline
+        .nodes
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|item| match item {
+            Item::ReopenedEvent(e) => Some(e.created_at),
+            _ => None,
+        })
+        .collect();
+    (
+        reopens,
+        next_page(
+            timeline.page_info.has_next_page,
+            timeline.page_info.end_cursor,
+        ),
+    )
+}
+
+/// Same as `search_issue_reopens`, for the issues of `ReopensTimeline`.
+fn timeline_issue_reopens(
+    timeline: rt::ReopensTimelineRepositoryIssueOrPullRequestOnIssueTimelineItems,
+) -> ReopenPage {
+    use rt::ReopensTimelineRepositoryIssueOrPullRequestOnIssueTimelineItemsNodes as Item;
+
+    let reopens = timeline
+        .nodes
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|item| match item {
+            Item::ReopenedEvent(e) => Some(e.created_at),
+            _ => None,
+        })
+        .collect();
+    (
+        reopens,
+        next_page(
+            timeline.page_info.has_next_page,
+            timeline.page_info.end_cursor,
+        ),
+    )
+}
+
+/// Same as `search_issue_reopens`, for the PRs of `ReopensTimeline`.
+fn timeline_pr_reopens(
+    timeline: rt::ReopensTimelineRepositoryIssueOrPullRequestOnPullRequestTimelineItems,
+) -> ReopenPage {
+    use rt::ReopensTimelineRepositoryIssueOrPullRequestOnPullRequestTimelineItemsNodes as Item;
+
+    let reopens = timeline
+        .nodes
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|item| match item {
+            Item::ReopenedEvent(e) => Some(e.created_at),
+            _ => None,
+        })
+        .collect();
+    (
+        reopens,
+        next_page(
+            timeline.page_info.has_next_page,
+            timeline.page_info.end_cursor,
+        ),
+    )
+}
+
+/// The cursor to the next page, if there is one.
+fn next_page(has_next_page: bool, end_cursor: Option<String>) -> Option<String> {
+    if has_next_page {
+        end_cursor
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn item(typename: &str, number: i64, reopened_at: &[&str]) -> serde_json::Value {
+        let nodes: Vec<_> = reopened_at
+            .iter()
+            .map(|time| serde_json::json!({ "__typename": "ReopenedEvent", "createdAt": time }))
+            .collect();
+        serde_json::json!({
+            "__typename": typename,
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "timelineItems": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_a_reopened_issue() {
+        let graphql = replaying(
+            "reopens",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    item("Issue", 1, &["2024-01-10T10:00:00Z"]),
+                    item("Issue", 2, &[]),
+                    // reopened after the time period
+                    item("PullRequest", 3, &["2024-02-02T10:00:00Z"]),
+                ],
+            } } })],
+        );
+        let producer = Reopens::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "1", "0"]]);
+    }
+}
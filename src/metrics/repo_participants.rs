@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use fehler::throws;
@@ -7,8 +8,11 @@ use stable_eyre::eyre;
 use stable_eyre::eyre::Error;
 use tokio::sync::mpsc::Sender;
 use toml::value::Datetime;
+use tracing::Instrument;
 
-use super::{Graphql, Producer};
+use super::{GithubContext, Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::ranking;
 
 pub struct RepoParticipants {
     graphql: Graphql,
@@ -16,6 +20,9 @@ pub struct RepoParticipants {
     repo_names: Vec<String>,
     start_date: Datetime,
     end_date: Datetime,
+    aliases: HashMap<String, String>,
+    context: Option<Arc<GithubContext>>,
+    bots: Vec<String>,
 }
 
 impl RepoParticipants {
@@ -32,8 +39,26 @@ impl RepoParticipants {
             repo_names,
             start_date,
             end_date,
+            aliases: HashMap::new(),
+            context: None,
+            bots: vec![],
         }
     }
+
+    /// Tag each participant as internal or external by the organization members in
+    /// `context`, if they were fetched; `bots` are neither.
+    pub fn with_affiliations(mut self, context: Arc<GithubContext>, bots: Vec<String>) -> Self {
+        self.context = Some(context);
+        self.bots = bots;
+        self
+    }
+
+    /// Count the activity of each login in `aliases` (alternate login to canonical
+    /// login) as that of its canonical login.
+    pub fn with_aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.aliases = aliases;
+        self
+    }
 }
 
 #[async_trait]
@@ -46,43 +71,58 @@ impl Producer for RepoParticipants {
             String::from("PRs authored"),
             String::from("PRs reviewed"),
             String::from("PRs resolved"),
+            String::from("Affiliation"),
         ]
     }
 
     async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Repo participants", self.repo_names.len());
         // If no repository is given, repeat for all repositories.
         for repo_name in &self.repo_names {
-            let data = pr_participants(
-                &mut self.graphql,
-                &self.org_name,
-                repo_name,
-                &self.start_date,
-                &self.end_date,
-            )
-            .await?;
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let data = pr_participants(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    &self.aliases,
+                )
+                .await?;
 
-            // FIXME -- there must be some way to "autoderive" this from
-            // the `ParticipantCounts` data structure, maybe with serde?
-            for (
-                login,
-                ParticipantCounts {
-                    participated_in,
-                    authored,
-                    reviewed,
-                    resolved,
-                },
-            ) in data
-            {
-                tx.send(vec![
+                // FIXME -- there must be some way to "autoderive" this from
+                // the `ParticipantCounts` data structure, maybe with serde?
+                for (
                     login,
-                    repo_name.clone(),
-                    participated_in.to_string(),
-                    authored.to_string(),
-                    reviewed.to_string(),
-                    resolved.to_string(),
-                ])
-                .await?;
+                    ParticipantCounts {
+                        participated_in,
+                        authored,
+                        reviewed,
+                        resolved,
+                    },
+                ) in data
+                {
+                    let affiliation = match &self.context {
+                        Some(context) => context.affiliation(&login, &self.bots),
+                        None => "",
+                    };
+                    tx.send(vec![
+                        login,
+                        repo_name.clone(),
+                        participated_in.to_string(),
+                        authored.to_string(),
+                        reviewed.to_string(),
+                        resolved.to_string(),
+                        affiliation.to_string(),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
             }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
         }
 
         Ok(())
@@ -112,6 +152,7 @@ use prs_and_participants as pap;
 /// - `org_name` — The name of the github organization that owns the specified repository
 /// - `repo_name` — The name of the repository to count pull requests for. **Note:** repository should exist within the `org_name` Github Organization
 /// - `time_period` — The relevant time period to search within
+/// - `aliases` — Alternate logins, mapped to the canonical login they are counted as
 #[throws]
 async fn pr_participants(
     graphql: &mut Graphql,
@@ -119,16 +160,21 @@ async fn pr_participants(
     repo_name: &str,
     start_date: &Datetime,
     end_date: &Datetime,
+    aliases: &HashMap<String, String>,
 ) -> Vec<(String, ParticipantCounts)> {
+    let canonical = |login: String| aliases.get(&login).cloned().unwrap_or(login);
+
     // Tracks, for each github login, how many PRs they participated in on this repository.
     let mut counts: HashMap<String, ParticipantCounts> = HashMap::new();
 
     let mut after_cursor = None;
 
     loop {
-        let response = graphql
+        let page_size = graphql.page_size(100);
+        let response_data = graphql
             .query(PrsAndParticipants)
-            .execute(pap::Variables {
+            .execute_checked(pap::Variables {
+                page_size,
                 query_string: format!(
                     r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
                     org_name = org_name,
@@ -139,7 +185,6 @@ async fn pr_participants(
                 after_cursor,
             })
             .await?;
-        let response_data = response.data.expect("missing response data");
         for pr_edge in response_data.search.edges.into_iter().flatten().flatten() {
             let pr = match pr_edge.node {
                 Some(pap::PrsAndParticipantsSearchEdgesNode::PullRequest(pr)) => pr,
@@ -148,30 +193,30 @@ async fn pr_participants(
 
             // Extract PR author
             let mut author = None;
-            if let Some(a) = pr.author {
-                if let pap::PrsAndParticipantsSearchEdgesNodeOnPullRequestAuthor::User(u) = a {
-                    author = Some(u.login);
-                }
+            if let Some(pap::PrsAndParticipantsSearchEdgesNodeOnPullRequestAuthor::User(u)) =
+                pr.author
+            {
+                author = Some(canonical(u.login));
             }
             let is_author = |s: &str| author.iter().any(|a| a == s);
 
             // For each person who participated on this PR, increment their
             // entry in the `participated` map.
             //
-            // Assumption: a given individual will not appear more than once
-            // in this list.
+            // A given login appears at most once in this list, but aliases of
+            // the same person may both appear, so they are deduplicated.
             let mut participants_found = 0;
-            for participant in pr
+            let participants: HashSet<_> = pr
                 .participants
                 .edges
                 .into_iter()
                 .flatten()
                 .flatten()
-                .map(|p| p.node)
-                .flatten()
+                .filter_map(|p| p.node)
                 .inspect(|_| participants_found += 1)
-            {
-                let login = participant.login;
+                .map(|p| canonical(p.login))
+                .collect();
+            for login in participants {
                 if !is_author(&login) {
                     counts.entry(login).or_default().participated_in += 1;
                 }
@@ -187,7 +232,9 @@ async fn pr_participants(
             }
 
             // Count the number of PRs on which a person has issued a review.
-            let reviews = pr.reviews.unwrap();
+            let reviews = pr
+                .reviews
+                .ok_or_else(|| eyre::eyre!("no reviews returned for PR #{}", pr.number))?;
             let mut reviews_found = 0;
 
             let reviewers: HashSet<_> = reviews
@@ -200,7 +247,7 @@ async fn pr_participants(
                 .flat_map(|a| match a {
                     pap::PrsAndParticipantsSearchEdgesNodeOnPullRequestReviewsNodesAuthor::User(
                         u,
-                    ) => Some(u.login),
+                    ) => Some(canonical(u.login)),
                     _ => None,
                 })
                 .collect();
@@ -221,10 +268,10 @@ async fn pr_participants(
             }
 
             // Count the number of PRs which a person has merged.
-            if let Some(a) = pr.merged_by {
-                if let pap::PrsAndParticipantsSearchEdgesNodeOnPullRequestMergedBy::User(u) = a {
-                    counts.entry(u.login).or_default().resolved += 1;
-                }
+            if let Some(pap::PrsAndParticipantsSearchEdgesNodeOnPullRequestMergedBy::User(u)) =
+                pr.merged_by
+            {
+                counts.entry(canonical(u.login)).or_default().resolved += 1;
             }
         }
 
@@ -236,6 +283,117 @@ async fn pr_participants(
     }
 
     let mut counts: Vec<_> = counts.into_iter().collect();
-    counts.sort_by_key(|(login, p)| (u64::MAX - p.participated_in, login.clone()));
+    counts.sort_by_key(|(login, p)| ranking(p.participated_in, login.clone()));
     counts
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn user(login: &str) -> serde_json::Value {
+        serde_json::json!({ "__typename": "User", "login": login })
+    }
+
+    fn pull_request(
+        number: u64,
+        author: &str,
+        reviewers: &[&str],
+        participants: &[&str],
+        merged_by: &str,
+    ) -> serde_json::Value {
+        let reviews: Vec<_> = reviewers
+            .iter()
+            .map(|login| serde_json::json!({ "author": user(login) }))
+            .collect();
+        let participants: Vec<_> = participants
+            .iter()
+            .map(|login| serde_json::json!({ "node": { "login": login } }))
+            .collect();
+        serde_json::json!({ "node": {
+            "__typename": "PullRequest",
+            "number": number,
+            "author": user(author),
+            "mergedBy": user(merged_by),
+            "reviews": { "totalCount": reviews.len(), "nodes": reviews },
+            "participants": { "totalCount": participants.len(), "edges": participants },
+        } })
+    }
+
+    fn search(edges: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": {
+            "pageInfo": { "hasNextPage": false, "endCursor": null },
+            "edges": edges,
+        } } })
+    }
+
+    fn producer(name: &str, prs: Vec<serde_json::Value>) -> RepoParticipants {
+        RepoParticipants::new(
+            replaying(name, &[search(prs)]),
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+        )
+    }
+
+    fn row(fields: [&str; 7]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn aliases_are_counted_as_one_participant() {
+        let prs = vec![
+            pull_request(1, "alice", &["bob"], &["alice", "bob"], "bob"),
+            pull_request(2, "alice-work", &["bob"], &["alice-work", "bob"], "bob"),
+            // both of alice's logins take part in bob's PR, but it counts once
+            pull_request(
+                3,
+                "bob",
+                &["alice"],
+                &["bob", "alice", "alice-work"],
+                "alice-work",
+            ),
+        ];
+        let aliases = HashMap::from([("alice-work".to_string(), "alice".to_string())]);
+        let producer = producer("participants-aliases", prs).with_aliases(aliases);
+        assert_eq!(
+            rows(producer).await,
+            [
+                row(["bob", "rust", "2", "1", "2", "2", ""]),
+                row(["alice", "rust", "1", "2", "1", "1", ""]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn participants_are_internal_or_external() {
+        let prs = vec![
+            pull_request(1, "alice", &["bob"], &["alice", "bob"], "alice"),
+            pull_request(2, "carol", &["bors"], &["carol", "bors"], "bors"),
+        ];
+        let context = Arc::new(GithubContext {
+            viewer_login: "me".to_string(),
+            org: None,
+            members: Some(HashSet::from(["alice".to_string()])),
+        });
+        let producer = producer("participants-affiliations", prs)
+            .with_affiliations(context, vec!["bors".to_string()]);
+        let mut affiliations: Vec<_> = rows(producer)
+            .await
+            .into_iter()
+            .map(|row| (row[0].clone(), row[6].clone()))
+            .collect();
+        affiliations.sort();
+        assert_eq!(
+            affiliations,
+            [
+                ("alice".to_string(), "Internal".to_string()),
+                ("bob".to_string(), "External".to_string()),
+                ("bors".to_string(), String::new()),
+                ("carol".to_string(), "External".to_string()),
+            ]
+        );
+    }
+}
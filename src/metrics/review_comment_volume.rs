@@ -0,0 +1,278 @@
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Counts the review comments left on the PRs opened in the given time period,
+/// as a rough measure of how much engagement PRs get.
+///
+/// PRs opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct ReviewCommentVolume {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl ReviewCommentVolume {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/review_comments.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewCommentsQuery;
+use review_comments_query as rc;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/review_comments.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewThreadComments;
+use review_thread_comments as rtc;
+
+#[async_trait]
+impl Producer for ReviewCommentVolume {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR count"),
+            String::from("Total review comments"),
+            String::from("Avg per PR"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Review comment volume", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let (prs, comments) = count_review_comments(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    &self.bots,
+                )
+                .await?;
+                let average = if prs == 0 {
+                    0.0
+                } else {
+                    comments as f64 / prs as f64
+                };
+                tx.send(vec![
+                    repo_name.clone(),
+                    prs.to_string(),
+                    comments.to_string(),
+                    format!("{:.1}", average),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the PRs opened in the time period and the review comments on them.
+#[throws]
+async fn count_review_comments(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    bots: &[String],
+) -> (u64, u64) {
+    let mut prs = 0;
+    let mut comments = 0;
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(50);
+        let response_data = graphql
+            .query(ReviewCommentsQuery)
+            .execute_checked(rc::Variables {
+                page_size,
+                query_string: format!(
+                    r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                    org_name = org_name,
+                    repo_name = repo_name,
+                    start_date = start_date,
+                    end_date = end_date,
+                ),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            let pr = match node {
+                rc::ReviewCommentsQuerySearchNodes::PullRequest(pr) => pr,
+                _ => continue,
+            };
+            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                is_bot(
+                    &a.login,
+                    matches!(
+                        a.on,
+                        rc::ReviewCommentsQuerySearchNodesOnPullRequestAuthorOn::Bot
+                    ),
+                    bots,
+                )
+            });
+            if by_bot {
+                continue;
+            }
+
+            let threads = pr.review_threads;
+            prs += 1;
+            comments += threads
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|thread| thread.comments.total_count as u64)
+                .sum::<u64>();
+            if threads.page_info.has_next_page {
+                comments += remaining_review_comments(
+                    graphql,
+                    org_name,
+                    repo_name,
+                    pr.number,
+                    threads.page_info.end_cursor,
+                )
+                .await?;
+            }
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    (prs, comments)
+}
+
+/// Counts the review comments in the PR's review threads, starting at `after_cursor`.
+#[throws]
+pub(super) async fn remaining_review_comments(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> u64 {
+    let mut comments = 0;
+
+    loop {
+        let response_data = graphql
+            .query(ReviewThreadComments)
+            .execute_checked(rtc::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let threads = match response_data.repository.and_then(|r| r.pull_request) {
+            Some(pr) => pr.review_threads,
+            None => break,
+        };
+
+        comments += threads
+            .nodes
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|thread| thread.comments.total_count as u64)
+            .sum::<u64>();
+
+        if threads.page_info.has_next_page {
+            after_cursor = threads.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(number: i64, threads: &[u64]) -> serde_json::Value {
+        let nodes: Vec<_> = threads
+            .iter()
+            .map(|comments| serde_json::json!({ "comments": { "totalCount": comments } }))
+            .collect();
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "reviewThreads": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn averages_review_comments_across_prs() {
+        let graphql = replaying(
+            "review-comment-volume",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [pr(1, &[2, 1]), pr(2, &[])],
+            } } })],
+        );
+        let producer = ReviewCommentVolume::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "2", "3", "1.5"]]);
+    }
+}
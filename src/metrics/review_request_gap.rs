@@ -0,0 +1,389 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Counts how many of the closed or merged PRs opened in the given time period had
+/// review requests that were never fulfilled ("ghosted").
+///
+/// A request to a user is fulfilled once that user submits a review. A request to a
+/// team is fulfilled once anyone other than the PR's author submits a review after it,
+/// since team membership isn't known. Requests that were withdrawn are not counted as
+/// unfulfilled.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct ReviewRequestGap {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl ReviewRequestGap {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/review_requests.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewRequestsQuery;
+use review_requests_query as rr;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/review_requests.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewRequestsTimeline;
+use review_requests_timeline as rrt;
+
+#[async_trait]
+impl Producer for ReviewRequestGap {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PR count requested"),
+            String::from("PR count unfulfilled"),
+            String::from("Rate"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Review request gap", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut requested = 0;
+                let mut unfulfilled = 0;
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(ReviewRequestsQuery)
+                        .execute_checked(rr::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:closed created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            rr::ReviewRequestsQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        let by_bot = pr.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, rr::ReviewRequestsQuerySearchNodesOnPullRequestAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+                        let timeline = pr.timeline_items;
+                        let mut events: Vec<Event> = timeline
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .filter_map(search_event)
+                            .collect();
+                        if timeline.page_info.has_next_page {
+                            events.extend(
+                                remaining_events(
+                                    &mut self.graphql,
+                                    &self.org_name,
+                                    repo_name,
+                                    pr.number,
+                                    timeline.page_info.end_cursor,
+                                )
+                                .await?,
+                            );
+                        }
+
+                        let requests = review_requests(pr.author.map(|a| a.login), events);
+                        if !requests.is_empty() {
+                            requested += 1;
+                            if requests.iter().any(|r| r.is_ghosted()) {
+                                unfulfilled += 1;
+                            }
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                let rate = if requested == 0 {
+                    String::new()
+                } else {
+                    format!("{:.2}", unfulfilled as f64 / requested as f64)
+                };
+                tx.send(vec![
+                    repo_name.clone(),
+                    requested.to_string(),
+                    unfulfilled.to_string(),
+                    rate,
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(PartialEq)]
+enum Reviewer {
+    User(String),
+    Team(String),
+}
+
+struct Request {
+    reviewer: Reviewer,
+    fulfilled: bool,
+    withdrawn: bool,
+}
+
+impl Request {
+    fn is_open(&self) -> bool {
+        !self.fulfilled && !self.withdrawn
+    }
+
+    fn is_ghosted(&self) -> bool {
+        self.is_open()
+    }
+}
+
+/// The events of a PR's timeline that `review_requests` replays.
+enum Event {
+    Requested(Reviewer),
+    Removed(Reviewer),
+    /// A review, by the given login.
+    Reviewed(String),
+}
+
+/// Replays a PR's timeline (which is in chronological order) to find its review
+/// requests and whether each was fulfilled or withdrawn.
+fn review_requests(pr_author: Option<String>, timeline: Vec<Event>) -> Vec<Request> {
+    let mut requests: Vec<Request> = vec![];
+    for event in timeline {
+        match event {
+            Event::Requested(reviewer) => {
+                if !requests
+                    .iter()
+                    .any(|r| r.is_open() && r.reviewer == reviewer)
+                {
+                    requests.push(Request {
+                        reviewer,
+                        fulfilled: false,
+                        withdrawn: false,
+                    });
+                }
+            }
+            Event::Removed(reviewer) => {
+                for request in &mut requests {
+                    if request.is_open() && request.reviewer == reviewer {
+                        request.withdrawn = true;
+                    }
+                }
+            }
+            Event::Reviewed(login) => {
+                for request in &mut requests {
+                    let fulfills = match &request.reviewer {
+                        Reviewer::User(user) => *user == login,
+                        Reviewer::Team(_) => pr_author.as_deref() != Some(login.as_str()),
+                    };
+                    if request.is_open() && fulfills {
+                        request.fulfilled = true;
+                    }
+                }
+            }
+        }
+    }
+    requests
+}
+
+/// Lists the events of the PR's timeline, starting at `after_cursor`.
+async fn remaining_events(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> eyre::Result<Vec<Event>> {
+    let mut events = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(ReviewRequestsTimeline)
+            .execute_checked(rrt::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let timeline = match response_data.repository.and_then(|r| r.pull_request) {
+            Some(pr) => pr.timeline_items,
+            None => break,
+        };
+
+        let nodes = timeline.nodes.into_iter().flatten().flatten();
+        events.extend(nodes.filter_map(remaining_event));
+
+        if timeline.page_info.has_next_page {
+            after_cursor = timeline.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    Ok(events)
+}
+
+/// The event a timeline item stands for, if it is one `review_requests` replays.
+fn search_event(
+    item: rr::ReviewRequestsQuerySearchNodesOnPullRequestTimelineItemsNodes,
+) -> Option<Event> {
+    use rr::ReviewRequestsQuerySearchNodesOnPullRequestTimelineItemsNodes as Item;
+    use rr::ReviewRequestsQuerySearchNodesOnPullRequestTimelineItemsNodesOnReviewRequestRemovedEventRequestedReviewer as Removed;
+    use rr::ReviewRequestsQuerySearchNodesOnPullRequestTimelineItemsNodesOnReviewRequestedEventRequestedReviewer as Requested;
+
+    match item {
+        Item::ReviewRequestedEvent(e) => match e.requested_reviewer? {
+            Requested::User(u) => Some(Event::Requested(Reviewer::User(u.login))),
+            Requested::Team(t) => Some(Event::Requested(Reviewer::Team(t.slug))),
+            _ => None,
+        },
+        Item::ReviewRequestRemovedEvent(e) => match e.requested_reviewer? {
+            Removed::User(u) => Some(Event::Removed(Reviewer::User(u.login))),
+            Removed::Team(t) => Some(Event::Removed(Reviewer::Team(t.slug))),
+            _ => None,
+        },
+        Item::PullRequestReview(review) => Some(Event::Reviewed(review.author?.login)),
+        _ => None,
+    }
+}
+
+/// Same as `search_event`, for the timeline items of `ReviewRequestsTimeline`.
+fn remaining_event(
+    item: rrt::ReviewRequestsTimelineRepositoryPullRequestTimelineItemsNodes,
+) -> Option<Event> {
+    use rrt::ReviewRequestsTimelineRepositoryPullRequestTimelineItemsNodes as Item;
+    use rrt::ReviewRequestsTimelineRepositoryPullRequestTimelineItemsNodesOnReviewRequestRemovedEventRequestedReviewer as Removed;
+    use rrt::ReviewRequestsTimelineRepositoryPullRequestTimelineItemsNodesOnReviewRequestedEventRequestedReviewer as Requested;
+
+    match item {
+        Item::ReviewRequestedEvent(e) => match e.requested_reviewer? {
+            Requested::User(u) => Some(Event::Requested(Reviewer::User(u.login))),
+            Requested::Team(t) => Some(Event::Requested(Reviewer::Team(t.slug))),
+            _ => None,
+        },
+        Item::ReviewRequestRemovedEvent(e) => match e.requested_reviewer? {
+            Removed::User(u) => Some(Event::Removed(Reviewer::User(u.login))),
+            Removed::Team(t) => Some(Event::Removed(Reviewer::Team(t.slug))),
+            _ => None,
+        },
+        Item::PullRequestReview(review) => Some(Event::Reviewed(review.author?.login)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn requested(login: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "ReviewRequestedEvent",
+            "requestedReviewer": { "__typename": "User", "login": login },
+        })
+    }
+
+    fn reviewed(login: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequestReview",
+            "author": { "__typename": "User", "login": login },
+        })
+    }
+
+    fn pr(number: i64, timeline: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "timelineItems": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": timeline,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_the_prs_with_a_ghosted_request() {
+        let graphql = replaying(
+            "review-request-gap",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(1, vec![requested("bob"), reviewed("bob")]),
+                    pr(2, vec![requested("carol"), reviewed("bob")]),
+                    pr(3, vec![]),
+                ],
+            } } })],
+        );
+        let producer = ReviewRequestGap::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "2", "1", "0.50"]]);
+    }
+}
@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::reviewer_latency::first_reviews;
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{business_hours_between, hours_between, percentage, BusinessCalendar};
+
+/// Measures, per repository, how many of the PRs opened in the given time period were
+/// first reviewed (by someone other than their author) within `sla_hours` of becoming
+/// ready for review.
+///
+/// A PR that hasn't been reviewed yet misses the SLA once `sla_hours` have passed;
+/// until then, like a PR that is still a draft, it isn't counted at all.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out, and reviews by
+/// bots don't count. With `with_business_hours`, only the business hours are counted.
+pub struct ReviewSla {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    sla_hours: f64,
+    business_hours: Option<BusinessCalendar>,
+    bots: Vec<String>,
+}
+
+impl ReviewSla {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        sla_hours: f64,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            sla_hours,
+            business_hours: None,
+            bots,
+        }
+    }
+
+    /// Count only the business hours of `calendar`, leaving out nights, weekends and holidays.
+    pub fn with_business_hours(mut self, calendar: BusinessCalendar) -> Self {
+        self.business_hours = Some(calendar);
+        self
+    }
+}
+
+#[async_trait]
+impl Producer for ReviewSla {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("PRs"),
+            String::from("Within SLA"),
+            String::from("Compliance %"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let now = Utc::now();
+        let elapsed = |from, to| match &self.business_hours {
+            Some(calendar) => business_hours_between(from, to, calendar),
+            None => hours_between(from, to),
+        };
+
+        let mut progress = Progress::new("Review SLA", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let prs = first_reviews(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    &self.bots,
+                )
+                .await?;
+
+                let mut counted = 0;
+                let mut within_sla = 0;
+                for pr in prs {
+                    match pr.reviewed_at.values().min() {
+                        Some(&reviewed_at) => {
+                            counted += 1;
+                            // Reviews left while the PR was still a draft are in time.
+                            if elapsed(pr.ready_at, reviewed_at) <= self.sla_hours {
+                                within_sla += 1;
+                            }
+                        }
+                        None if !pr.is_draft && elapsed(pr.ready_at, now) > self.sla_hours => {
+                            counted += 1;
+                        }
+                        None => {}
+                    }
+                }
+
+                tx.send(vec![
+                    repo_name.clone(),
+                    counted.to_string(),
+                    within_sla.to_string(),
+                    percentage(within_sla, counted).to_string(),
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(number: u64, created_at: &str, reviewed_at: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "number": number,
+            "createdAt": created_at,
+            "isDraft": false,
+            "author": { "__typename": "User", "login": "alice" },
+            "timelineItems": { "nodes": [] },
+            "reviews": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [{
+                    "submittedAt": reviewed_at,
+                    "author": { "__typename": "User", "login": "bob" },
+                }],
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_the_prs_reviewed_within_the_sla() {
+        let graphql = replaying(
+            "review-sla",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(1, "2024-01-02T00:00:00Z", "2024-01-02T20:00:00Z"),
+                    pr(2, "2024-01-03T00:00:00Z", "2024-01-04T06:00:00Z"),
+                ],
+            } } })],
+        );
+        let producer = ReviewSla::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            24.0,
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "2", "1", "50"]]);
+    }
+}
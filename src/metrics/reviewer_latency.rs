@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use fehler::throws;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use stable_eyre::eyre::Error;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{business_hours_between, hours_between, is_bot, median, BusinessCalendar};
+
+/// Reports, per reviewer, how long it takes them to first review a PR once it is
+/// ready for review: the time it was opened, or marked ready if it was opened as a draft.
+///
+/// PRs by bots (GitHub Apps, or any of the given `bots`) are left out, and bots don't
+/// count as reviewers.
+///
+/// With `with_business_hours`, only the business hours are counted.
+pub struct ReviewerLatency {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    business_hours: Option<BusinessCalendar>,
+    bots: Vec<String>,
+}
+
+impl ReviewerLatency {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            business_hours: None,
+            bots,
+        }
+    }
+
+    /// Count only the business hours of `calendar`, leaving out nights, weekends and holidays.
+    pub fn with_business_hours(mut self, calendar: BusinessCalendar) -> Self {
+        self.business_hours = Some(calendar);
+        self
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/reviewer_latency.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewerLatencyQuery;
+use reviewer_latency_query as rlq;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/reviewer_latency.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct ReviewerLatencyReviews;
+use reviewer_latency_reviews as rlr;
+
+/// A review: who left it, whether they are a GitHub App, and when it was submitted.
+type Review = (String, bool, DateTime);
+
+#[async_trait]
+impl Producer for ReviewerLatency {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Reviewer"),
+            String::from("PRs reviewed"),
+            match self.business_hours {
+                Some(_) => String::from("Median business hours"),
+                None => String::from("Median hours"),
+            },
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Reviewer latency", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let latencies = review_latencies(
+                    &mut self.graphql,
+                    &self.org_name,
+                    repo_name,
+                    &self.start_date,
+                    &self.end_date,
+                    self.business_hours.as_ref(),
+                    &self.bots,
+                )
+                .await?;
+
+                for (reviewer, hours) in latencies {
+                    let prs_reviewed = hours.len();
+                    let median_hours = median(hours).unwrap_or_default();
+                    tx.send(vec![
+                        repo_name.clone(),
+                        reviewer,
+                        prs_reviewed.to_string(),
+                        format!("{:.1}", median_hours),
+                    ])
+                    .await?;
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects, for each reviewer, the hours between each PR becoming ready for review
+/// and that reviewer's first review of it (only the business hours of `business_hours`,
+/// if given). Self-reviews are not counted.
+///
+/// Returns `(reviewer, hours)` pairs sorted by reviewer.
+#[throws]
+async fn review_latencies(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    business_hours: Option<&BusinessCalendar>,
+    bots: &[String],
+) -> Vec<(String, Vec<f64>)> {
+    let mut latencies: HashMap<String, Vec<f64>> = HashMap::new();
+
+    for pr in first_reviews(graphql, org_name, repo_name, start_date, end_date, bots).await? {
+        for (reviewer, reviewed_at) in pr.reviewed_at {
+            // Reviews left while the PR was still a draft count as immediate.
+            let hours = match business_hours {
+                Some(calendar) => business_hours_between(pr.ready_at, reviewed_at, calendar),
+                None => hours_between(pr.ready_at, reviewed_at).max(0.0),
+            };
+            latencies.entry(reviewer).or_default().push(hours);
+        }
+    }
+
+    let mut latencies: Vec<_> = latencies.into_iter().collect();
+    latencies.sort_by(|(a, _), (b, _)| a.cmp(b));
+    latencies
+}
+
+/// When a PR became ready for review, and was first reviewed by each of its reviewers.
+pub(super) struct FirstReviews {
+    /// When the PR was opened, or marked ready if it was opened as a draft.
+    pub(super) ready_at: DateTime,
+    /// Whether the PR is still a draft, and so not ready for review yet.
+    pub(super) is_draft: bool,
+    /// When each reviewer first reviewed the PR. Self-reviews are not counted.
+    pub(super) reviewed_at: HashMap<String, DateTime>,
+}
+
+/// Lists, for each PR opened in the given time period, when it became ready for review
+/// and when each of its reviewers first reviewed it. PRs and reviews by bots (GitHub
+/// Apps, or any of `bots`) are left out.
+#[throws]
+pub(super) async fn first_reviews(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    bots: &[String],
+) -> Vec<FirstReviews> {
+    let mut prs = vec![];
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(50);
+        let response_data = graphql
+            .query(ReviewerLatencyQuery)
+            .execute_checked(rlq::Variables {
+                page_size,
+                query_string: format!(
+                    r#"repo:{org_name}/{repo_name} is:pr created:{start_date}..{end_date}"#,
+                    org_name = org_name,
+                    repo_name = repo_name,
+                    start_date = start_date,
+                    end_date = end_date,
+                ),
+                after_cursor,
+            })
+            .await?;
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            let pr = match node {
+                rlq::ReviewerLatencyQuerySearchNodes::PullRequest(pr) => pr,
+                _ => continue,
+            };
+            let by_bot = pr.author.as_ref().is_some_and(|a| {
+                is_bot(
+                    &a.login,
+                    matches!(
+                        a.on,
+                        rlq::ReviewerLatencyQuerySearchNodesOnPullRequestAuthorOn::Bot
+                    ),
+                    bots,
+                )
+            });
+            if by_bot {
+                continue;
+            }
+
+            let author = pr.author.map(|a| a.login);
+            let ready_at = pr
+                .timeline_items
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .find_map(|item| match item {
+                    rlq::ReviewerLatencyQuerySearchNodesOnPullRequestTimelineItemsNodes::ReadyForReviewEvent(e) => Some(e.created_at),
+                    _ => None,
+                })
+                .unwrap_or(pr.created_at);
+
+            let mut reviews = vec![];
+            if let Some(page) = pr.reviews {
+                let nodes = page.nodes.into_iter().flatten().flatten();
+                reviews.extend(nodes.filter_map(search_review));
+                if page.page_info.has_next_page {
+                    reviews.extend(
+                        remaining_reviews(
+                            graphql,
+                            org_name,
+                            repo_name,
+                            pr.number,
+                            page.page_info.end_cursor,
+                        )
+                        .await?,
+                    );
+                }
+            }
+
+            // The first review by each reviewer on this PR.
+            let mut reviewed_at: HashMap<String, DateTime> = HashMap::new();
+            for (login, is_app, submitted_at) in reviews {
+                if is_bot(&login, is_app, bots) {
+                    continue;
+                }
+                // you don't count as a reviewer if you review your own PR
+                if author.as_deref() == Some(login.as_str()) {
+                    continue;
+                }
+                let first = reviewed_at.entry(login).or_insert(submitted_at);
+                *first = (*first).min(submitted_at);
+            }
+
+            prs.push(FirstReviews {
+                ready_at,
+                is_draft: pr.is_draft,
+                reviewed_at,
+            });
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    prs
+}
+
+/// Lists the reviews of the PR, starting at `after_cursor`.
+#[throws]
+async fn remaining_reviews(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    number: i64,
+    mut after_cursor: Option<String>,
+) -> Vec<Review> {
+    let mut reviews = vec![];
+
+    loop {
+        let response_data = graphql
+            .query(ReviewerLatencyReviews)
+            .execute_checked(rlr::Variables {
+                owner: org_name.to_string(),
+                repo_name: repo_name.to_string(),
+                number,
+                after_cursor,
+            })
+            .await?;
+        let page = match response_data
+            .repository
+            .and_then(|r| r.pull_request)
+            .and_then(|pr| pr.reviews)
+        {
+            Some(page) => page,
+            None => break,
+        };
+
+        let nodes = page.nodes.into_iter().flatten().flatten();
+        reviews.extend(nodes.filter_map(remaining_review));
+
+        if page.page_info.has_next_page {
+            after_cursor = page.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    reviews
+}
+
+/// The review, unless its author or submission time is unknown.
+fn search_review(
+    review: rlq::ReviewerLatencyQuerySearchNodesOnPullRequestReviewsNodes,
+) -> Option<Review> {
+    use rlq::ReviewerLatencyQuerySearchNodesOnPullRequestReviewsNodesAuthorOn as AuthorOn;
+
+    let author = review.author?;
+    let is_app = matches!(author.on, AuthorOn::Bot);
+    Some((author.login, is_app, review.submitted_at?))
+}
+
+/// Same as `search_review`, for the reviews of `ReviewerLatencyReviews`.
+fn remaining_review(
+    review: rlr::ReviewerLatencyReviewsRepositoryPullRequestReviewsNodes,
+) -> Option<Review> {
+    use rlr::ReviewerLatencyReviewsRepositoryPullRequestReviewsNodesAuthorOn as AuthorOn;
+
+    let author = review.author?;
+    let is_app = matches!(author.on, AuthorOn::Bot);
+    Some((author.login, is_app, review.submitted_at?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(
+        number: u64,
+        created_at: &str,
+        ready_at: Option<&str>,
+        reviews: &[(&str, &str)],
+    ) -> serde_json::Value {
+        let ready: Vec<_> = ready_at
+            .iter()
+            .map(|at| serde_json::json!({ "__typename": "ReadyForReviewEvent", "createdAt": at }))
+            .collect();
+        let reviews: Vec<_> = reviews
+            .iter()
+            .map(|(login, at)| {
+                serde_json::json!({
+                    "submittedAt": at,
+                    "author": { "__typename": "User", "login": login },
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "number": number,
+            "createdAt": created_at,
+            "isDraft": false,
+            "author": { "__typename": "User", "login": "alice" },
+            "timelineItems": { "nodes": ready },
+            "reviews": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": reviews,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn median_hours_to_each_reviewers_first_review() {
+        let graphql = replaying(
+            "reviewer-latency",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    pr(1, "2024-01-02T00:00:00Z", None, &[
+                        ("alice", "2024-01-02T00:30:00Z"),
+                        ("bob", "2024-01-02T01:00:00Z"),
+                        ("carol", "2024-01-02T02:00:00Z"),
+                        ("carol", "2024-01-02T05:00:00Z"),
+                    ]),
+                    pr(2, "2024-01-03T00:00:00Z", None, &[("bob", "2024-01-03T04:00:00Z")]),
+                    // opened as a draft, and only ready for review ten hours later
+                    pr(3, "2024-01-04T00:00:00Z", Some("2024-01-04T10:00:00Z"), &[
+                        ("bob", "2024-01-04T20:00:00Z"),
+                        ("carol", "2024-01-04T11:00:00Z"),
+                    ]),
+                ],
+            } } })],
+        );
+        let producer = ReviewerLatency::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(
+            rows(producer).await,
+            [["rust", "bob", "3", "4.0"], ["rust", "carol", "2", "1.5"]]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::is_bot;
+
+/// Counts how many of the PRs merged in the given time period were merged by their
+/// own author. PRs by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct SelfMerges {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl SelfMerges {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/self_merges.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct SelfMergesQuery;
+use self_merges_query as smq;
+
+#[async_trait]
+impl Producer for SelfMerges {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Self-merged count"),
+            String::from("Total merged"),
+            String::from("Rate"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Self merges", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut self_merged = 0;
+                let mut total = 0;
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(SelfMergesQuery)
+                        .execute_checked(smq::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:pr is:merged merged:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let pr = match node {
+                            smq::SelfMergesQuerySearchNodes::PullRequest(pr) => pr,
+                            _ => continue,
+                        };
+                        // only users have a login here; bots and deleted accounts don't
+                        let author = match pr.author {
+                            Some(smq::SelfMergesQuerySearchNodesOnPullRequestAuthor::User(
+                                user,
+                            )) => user.login,
+                            _ => continue,
+                        };
+                        if is_bot(&author, false, &self.bots) {
+                            continue;
+                        }
+                        let merged_by = match pr.merged_by {
+                            Some(smq::SelfMergesQuerySearchNodesOnPullRequestMergedBy::User(
+                                user,
+                            )) => Some(user.login),
+                            _ => None,
+                        };
+
+                        total += 1;
+                        if merged_by.as_deref() == Some(author.as_str()) {
+                            self_merged += 1;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+
+                let rate = if total == 0 {
+                    String::new()
+                } else {
+                    format!("{:.2}", self_merged as f64 / total as f64)
+                };
+                tx.send(vec![
+                    repo_name.clone(),
+                    self_merged.to_string(),
+                    total.to_string(),
+                    rate,
+                ])
+                .await?;
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn pr(author: &str, merged_by: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "author": { "__typename": "User", "login": author },
+            "mergedBy": { "__typename": "User", "login": merged_by },
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_prs_merged_by_their_author() {
+        let graphql = replaying(
+            "self-merges",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [pr("alice", "alice"), pr("bob", "carol")],
+            } } })],
+        );
+        let producer = SelfMerges::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "1", "2", "0.50"]]);
+    }
+}
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use futures::future::{join_all, BoxFuture};
+use stable_eyre::eyre;
+use stable_eyre::eyre::WrapErr;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use super::{Consumer, DEFAULT_CHANNEL_CAPACITY};
+
+type BoxedConsumer = Box<
+    dyn FnOnce(Receiver<Vec<String>>, Vec<String>) -> BoxFuture<'static, eyre::Result<()>> + Send,
+>;
+
+/// A consumer that re-broadcasts every row it receives to several other consumers,
+/// so that a single producer run can feed e.g. both a CSV file and another sink.
+///
+/// All consumers run concurrently. If one of them fails, the remaining consumers
+/// still receive every row; the first error is reported once all have finished.
+pub struct Tee {
+    consumers: Vec<BoxedConsumer>,
+}
+
+impl Default for Tee {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tee {
+    pub fn new() -> Self {
+        Self { consumers: vec![] }
+    }
+
+    /// Adds another consumer that will receive a copy of every row.
+    pub fn with(mut self, consumer: impl Consumer + Send + 'static) -> Self {
+        self.consumers.push(Box::new(move |mut rx, column_names| {
+            Box::pin(async move { consumer.consume(&mut rx, column_names).await })
+        }));
+        self
+    }
+}
+
+#[async_trait]
+impl Consumer for Tee {
+    async fn consume(
+        self,
+        rx: &mut Receiver<Vec<String>>,
+        column_names: Vec<String>,
+    ) -> eyre::Result<()> {
+        let mut senders: Vec<Option<Sender<Vec<String>>>> = vec![];
+        let mut tasks = vec![];
+        for consumer in self.consumers {
+            let (tx, consumer_rx) = mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
+            senders.push(Some(tx));
+            tasks.push(consumer(consumer_rx, column_names.clone()));
+        }
+
+        let fan_out = async move {
+            while let Some(entry) = rx.recv().await {
+                for sender in &mut senders {
+                    if let Some(tx) = sender {
+                        // A send only fails if that consumer has stopped (i.e., errored);
+                        // keep feeding the others.
+                        if tx.send(entry.clone()).await.is_err() {
+                            *sender = None;
+                        }
+                    }
+                }
+            }
+        };
+
+        let ((), results) = futures::join!(fan_out, join_all(tasks));
+
+        for (index, result) in results.into_iter().enumerate() {
+            result.wrap_err_with(|| format!("Tee consumer #{} failed", index))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Keeps the column names and every row it receives.
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<Vec<String>>>>);
+
+    #[async_trait]
+    impl Consumer for Collect {
+        async fn consume(
+            self,
+            rx: &mut Receiver<Vec<String>>,
+            column_names: Vec<String>,
+        ) -> eyre::Result<()> {
+            self.0.lock().unwrap().push(column_names);
+            while let Some(row) = rx.recv().await {
+                self.0.lock().unwrap().push(row);
+            }
+            Ok(())
+        }
+    }
+
+    /// Fails after its first row.
+    struct FailAfterOne;
+
+    #[async_trait]
+    impl Consumer for FailAfterOne {
+        async fn consume(
+            self,
+            rx: &mut Receiver<Vec<String>>,
+            _column_names: Vec<String>,
+        ) -> eyre::Result<()> {
+            rx.recv().await;
+            eyre::bail!("disk full")
+        }
+    }
+
+    /// Has `tee` consume `count` rows, more than fit in a channel at once.
+    async fn tee_rows(tee: Tee, count: usize) -> eyre::Result<()> {
+        let (tx, mut rx) = mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
+        let send = async move {
+            for i in 0..count {
+                tx.send(vec![i.to_string()]).await.unwrap();
+            }
+        };
+        let (result, ()) = futures::join!(tee.consume(&mut rx, vec!["Row".to_string()]), send);
+        result
+    }
+
+    fn expected(count: usize) -> Vec<Vec<String>> {
+        std::iter::once(vec!["Row".to_string()])
+            .chain((0..count).map(|i| vec![i.to_string()]))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn every_consumer_gets_every_row() {
+        let count = DEFAULT_CHANNEL_CAPACITY * 3;
+        let (first, second) = (Collect::default(), Collect::default());
+        tee_rows(Tee::new().with(first.clone()).with(second.clone()), count)
+            .await
+            .unwrap();
+        assert_eq!(*first.0.lock().unwrap(), expected(count));
+        assert_eq!(*second.0.lock().unwrap(), expected(count));
+    }
+
+    #[tokio::test]
+    async fn a_failing_consumer_fails_the_tee_but_not_the_others() {
+        let count = DEFAULT_CHANNEL_CAPACITY * 3;
+        let collect = Collect::default();
+        let error = tee_rows(Tee::new().with(FailAfterOne).with(collect.clone()), count)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Tee consumer #0 failed");
+        assert_eq!(error.root_cause().to_string(), "disk full");
+        assert_eq!(*collect.0.lock().unwrap(), expected(count));
+    }
+}
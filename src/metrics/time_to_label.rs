@@ -0,0 +1,186 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{hours_between, is_bot};
+
+/// Lists, for each issue opened in the given time period, how many hours passed
+/// before it was first labeled. Issues that were never labeled are skipped.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct TimeToLabel {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl TimeToLabel {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/time_to_label.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct TimeToLabelQuery;
+use time_to_label_query as ttl;
+
+#[async_trait]
+impl Producer for TimeToLabel {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            String::from("Hours to first label"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Time to label", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(TimeToLabelQuery)
+                        .execute_checked(ttl::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let issue = match node {
+                            ttl::TimeToLabelQuerySearchNodes::Issue(issue) => issue,
+                            _ => continue,
+                        };
+                        let by_bot = issue.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, ttl::TimeToLabelQuerySearchNodesOnIssueAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+
+                        let first_labeled_at = issue
+                            .timeline_items
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .find_map(|item| match item {
+                                ttl::TimeToLabelQuerySearchNodesOnIssueTimelineItemsNodes::LabeledEvent(e) => Some(e.created_at),
+                                _ => None,
+                            });
+
+                        if let Some(labeled_at) = first_labeled_at {
+                            // Labels applied while the issue was being filed count as immediate.
+                            let hours = hours_between(issue.created_at, labeled_at).max(0.0);
+                            tx.send(vec![
+                                repo_name.clone(),
+                                issue.number.to_string(),
+                                format!("{:.1}", hours),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn issue(number: u64, labeled_at: Option<&str>) -> serde_json::Value {
+        let labeled: Vec<_> = labeled_at
+            .iter()
+            .map(|at| serde_json::json!({ "__typename": "LabeledEvent", "createdAt": at }))
+            .collect();
+        serde_json::json!({
+            "__typename": "Issue",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "createdAt": "2024-01-02T00:00:00Z",
+            "timelineItems": { "nodes": labeled },
+        })
+    }
+
+    #[tokio::test]
+    async fn hours_to_label_of_labeled_issues() {
+        let graphql = replaying(
+            "time-to-label",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    issue(1, Some("2024-01-02T00:45:00Z")),
+                    issue(2, None),
+                ],
+            } } })],
+        );
+        let producer = TimeToLabel::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        // the issue that was never labeled has no time to label
+        assert_eq!(rows(producer).await, [["rust", "1", "0.8"]]);
+    }
+}
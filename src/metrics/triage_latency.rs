@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use graphql_client::GraphQLQuery;
+use stable_eyre::eyre;
+use tokio::sync::mpsc::Sender;
+use toml::value::Datetime;
+use tracing::Instrument;
+
+use super::{Graphql, Producer};
+use crate::progress::Progress;
+use crate::util::{hours_between, is_bot};
+
+/// Lists, for each issue opened in the given time period, how many hours passed
+/// before it was triaged: first labeled, assigned or added to a milestone, whichever
+/// came first. Issues that were never triaged are skipped.
+///
+/// Issues opened by bots (GitHub Apps, or any of the given `bots`) are left out.
+pub struct TriageLatency {
+    graphql: Graphql,
+    org_name: String,
+    repo_names: Vec<String>,
+    start_date: Datetime,
+    end_date: Datetime,
+    bots: Vec<String>,
+}
+
+impl TriageLatency {
+    pub fn new(
+        graphql: Graphql,
+        org_name: String,
+        repo_names: Vec<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+        bots: Vec<String>,
+    ) -> Self {
+        Self {
+            graphql,
+            org_name,
+            repo_names,
+            start_date,
+            end_date,
+            bots,
+        }
+    }
+}
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/triage_latency.graphql",
+    response_derives = "Serialize,Debug"
+)]
+pub struct TriageLatencyQuery;
+use triage_latency_query as tl;
+
+#[async_trait]
+impl Producer for TriageLatency {
+    fn column_names(&self) -> Vec<String> {
+        vec![
+            String::from("Repository"),
+            String::from("Issue number"),
+            String::from("Hours to triage"),
+        ]
+    }
+
+    async fn producer_task(mut self, tx: Sender<Vec<String>>) -> Result<(), eyre::Error> {
+        let mut progress = Progress::new("Triage latency", self.repo_names.len());
+        for repo_name in &self.repo_names {
+            let span = super::repo_span(&self.org_name, repo_name);
+            async {
+                let mut after_cursor = None;
+
+                loop {
+                    let page_size = self.graphql.page_size(50);
+                    let response_data = self
+                        .graphql
+                        .query(TriageLatencyQuery)
+                        .execute_checked(tl::Variables {
+                            page_size,
+                            query_string: format!(
+                                r#"repo:{org_name}/{repo_name} is:issue created:{start_date}..{end_date}"#,
+                                org_name = self.org_name,
+                                repo_name = repo_name,
+                                start_date = self.start_date,
+                                end_date = self.end_date,
+                            ),
+                            after_cursor,
+                        })
+                        .await?;
+
+                    for node in response_data.search.nodes.into_iter().flatten().flatten() {
+                        let issue = match node {
+                            tl::TriageLatencyQuerySearchNodes::Issue(issue) => issue,
+                            _ => continue,
+                        };
+                        let by_bot = issue.author.as_ref().is_some_and(|a| {
+                            let is_app = matches!(a.on, tl::TriageLatencyQuerySearchNodesOnIssueAuthorOn::Bot);
+                            is_bot(&a.login, is_app, &self.bots)
+                        });
+                        if by_bot {
+                            continue;
+                        }
+
+                        use tl::TriageLatencyQuerySearchNodesOnIssueTimelineItemsNodes as Item;
+                        let triaged_at = issue
+                            .timeline_items
+                            .nodes
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                            .filter_map(|item| match item {
+                                Item::LabeledEvent(e) => Some(e.created_at),
+                                Item::AssignedEvent(e) => Some(e.created_at),
+                                Item::MilestonedEvent(e) => Some(e.created_at),
+                                _ => None,
+                            })
+                            .min();
+
+                        if let Some(triaged_at) = triaged_at {
+                            // Triage done while the issue was being filed counts as immediate.
+                            let hours = hours_between(issue.created_at, triaged_at).max(0.0);
+                            tx.send(vec![
+                                repo_name.clone(),
+                                issue.number.to_string(),
+                                format!("{:.1}", hours),
+                            ])
+                            .await?;
+                        }
+                    }
+
+                    if response_data.search.page_info.has_next_page {
+                        after_cursor = response_data.search.page_info.end_cursor;
+                    } else {
+                        break;
+                    }
+                }
+                Ok::<_, eyre::Error>(())
+            }
+            .instrument(span)
+            .await?;
+            progress.inc(repo_name);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::{replaying, rows};
+
+    fn issue(number: i64, created_at: &str, timeline: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "Issue",
+            "author": { "__typename": "User", "login": "alice" },
+            "number": number,
+            "createdAt": created_at,
+            "timelineItems": { "nodes": timeline },
+        })
+    }
+
+    fn event(typename: &str, created_at: &str) -> serde_json::Value {
+        serde_json::json!({ "__typename": typename, "createdAt": created_at })
+    }
+
+    #[tokio::test]
+    async fn an_assignment_counts_as_triage() {
+        let graphql = replaying(
+            "triage-latency",
+            &[serde_json::json!({ "data": { "search": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [
+                    issue(
+                        1,
+                        "2024-01-02T10:00:00Z",
+                        vec![
+                            event("AssignedEvent", "2024-01-02T12:30:00Z"),
+                            event("LabeledEvent", "2024-01-03T10:00:00Z"),
+                        ],
+                    ),
+                    // never triaged
+                    issue(2, "2024-01-04T10:00:00Z", vec![]),
+                ],
+            } } })],
+        );
+        let producer = TriageLatency::new(
+            graphql,
+            "rust-lang".to_string(),
+            vec!["rust".to_string()],
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+            vec![],
+        );
+        assert_eq!(rows(producer).await, [["rust", "1", "2.5"]]);
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
 use fehler::throws;
 use graphql_client::GraphQLQuery;
-use log::debug;
-use stable_eyre::eyre::Error;
+use serde::Deserialize;
+use stable_eyre::eyre::{self, Error};
 use toml::value::Datetime;
+use tracing::debug;
 
 use super::Graphql;
 
@@ -17,43 +21,49 @@ use super::Graphql;
 )]
 struct OrgRepos;
 
+/// Lists the names of the repositories owned by `org`, which may be an organization
+/// or a user account.
 #[throws]
 pub async fn all_repos(graphql: &mut Graphql, org: &str) -> Vec<String> {
-    let org_name = format!("{}", org);
+    let org_name = org.to_string();
     let mut repos: Vec<String> = vec![];
     let mut after_cursor = None;
 
     loop {
-        let res = graphql
+        let response_data = graphql
             .query(OrgRepos)
-            .execute(org_repos::Variables {
+            .execute_checked(org_repos::Variables {
                 org_name: org_name.to_owned(),
                 after_cursor,
             })
             .await?;
-
-        let response_data = res.data.expect("missing response data");
-        let repos_data = if let Some(org_data) = response_data.organization {
-            org_data.repositories
-        } else {
-            break;
+        let owner = match response_data.repository_owner {
+            Some(owner) => owner,
+            None => eyre::bail!("There is no organization or user named `{}`", org),
         };
 
-        if let Some(edges) = repos_data.edges {
-            for edge in edges.iter() {
-                if let Some(Some(name)) = edge
-                    .as_ref()
-                    .map(|e| e.node.as_ref().map(|n| n.name.to_owned()))
-                {
-                    repos.push(name);
-                }
+        // Organizations and users have the same kind of repository list,
+        // but the generated types differ.
+        let (names, page_info) = match owner {
+            org_repos::OrgReposRepositoryOwner::Organization(o) => {
+                let edges = o.repositories.edges.into_iter().flatten().flatten();
+                let names: Vec<_> = edges.filter_map(|e| e.node.map(|n| n.name)).collect();
+                let page_info = o.repositories.page_info;
+                (names, (page_info.has_next_page, page_info.end_cursor))
             }
-        }
+            org_repos::OrgReposRepositoryOwner::User(u) => {
+                debug!("`{}` is a user, not an organization", org);
+                let edges = u.repositories.edges.into_iter().flatten().flatten();
+                let names: Vec<_> = edges.filter_map(|e| e.node.map(|n| n.name)).collect();
+                let page_info = u.repositories.page_info;
+                (names, (page_info.has_next_page, page_info.end_cursor))
+            }
+        };
+        repos.extend(names);
 
-        if repos_data.page_info.has_next_page {
-            after_cursor = repos_data.page_info.end_cursor;
-        } else {
-            break;
+        match page_info {
+            (true, end_cursor) => after_cursor = end_cursor,
+            (false, _) => break,
         }
     }
 
@@ -83,16 +93,79 @@ impl CountIssues {
     /// - `query_string` — The relevant `query_string` to pass into the GQL query
     #[throws]
     pub async fn query(graphql: &mut Graphql, query_string: String) -> usize {
-        let response = graphql
+        let response_data = graphql
             .query(Self)
-            .execute(count_issues::Variables { query_string })
+            .execute_checked(count_issues::Variables { query_string })
             .await?;
-        let response_data = response.data.expect("missing response data");
         let count = response_data.search.issue_count;
         count as usize
     }
 }
 
+/// The most results GitHub's search returns for a single query, however many pages are requested.
+const SEARCH_RESULT_LIMIT: i64 = 1000;
+
+/// A struct representation of the GraphQL query found in `gql/count_by_repo.graphql`
+///
+/// Used to count the issues (or PRs) matching an org-wide `query_string` per repository.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "gql/schema.docs.graphql",
+    query_path = "gql/count_by_repo.graphql",
+    response_derives = "Serialize,Debug"
+)]
+struct CountByRepo;
+
+/// Counts the issues or PRs matching `query_string` (e.g. an `org:` search) per repository,
+/// paginating through a single search rather than making one query per repository.
+///
+/// Returns `None` if more results match than GitHub's search will return, in which case
+/// the counts have to be made per repository instead.
+#[throws]
+pub(super) async fn count_by_repo(
+    graphql: &mut Graphql,
+    query_string: String,
+) -> Option<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+    let mut after_cursor = None;
+
+    loop {
+        let page_size = graphql.page_size(100);
+        let response_data = graphql
+            .query(CountByRepo)
+            .execute_checked(count_by_repo::Variables {
+                page_size,
+                query_string: query_string.clone(),
+                after_cursor,
+            })
+            .await?;
+        if response_data.search.issue_count > SEARCH_RESULT_LIMIT {
+            debug!(
+                "{} results for `{}`, more than search returns",
+                response_data.search.issue_count, query_string
+            );
+            return None;
+        }
+
+        for node in response_data.search.nodes.into_iter().flatten().flatten() {
+            let repo_name = match node {
+                count_by_repo::CountByRepoSearchNodes::Issue(issue) => issue.repository.name,
+                count_by_repo::CountByRepoSearchNodes::PullRequest(pr) => pr.repository.name,
+                _ => continue,
+            };
+            *counts.entry(repo_name).or_insert(0) += 1;
+        }
+
+        if response_data.search.page_info.has_next_page {
+            after_cursor = response_data.search.page_info.end_cursor;
+        } else {
+            break;
+        }
+    }
+
+    Some(counts)
+}
+
 /// count the number of pull requests created in the given time period for the given repository within the given GitHub organization
 ///
 /// # Arguments
@@ -117,6 +190,114 @@ pub(super) async fn count_pull_requests(
     CountIssues::query(graphql, query_string).await?
 }
 
+/// count the number of pull requests merged in a given time period
+///
+/// # Arguments
+/// - `graphql` — A `graphql_client::GraphQLQuery` instance to make a GQL query
+/// - `org_name` — The name of the github organization that owns the specified repository
+/// - `repo_name` — The name of the repository to count pull requests for. **Note:** repository should exist within the `org_name` GitHub Organization
+/// - `start_date` — The beginning of the relevant time period to search within
+/// - `end_date` — The end of the relevant time period to search within
+#[throws]
+pub(super) async fn count_merged_pull_requests(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+) -> usize {
+    let query_string = format!(
+        r#"repo:{}/{} is:pr is:merged merged:{}..{}"#,
+        org_name, repo_name, start_date, end_date
+    );
+
+    CountIssues::query(graphql, query_string).await?
+}
+
+/// count the pull requests created in a given time period that are still open and
+/// haven't been updated since it ended
+///
+/// # Arguments
+/// - `graphql` — A `graphql_client::GraphQLQuery` instance to make a GQL query
+/// - `org_name` — The name of the github organization that owns the specified repository
+/// - `repo_name` — The name of the repository to count pull requests for. **Note:** repository should exist within the `org_name` GitHub Organization
+/// - `start_date` — The beginning of the relevant time period to search within
+/// - `end_date` — The end of the relevant time period to search within
+#[throws]
+pub(super) async fn count_stale_pull_requests(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+) -> usize {
+    let query_string = format!(
+        r#"repo:{}/{} is:pr is:open created:{}..{} updated:<={}"#,
+        org_name, repo_name, start_date, end_date, end_date
+    );
+
+    CountIssues::query(graphql, query_string).await?
+}
+
+/// Whether a PR must carry any one, or all, of a set of labels to match.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelMatch {
+    #[default]
+    Any,
+    All,
+}
+
+/// Builds a search for the PRs created in a given time period that carry
+/// any or all of `labels`, depending on `label_match`.
+pub(super) fn labeled_pr_query(
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    labels: &[String],
+    label_match: LabelMatch,
+) -> String {
+    let quoted: Vec<String> = labels.iter().map(|l| format!("{:?}", l)).collect();
+    // `label:a,b` matches either label, whereas `label:a label:b` requires both.
+    let label_filter = match label_match {
+        LabelMatch::Any => format!("label:{}", quoted.join(",")),
+        LabelMatch::All => quoted
+            .iter()
+            .map(|l| format!("label:{}", l))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    format!(
+        r#"repo:{}/{} is:pr created:{}..{} {}"#,
+        org_name, repo_name, start_date, end_date, label_filter
+    )
+}
+
+/// count the number of pull requests created in a given time period that carry
+/// any or all of `labels`
+#[throws]
+pub(super) async fn count_labeled_pull_requests(
+    graphql: &mut Graphql,
+    org_name: &str,
+    repo_name: &str,
+    start_date: &Datetime,
+    end_date: &Datetime,
+    labels: &[String],
+    label_match: LabelMatch,
+) -> usize {
+    let query_string = labeled_pr_query(
+        org_name,
+        repo_name,
+        start_date,
+        end_date,
+        labels,
+        label_match,
+    );
+
+    CountIssues::query(graphql, query_string).await?
+}
+
 /// count the number of issues with the given state in a given time period
 ///
 /// # Arguments
@@ -148,3 +329,198 @@ pub(super) async fn count_issues(
 
     CountIssues::query(graphql, query_string).await?
 }
+
+/// Formats `date` as a `GitTimestamp` (an ISO-8601 timestamp), as expected by
+/// e.g. the `since`/`until` arguments of a commit history.
+///
+/// Plain dates are taken to mean midnight UTC, and times without an offset are taken to be UTC.
+pub(super) fn git_timestamp(date: &Datetime) -> String {
+    match (&date.time, &date.offset) {
+        (None, _) => format!("{}T00:00:00Z", date),
+        (Some(_), None) => format!("{}Z", date),
+        (Some(_), Some(_)) => date.to_string(),
+    }
+}
+
+/// Formats the end of a time span ending at `date` as a `GitTimestamp`, like
+/// `git_timestamp`, except that a plain date includes that whole day (as the
+/// `created:a..b` ranges of searches do), so it is taken to mean the following midnight.
+pub(super) fn git_end_timestamp(date: &Datetime) -> String {
+    let next_day = match (&date.date, &date.time) {
+        (Some(d), None) => NaiveDate::from_ymd_opt(d.year.into(), d.month.into(), d.day.into())
+            .and_then(|day| day.succ_opt()),
+        _ => None,
+    };
+    match next_day {
+        Some(next_day) => format!("{}T00:00:00Z", next_day),
+        None => git_timestamp(date),
+    }
+}
+
+/// The length of the periods that time-series metrics split the report's time span into.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    /// Weeks starting on Monday.
+    Week,
+    /// Calendar months.
+    #[default]
+    Month,
+}
+
+impl TimeBucket {
+    /// Splits the days from `start` to `end` (inclusive) into one `(first, last)` range
+    /// per bucket. The first and last ranges are cut short if the span starts or ends
+    /// partway through a bucket.
+    pub(super) fn split(self, start: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut buckets = vec![];
+        let mut first_day = start;
+        while first_day <= end {
+            let next_bucket = self.next_start(first_day);
+            let last_day = next_bucket
+                .pred_opt()
+                .expect("not the first day ever")
+                .min(end);
+            buckets.push((first_day, last_day));
+            first_day = next_bucket;
+        }
+        buckets
+    }
+
+    /// How to label the bucket starting on `first_day`, e.g. `2021-07` for a month.
+    pub(super) fn label(self, first_day: NaiveDate) -> String {
+        match self {
+            TimeBucket::Week => first_day.format("%Y-%m-%d").to_string(),
+            TimeBucket::Month => first_day.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// The first day of the bucket after the one containing `day`.
+    fn next_start(self, day: NaiveDate) -> NaiveDate {
+        match self {
+            TimeBucket::Week => {
+                day + Duration::days(7 - i64::from(day.weekday().num_days_from_monday()))
+            }
+            TimeBucket::Month => if day.month() == 12 {
+                NaiveDate::from_ymd_opt(day.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(day.year(), day.month() + 1, 1)
+            }
+            .expect("the first of a month is a valid date"),
+        }
+    }
+}
+
+/// The ISO week containing `day`, e.g. `2021-W52`. The year is the ISO week-numbering
+/// year, so e.g. 2021-01-01 (a Friday) is in `2020-W53`.
+pub(super) fn iso_week_label(day: NaiveDate) -> String {
+    let week = day.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::testing::replaying;
+
+    fn repositories(owner: &str, names: &[&str], end_cursor: Option<&str>) -> serde_json::Value {
+        let edges: Vec<_> = names
+            .iter()
+            .map(|name| serde_json::json!({ "node": { "name": name } }))
+            .collect();
+        serde_json::json!({ "data": { "repositoryOwner": {
+            "__typename": owner,
+            "repositories": {
+                "edges": edges,
+                "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+            },
+        } } })
+    }
+
+    #[tokio::test]
+    async fn all_repos_of_a_user() {
+        let mut graphql = replaying(
+            "all-repos-user",
+            &[
+                repositories("User", &["dotfiles", "blog"], Some("page-2")),
+                repositories("User", &["optopodi"], None),
+            ],
+        );
+        assert_eq!(
+            all_repos(&mut graphql, "nikomatsakis").await.unwrap(),
+            ["dotfiles", "blog", "optopodi"]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_repos_of_nobody() {
+        let mut graphql = replaying(
+            "all-repos-nobody",
+            &[serde_json::json!({ "data": { "repositoryOwner": null } })],
+        );
+        let error = all_repos(&mut graphql, "no-such-login").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "There is no organization or user named `no-such-login`"
+        );
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn months_are_cut_short_at_the_ends_of_the_span() {
+        assert_eq!(
+            TimeBucket::Month.split(day("2023-12-15"), day("2024-02-10")),
+            [
+                (day("2023-12-15"), day("2023-12-31")),
+                (day("2024-01-01"), day("2024-01-31")),
+                (day("2024-02-01"), day("2024-02-10")),
+            ]
+        );
+        assert_eq!(
+            TimeBucket::Month.split(day("2024-02-01"), day("2024-02-29")),
+            [(day("2024-02-01"), day("2024-02-29"))]
+        );
+        assert_eq!(TimeBucket::Month.label(day("2023-12-15")), "2023-12");
+    }
+
+    #[test]
+    fn labels_are_quoted_and_combined_by_the_label_match() {
+        let labels = vec!["good first issue".to_string(), "E-easy".to_string()];
+        let query = |label_match| {
+            labeled_pr_query(
+                "rust-lang",
+                "rust",
+                &"2024-01-01".parse().unwrap(),
+                &"2024-01-31".parse().unwrap(),
+                &labels,
+                label_match,
+            )
+        };
+        assert_eq!(
+            query(LabelMatch::Any),
+            r#"repo:rust-lang/rust is:pr created:2024-01-01..2024-01-31 label:"good first issue","E-easy""#
+        );
+        assert_eq!(
+            query(LabelMatch::All),
+            r#"repo:rust-lang/rust is:pr created:2024-01-01..2024-01-31 label:"good first issue" label:"E-easy""#
+        );
+    }
+
+    #[test]
+    fn weeks_run_from_monday_to_sunday() {
+        // 2020-12-30 is a Wednesday, in the ISO week-numbering year 2020
+        assert_eq!(
+            TimeBucket::Week.split(day("2020-12-30"), day("2021-01-12")),
+            [
+                (day("2020-12-30"), day("2021-01-03")),
+                (day("2021-01-04"), day("2021-01-10")),
+                (day("2021-01-11"), day("2021-01-12")),
+            ]
+        );
+        assert_eq!(iso_week_label(day("2020-12-30")), "2020-W53");
+        assert_eq!(iso_week_label(day("2021-01-04")), "2021-W01");
+    }
+}
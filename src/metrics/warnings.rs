@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+/// Something that made a metric incomplete, e.g. a repository that was skipped.
+#[derive(Clone, Debug)]
+pub struct Warning {
+    pub metric: String,
+    /// The repository concerned, if the warning is about one in particular.
+    pub repo: Option<String>,
+    pub reason: String,
+}
+
+/// Collects the warnings of a whole report, so that they can be written out together
+/// at the end; each metric gets a copy, `for_metric`, that files warnings under its name.
+#[derive(Clone, Debug, Default)]
+pub struct Warnings {
+    metric: String,
+    warnings: Arc<Mutex<Vec<Warning>>>,
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink filing warnings under `metric`, in the same collection as this one.
+    pub fn for_metric(&self, metric: &str) -> Self {
+        Self {
+            metric: metric.to_string(),
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    /// Logs a warning about `repo` (or the metric as a whole, if `None`) and records it.
+    pub fn warn(&self, repo: Option<&str>, reason: impl Into<String>) {
+        let reason = reason.into();
+        match repo {
+            Some(repo) => tracing::warn!("{}: {}: {}", self.metric, repo, reason),
+            None => tracing::warn!("{}: {}", self.metric, reason),
+        }
+        self.add(repo, reason);
+    }
+
+    /// Records a warning about `repo` (or the metric as a whole, if `None`)
+    /// without logging it, e.g. because it has been logged in more detail already.
+    pub fn add(&self, repo: Option<&str>, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.warnings.lock().unwrap().push(Warning {
+            metric: self.metric.clone(),
+            repo: repo.map(|r| r.to_string()),
+            reason,
+        });
+    }
+
+    /// All of the warnings recorded so far, by any metric, in the order they were recorded.
+    pub fn all(&self) -> Vec<Warning> {
+        self.warnings.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,207 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use fehler::throws;
+use rust_xlsxwriter::Format;
+use stable_eyre::eyre::{self, Error, WrapErr};
+use tokio::sync::mpsc::Receiver;
+
+use super::Consumer;
+
+/// The longest name Excel allows for a worksheet.
+const MAX_SHEET_NAME: usize = 31;
+
+/// The widest a column is sized to fit its contents, in characters.
+const MAX_COLUMN_WIDTH: usize = 80;
+
+/// An Excel workbook that several `Xlsx` consumers add worksheets to, saved once
+/// they have all finished. Clones share the same workbook.
+#[derive(Clone, Default)]
+pub struct Workbook {
+    sheets: Arc<Mutex<Vec<Sheet>>>,
+}
+
+struct Sheet {
+    name: String,
+    column_names: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Workbook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no worksheets have been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.sheets.lock().unwrap().is_empty()
+    }
+
+    /// Writes the workbook, with the worksheets in the order they were added, to `path`.
+    ///
+    /// Fails if a worksheet has more rows or columns than Excel allows.
+    #[throws]
+    pub fn save(&self, path: &Path) {
+        let sheets = self.sheets.lock().unwrap();
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let bold = Format::new().set_bold();
+        for sheet in sheets.iter() {
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(&sheet.name)?;
+            for (col, width) in column_widths(sheet).into_iter().enumerate() {
+                worksheet.set_column_width(u16::try_from(col)?, width as f64)?;
+            }
+            for (col, name) in sheet.column_names.iter().enumerate() {
+                worksheet.write_string_with_format(0, u16::try_from(col)?, name, &bold)?;
+            }
+            for (index, row) in sheet.rows.iter().enumerate() {
+                let row_number = u32::try_from(index + 1)?;
+                for (col, cell) in row.iter().enumerate() {
+                    let col = u16::try_from(col)?;
+                    match cell.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                        Some(number) => worksheet.write_number(row_number, col, number)?,
+                        None => worksheet.write_string(row_number, col, cell)?,
+                    };
+                }
+            }
+        }
+        workbook
+            .save(path)
+            .wrap_err_with(|| format!("Failed to write workbook {:?}", path))?;
+    }
+}
+
+/// Collects all the rows into a worksheet of a shared `Workbook`, with a bold header
+/// row and each column sized to fit, once the producer has finished.
+///
+/// Cells that parse as numbers are written as numbers, so they can be summed and
+/// charted in Excel; everything else is text.
+pub struct Xlsx {
+    workbook: Workbook,
+    sheet_name: String,
+}
+
+impl Xlsx {
+    /// Adds the rows to `workbook` as a worksheet named `sheet_name`, shortened to
+    /// the 31 characters Excel allows.
+    pub fn new(workbook: Workbook, sheet_name: &str) -> Self {
+        let sheet_name = sheet_name
+            .chars()
+            .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+            .take(MAX_SHEET_NAME)
+            .collect();
+        Self {
+            workbook,
+            sheet_name,
+        }
+    }
+}
+
+#[async_trait]
+impl Consumer for Xlsx {
+    async fn consume(
+        self,
+        rx: &mut Receiver<Vec<String>>,
+        column_names: Vec<String>,
+    ) -> eyre::Result<()> {
+        let mut rows = vec![];
+        while let Some(entry) = rx.recv().await {
+            rows.push(entry);
+        }
+
+        self.workbook.sheets.lock().unwrap().push(Sheet {
+            name: self.sheet_name,
+            column_names,
+            rows,
+        });
+        Ok(())
+    }
+}
+
+/// The width of each column, in characters: enough for its longest cell, with a
+/// little padding.
+fn column_widths(sheet: &Sheet) -> Vec<usize> {
+    let mut widths: Vec<usize> = vec![];
+    for row in std::iter::once(&sheet.column_names).chain(&sheet.rows) {
+        for (index, cell) in row.iter().enumerate() {
+            let width = cell.chars().count() + 2;
+            match widths.get_mut(index) {
+                Some(w) => *w = (*w).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    widths
+        .into_iter()
+        .map(|w| w.min(MAX_COLUMN_WIDTH))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{open_workbook, Data, Reader};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn saved_workbook_reads_back() {
+        let workbook = Workbook::new();
+        for (name, rows) in [
+            ("pr-sizes", vec![vec!["rust", "12", "3.5"]]),
+            ("a:sheet/name*longer-than-thirty-one-characters", vec![]),
+        ] {
+            let (tx, mut rx) = mpsc::channel(10);
+            for row in rows {
+                tx.send(row.into_iter().map(String::from).collect())
+                    .await
+                    .unwrap();
+            }
+            drop(tx);
+            let column_names = vec![
+                "Repository".to_string(),
+                "PRs".to_string(),
+                "Avg".to_string(),
+            ];
+            Xlsx::new(workbook.clone(), name)
+                .consume(&mut rx, column_names)
+                .await
+                .unwrap();
+        }
+
+        let dir = std::env::temp_dir().join(format!("optopodi-xlsx-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.xlsx");
+        workbook.save(&path).unwrap();
+
+        let mut excel: calamine::Xlsx<_> = open_workbook(&path).unwrap();
+        assert_eq!(
+            excel.sheet_names(),
+            vec!["pr-sizes", "asheetnamelonger-than-thirty-on"]
+        );
+        let range = excel.worksheet_range("pr-sizes").unwrap();
+        let rows: Vec<&[Data]> = range.rows().collect();
+        assert_eq!(
+            rows,
+            vec![
+                &[
+                    Data::String("Repository".into()),
+                    Data::String("PRs".into()),
+                    Data::String("Avg".into()),
+                ][..],
+                &[
+                    Data::String("rust".into()),
+                    Data::Float(12.0),
+                    Data::Float(3.5),
+                ][..],
+            ]
+        );
+        let range = excel
+            .worksheet_range("asheetnamelonger-than-thirty-on")
+            .unwrap();
+        assert_eq!(range.rows().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
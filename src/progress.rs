@@ -0,0 +1,125 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether progress is drawn at all; set once from `main`.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const BAR_WIDTH: usize = 30;
+
+/// Enables or disables progress output for the whole process.
+///
+/// Progress is never drawn when stderr is not a terminal, regardless of `enabled`.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(
+        enabled && std::io::stderr().is_terminal(),
+        Ordering::Relaxed,
+    );
+}
+
+/// A progress bar drawn on stderr, tracking how many repositories
+/// a producer has processed so far.
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    done: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    /// Starts tracking progress for `total` repositories.
+    pub fn new(label: &'static str, total: usize) -> Self {
+        let progress = Progress {
+            label,
+            total,
+            done: 0,
+            enabled: ENABLED.load(Ordering::Relaxed),
+        };
+        progress.draw("");
+        progress
+    }
+
+    /// Records that `repo` has been processed.
+    pub fn inc(&mut self, repo: &str) {
+        self.inc_to(&mut std::io::stderr(), repo);
+    }
+
+    fn inc_to(&mut self, out: &mut impl Write, repo: &str) {
+        self.done = (self.done + 1).min(self.total);
+        self.draw_to(out, repo);
+        if self.done == self.total && self.enabled {
+            let _ = writeln!(out);
+        }
+    }
+
+    fn draw(&self, repo: &str) {
+        self.draw_to(&mut std::io::stderr(), repo);
+    }
+
+    fn draw_to(&self, out: &mut impl Write, repo: &str) {
+        if !self.enabled {
+            return;
+        }
+        let filled = (self.done * BAR_WIDTH)
+            .checked_div(self.total)
+            .unwrap_or(BAR_WIDTH);
+        let _ = write!(
+            out,
+            "\r\x1b[K{}: [{}{}] {}/{} {}",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total,
+            repo
+        );
+        let _ = out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(enabled: bool) -> Progress {
+        Progress {
+            label: "Commits",
+            total: 2,
+            done: 0,
+            enabled,
+        }
+    }
+
+    #[test]
+    fn nothing_is_drawn_when_disabled() {
+        // as in tests, where `set_enabled` is never called
+        assert!(!Progress::new("Commits", 2).enabled);
+
+        let mut out = vec![];
+        let mut progress = progress(false);
+        progress.draw_to(&mut out, "");
+        progress.inc_to(&mut out, "rust");
+        progress.inc_to(&mut out, "cargo");
+        assert!(out.is_empty());
+        assert_eq!(progress.done, 2);
+    }
+
+    #[test]
+    fn a_bar_is_drawn_when_enabled() {
+        let mut out = vec![];
+        let mut progress = progress(true);
+        progress.inc_to(&mut out, "rust");
+        progress.inc_to(&mut out, "cargo");
+        let out = String::from_utf8(out).unwrap();
+        let half = format!("{}{}", "#".repeat(15), " ".repeat(15));
+        assert!(
+            out.contains(&format!("Commits: [{}] 1/2 rust", half)),
+            "{:?}",
+            out
+        );
+        assert!(
+            out.ends_with(&format!("Commits: [{}] 2/2 cargo\n", "#".repeat(30))),
+            "{:?}",
+            out
+        );
+    }
+}
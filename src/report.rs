@@ -1,20 +1,33 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::sync::Arc;
 use std::{fs::File, path::PathBuf};
 
+use chrono::{DateTime, Duration, Utc};
 use fehler::throws;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 use stable_eyre::eyre::{self, Error, WrapErr};
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
 use toml::value::Datetime;
 
-use crate::metrics::Consumer;
-use crate::metrics::{self, Graphql};
+use crate::metrics::{self, GithubContext, Graphql, DEFAULT_MAX_ATTEMPTS};
+use crate::metrics::{Consumer, CsvOptions, LabelMatch, TimeBucket};
+use crate::util;
 
+mod bus_factor;
+mod contributor_balance;
+mod contributor_overlap;
+mod health_score;
 mod high_contributor;
 mod issue_closure;
 mod repo_info;
 mod repo_participant;
+mod reviewer_concentration;
 mod top_crates;
+mod warnings;
+
+pub use high_contributor::SaturationComparison;
 
 pub struct Report {
     /// Directory where to store the data.
@@ -22,13 +35,446 @@ pub struct Report {
 
     /// If true, load the saved graphql queries from disk.
     replay_graphql: bool,
+
+    /// If true, gzip the graphql responses saved to disk.
+    compress_replay: bool,
+
+    /// Organization to analyze instead of the one in `report.toml`.
+    org: Option<String>,
+
+    /// Repositories to analyze instead of the ones in `report.toml`, if non-empty.
+    repos: Vec<String>,
+
+    /// If set, only the first this many of the repositories are analyzed.
+    limit: Option<usize>,
+
+    /// Cancelled to stop the report early, keeping the output written so far.
+    cancel: CancellationToken,
+
+    /// Configuration to use instead of reading `report.toml`.
+    config: Option<ReportConfig>,
+
+    /// Configuration file to read instead of `$DATA_DIR/report.toml`.
+    config_path: Option<PathBuf>,
+
+    /// If true, save GraphQL responses to a scratch directory rather than `$DATA_DIR`,
+    /// so that nothing is written to the data directory.
+    scratch_graphql: bool,
+
+    /// The format of the files streamed straight from producers.
+    format: OutputFormat,
+
+    /// How long each attempt at a GraphQL query may take.
+    timeout: std::time::Duration,
+
+    /// If set, cache GraphQL responses in `$DATA_DIR/cache/`, reusing those saved after this.
+    cache_fresh_after: Option<DateTime<Utc>>,
+
+    /// How many results to fetch per page of a search, from `github.page_size`.
+    page_size: Option<u32>,
+
+    /// Query documents from `$DATA_DIR/gql/` to send instead of the compiled-in ones.
+    query_overrides: Arc<HashMap<String, String>>,
+
+    /// Collects what made the report incomplete, for `warnings.csv`.
+    warnings: metrics::Warnings,
+
+    /// If true, the outputs streamed straight from producers are appended to, with
+    /// the date of the run, rather than overwritten.
+    append: bool,
+
+    /// If set, the outputs streamed straight from producers are also collected here,
+    /// one worksheet each, for `$DATA_DIR/output/report.xlsx`.
+    workbook: Option<metrics::Workbook>,
+}
+
+/// The format of the files produced in `$DATA_DIR/inputs/` and (for the metrics
+/// streamed straight from a producer) in `$DATA_DIR/output/`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// A JSON array of objects keyed by column name.
+    Json,
+    /// One JSON object per line.
+    NdJson,
+}
+
+impl OutputFormat {
+    /// The file extension for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::NdJson => "ndjson",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = Error;
+
+    #[throws]
+    fn from_str(s: &str) -> Self {
+        match s {
+            "csv" => OutputFormat::Csv,
+            "json" => OutputFormat::Json,
+            "ndjson" => OutputFormat::NdJson,
+            _ => eyre::bail!("unknown format {:?}; expected csv, json or ndjson", s),
+        }
+    }
+}
+
+/// Feeds the rows from `rx` to `consumer`, and to `xlsx` as well if given.
+async fn consume(
+    consumer: impl Consumer + Send + 'static,
+    xlsx: Option<metrics::Xlsx>,
+    rx: &mut Receiver<Vec<String>>,
+    column_names: Vec<String>,
+) -> eyre::Result<()> {
+    match xlsx {
+        Some(xlsx) => {
+            metrics::Tee::new()
+                .with(consumer)
+                .with(xlsx)
+                .consume(rx, column_names)
+                .await
+        }
+        None => consumer.consume(rx, column_names).await,
+    }
+}
+
+/// Reads the rows of an input file written by `Report::produce_input` in `format`.
+#[throws]
+fn read_records<T: DeserializeOwned>(path: &Path, format: OutputFormat) -> Vec<T> {
+    if format == OutputFormat::Csv {
+        let mut rdr = csv::Reader::from_path(path)
+            .wrap_err_with(|| format!("Failed to create reader from path {:?}", path))?;
+        return rdr
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .wrap_err_with(|| format!("Failed to deserialize a row of {:?}", path))?;
+    }
+
+    let text = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read input file {:?}", path))?;
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> = match format {
+        OutputFormat::Json => serde_json::from_str(&text)?,
+        _ => text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?,
+    };
+    // Every value is a string, as in the CSV, so deserialize each object as a CSV row
+    // to parse numbers the same way.
+    objects
+        .into_iter()
+        .map(|object| {
+            let (headers, values): (Vec<String>, Vec<String>) = object
+                .into_iter()
+                .map(|(key, value)| (key, value.as_str().unwrap_or_default().to_string()))
+                .unzip();
+            csv::StringRecord::from(values).deserialize(Some(&csv::StringRecord::from(headers)))
+        })
+        .collect::<Result<_, _>>()
+        .wrap_err_with(|| format!("Failed to deserialize a row of {:?}", path))?
+}
+
+/// The error from `Report::run` when some of the metrics failed; the others were
+/// still produced.
+#[derive(Debug)]
+pub struct MetricsFailed {
+    /// The names of the metrics that failed.
+    pub metrics: Vec<String>,
+}
+
+impl std::fmt::Display for MetricsFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to produce {} metric(s): {}",
+            self.metrics.len(),
+            self.metrics.join(", ")
+        )
+    }
 }
 
+impl std::error::Error for MetricsFailed {}
+
+/// The environment variable that can hold the contents of `report.toml`.
+const REPORT_TOML_VAR: &str = "REPORT_TOML";
+
+/// The contents of `report.toml`; see `report-template.toml` for what each option does.
 #[derive(Debug, Deserialize)]
-struct ReportConfig {
+pub struct ReportConfig {
     github: GithubConfig,
     high_contributor: HighContributorConfig,
     data_source: DataSourceConfig,
+    /// How the CSV files in `$DATA_DIR/output/` are formatted.
+    #[serde(default)]
+    csv: CsvOptions,
+    /// Where the top crates come from.
+    #[serde(default)]
+    top_crates: TopCratesConfig,
+    /// Options for `file-hotspots.csv`.
+    #[serde(default)]
+    file_hotspots: FileHotspotsConfig,
+    /// Options for `labeled-pr-counts.csv`.
+    #[serde(default)]
+    labeled_prs: LabeledPrsConfig,
+    /// Options for `inputs/pull-requests.csv`.
+    #[serde(default)]
+    pull_request_list: PullRequestListConfig,
+    /// Options for `inputs/comments.csv`.
+    #[serde(default)]
+    comment_export: CommentExportConfig,
+    /// How `health-score.csv` weighs its subscores.
+    #[serde(default)]
+    health_score: HealthScoreConfig,
+    /// The business hours, for the business-hours variants of the latency metrics.
+    #[serde(default)]
+    business_hours: BusinessHoursConfig,
+    /// The review response time that `review-sla.csv` measures compliance with.
+    #[serde(default)]
+    review_sla: ReviewSlaConfig,
+    /// Names to give columns in the outputs instead of their own, by their own names.
+    #[serde(default)]
+    column_labels: HashMap<String, String>,
+    /// Alternate logins (e.g. work accounts), mapped to the login they are counted as.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// Which metrics to produce.
+    #[serde(default)]
+    metrics: MetricsConfig,
+    /// How many rows each producer may get ahead of the file they are written to.
+    #[serde(default = "default_channel_capacity")]
+    channel_capacity: usize,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct TopCratesConfig {
+    /// A local file or an `http(s)://` URL to get `crate-information.json` from, e.g. a
+    /// pre-downloaded dump or an internal mirror, rather than generating it from crates.io.
+    source: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct FileHotspotsConfig {
+    /// How many of the most-touched files to list per repository.
+    top_n: usize,
+}
+
+impl Default for FileHotspotsConfig {
+    fn default() -> Self {
+        FileHotspotsConfig { top_n: 20 }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct LabeledPrsConfig {
+    /// The labels to count PRs for.
+    labels: Vec<String>,
+    /// Whether the combined count requires any or all of the labels.
+    #[serde(rename = "match")]
+    label_match: LabelMatch,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PullRequestListConfig {
+    /// Whether to list every PR considered; off by default, as the list can be large.
+    enabled: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CommentExportConfig {
+    /// Whether to export the text of every comment; off by default, as there can be
+    /// a great many.
+    enabled: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct BusinessHoursConfig {
+    /// Whether to also produce the latency metrics in business hours; off by default.
+    enabled: bool,
+    /// Weekdays that aren't business days, e.g. public holidays.
+    holidays: Vec<Datetime>,
+}
+
+impl BusinessHoursConfig {
+    /// The business hours in `timezone`, checking that the holidays are plain dates.
+    #[throws]
+    fn calendar(&self, timezone: chrono_tz::Tz) -> util::BusinessCalendar {
+        let holidays = self
+            .holidays
+            .iter()
+            .map(|holiday| {
+                if holiday.time.is_some() {
+                    eyre::bail!("holiday `{}` must be a date, without a time", holiday);
+                }
+                Ok(util::timestamp(holiday)?.date_naive())
+            })
+            .collect::<Result<_, Error>>()?;
+        util::BusinessCalendar { timezone, holidays }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ReviewSlaConfig {
+    /// How many hours a PR may wait for its first review.
+    sla_hours: f64,
+    /// Whether to count only the business hours of `[business_hours]`, rather than all hours.
+    business_hours: bool,
+}
+
+impl Default for ReviewSlaConfig {
+    fn default() -> Self {
+        ReviewSlaConfig {
+            sla_hours: 48.0,
+            business_hours: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct HealthScoreConfig {
+    /// How much each subscore counts towards the score, relative to the others.
+    review_coverage_weight: f64,
+    issue_closure_weight: f64,
+    bus_factor_weight: f64,
+    stale_prs_weight: f64,
+    /// The bus factor at which that subscore is 100.
+    bus_factor_target: u64,
+}
+
+impl Default for HealthScoreConfig {
+    fn default() -> Self {
+        HealthScoreConfig {
+            review_coverage_weight: 25.0,
+            issue_closure_weight: 25.0,
+            bus_factor_weight: 25.0,
+            stale_prs_weight: 25.0,
+            bus_factor_target: 3,
+        }
+    }
+}
+
+impl HealthScoreConfig {
+    /// Checks that the weights are non-negative and not all zero.
+    #[throws]
+    fn validate(&self) {
+        let weights = [
+            self.review_coverage_weight,
+            self.issue_closure_weight,
+            self.bus_factor_weight,
+            self.stale_prs_weight,
+        ];
+        if weights
+            .iter()
+            .any(|weight| !(weight.is_finite() && *weight >= 0.0))
+        {
+            eyre::bail!("weights must be non-negative numbers");
+        }
+        if weights.iter().all(|&weight| weight == 0.0) {
+            eyre::bail!("at least one weight must be more than 0");
+        }
+        if self.bus_factor_target == 0 {
+            eyre::bail!("bus_factor_target must be at least 1");
+        }
+    }
+}
+
+/// The metrics that can be turned off in the `[metrics]` section, by output file name.
+const METRICS: &[&str] = &[
+    "top-crates",
+    "high-contributors",
+    "issue-closures",
+    "contributor-overlap",
+    "reviewer-concentration",
+    "contributor-balance",
+    "bus-factor",
+    "health-score",
+    "commit-counts",
+    "pr-sizes",
+    "closed-without-pr",
+    "issue-reactions",
+    "reviewer-latency",
+    "reviewer-latency-business-hours",
+    "time-to-label",
+    "author-diversity",
+    "issue-trend",
+    "review-comment-volume",
+    "file-hotspots",
+    "labeled-pr-counts",
+    "merge-methods",
+    "first-response-time",
+    "first-response-time-business-hours",
+    "review-request-gap",
+    "pr-outcomes",
+    "pr-discussion",
+    "open-issue-ages",
+    "cross-repo-references",
+    "off-hours-activity",
+    "approval-to-merge",
+    "label-cooccurrence",
+    "draft-pr-usage",
+    "self-merges",
+    "triage-latency",
+    "merge-throughput",
+    "queue-depth",
+    "approval-counts",
+    "label-churn",
+    "ramp-time",
+    "reopens",
+    "review-sla",
+];
+
+/// Whether each metric is produced, keyed by its name with `_` for `-`
+/// (e.g. `issue_closures = false`); metrics that aren't listed are produced.
+#[derive(Deserialize, Debug, Default)]
+#[serde(transparent)]
+struct MetricsConfig {
+    enabled: BTreeMap<String, bool>,
+}
+
+impl MetricsConfig {
+    /// Whether the metric named `metric` (one of `METRICS`) is to be produced.
+    fn enabled(&self, metric: &str) -> bool {
+        self.enabled
+            .get(&metric.replace('-', "_"))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Whether any of `metrics` is to be produced.
+    fn any_enabled(&self, metrics: &[&str]) -> bool {
+        metrics.iter().any(|metric| self.enabled(metric))
+    }
+
+    /// Warns about keys that don't name a metric, as they are probably typos.
+    fn warn_unknown(&self) {
+        for key in self.enabled.keys() {
+            if !METRICS.contains(&key.replace('_', "-").as_str()) {
+                tracing::warn!(
+                    "unknown metric {:?} in [metrics]; expected one of: {}",
+                    key,
+                    METRICS.join(", ").replace('-', "_")
+                );
+            }
+        }
+    }
+}
+
+fn default_channel_capacity() -> usize {
+    metrics::DEFAULT_CHANNEL_CAPACITY
 }
 
 #[derive(Debug)]
@@ -42,37 +488,296 @@ pub struct ReportData {
 struct GithubConfig {
     org: String,
     repos: Vec<String>,
+    /// Repositories to skip, whether `repos` is given explicitly or
+    /// expanded to all repositories in the organization.
+    #[serde(default)]
+    exclude_repos: Vec<String>,
+    /// Branch to use for history-based metrics (e.g. commit counts), per repository.
+    /// Repositories without an entry use their default branch.
+    #[serde(default)]
+    branches: HashMap<String, String>,
+    /// Accounts whose activity is left out of the metrics, besides GitHub Apps.
+    #[serde(default = "default_bots")]
+    bots: Vec<String>,
+    /// Whether to fetch the organization's members, to tag participants in
+    /// `inputs/repo-participants.csv` as internal or external.
+    #[serde(default)]
+    fetch_members: bool,
+    /// How many results to fetch per page of a search, up to `metrics::MAX_PAGE_SIZE`;
+    /// by default, as many as each query is written to.
+    #[serde(default)]
+    page_size: Option<u32>,
+    /// Whether `repos` was expanded to all repositories in the organization.
+    #[serde(skip)]
+    all_repos: bool,
+}
+
+fn default_bots() -> Vec<String> {
+    [
+        "rust-highfive",
+        "bors",
+        "rustbot",
+        "rust-log-analyzer",
+        "rust-timer",
+        "rfcbot",
+    ]
+    .iter()
+    .map(|bot| bot.to_string())
+    .collect()
 }
 
 #[derive(Deserialize, Debug)]
 struct DataSourceConfig {
     start_date: Datetime,
     end_date: Datetime,
+    /// How time-series metrics (e.g. the issue trend) split up the time span.
+    #[serde(default)]
+    trend_bucket: TimeBucket,
+    /// The timezone for metrics about the time of day, e.g. off-hours activity.
+    #[serde(default = "default_timezone")]
+    timezone: chrono_tz::Tz,
+}
+
+fn default_timezone() -> chrono_tz::Tz {
+    chrono_tz::UTC
+}
+
+/// Builds a [`ReportConfig`] in code, for running a report without a `report.toml`.
+///
+/// ```
+/// use optopodi::metrics::CsvOptions;
+/// use optopodi::report::ReportConfig;
+///
+/// let config = ReportConfig::builder(
+///     "rust-lang",
+///     "2021-07-01".parse().unwrap(),
+///     "2021-08-01".parse().unwrap(),
+/// )
+/// .repos(vec!["rust".to_string(), "cargo".to_string()])
+/// .bots(vec!["bors".to_string()])
+/// .csv(CsvOptions {
+///     delimiter: ';',
+///     ..Default::default()
+/// })
+/// .build();
+/// assert_eq!(config.org(), "rust-lang");
+/// assert_eq!(config.repos(), ["rust", "cargo"]);
+/// ```
+pub struct ReportConfigBuilder {
+    config: ReportConfig,
+}
+
+impl ReportConfig {
+    /// Parses a configuration in the format of `report.toml`.
+    #[throws]
+    pub fn from_toml(toml: &str) -> Self {
+        toml::from_str(toml).wrap_err("Failed to parse Report Config")?
+    }
+
+    /// The organization being analyzed.
+    pub fn org(&self) -> &str {
+        &self.github.org
+    }
+
+    /// The repositories being analyzed, after expanding an empty list
+    /// and removing excluded repositories.
+    pub fn repos(&self) -> &[String] {
+        &self.github.repos
+    }
+
+    /// Starts building a configuration analyzing all of `org`'s repositories
+    /// between `start_date` and `end_date`, with default thresholds and CSV options.
+    pub fn builder(
+        org: impl Into<String>,
+        start_date: Datetime,
+        end_date: Datetime,
+    ) -> ReportConfigBuilder {
+        ReportConfigBuilder {
+            config: ReportConfig {
+                github: GithubConfig {
+                    org: org.into(),
+                    repos: vec![],
+                    exclude_repos: vec![],
+                    branches: HashMap::new(),
+                    bots: default_bots(),
+                    fetch_members: false,
+                    page_size: None,
+                    all_repos: false,
+                },
+                high_contributor: HighContributorConfig::default(),
+                data_source: DataSourceConfig {
+                    start_date,
+                    end_date,
+                    trend_bucket: TimeBucket::default(),
+                    timezone: default_timezone(),
+                },
+                csv: CsvOptions::default(),
+                top_crates: TopCratesConfig::default(),
+                file_hotspots: FileHotspotsConfig::default(),
+                labeled_prs: LabeledPrsConfig::default(),
+                pull_request_list: PullRequestListConfig::default(),
+                comment_export: CommentExportConfig::default(),
+                health_score: HealthScoreConfig::default(),
+                business_hours: BusinessHoursConfig::default(),
+                review_sla: ReviewSlaConfig::default(),
+                column_labels: HashMap::new(),
+                aliases: HashMap::new(),
+                metrics: MetricsConfig::default(),
+                channel_capacity: default_channel_capacity(),
+            },
+        }
+    }
+}
+
+impl ReportConfigBuilder {
+    /// Analyze only `repos` rather than all of the organization's repositories.
+    pub fn repos(mut self, repos: Vec<String>) -> Self {
+        self.config.github.repos = repos;
+        self
+    }
+
+    /// Skip `exclude_repos`.
+    pub fn exclude_repos(mut self, exclude_repos: Vec<String>) -> Self {
+        self.config.github.exclude_repos = exclude_repos;
+        self
+    }
+
+    /// Ignore the activity of `bots`.
+    pub fn bots(mut self, bots: Vec<String>) -> Self {
+        self.config.github.bots = bots;
+        self
+    }
+
+    /// Count the activity of each alternate login in `aliases` as that of the login
+    /// it is mapped to.
+    pub fn aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.config.aliases = aliases;
+        self
+    }
+
+    /// Use the given branch, per repository, for history-based metrics.
+    pub fn branches(mut self, branches: HashMap<String, String>) -> Self {
+        self.config.github.branches = branches;
+        self
+    }
+
+    /// Split the time span into weeks or months for time-series metrics.
+    pub fn trend_bucket(mut self, trend_bucket: TimeBucket) -> Self {
+        self.config.data_source.trend_bucket = trend_bucket;
+        self
+    }
+
+    /// Use `timezone` for metrics about the time of day, rather than UTC.
+    pub fn timezone(mut self, timezone: chrono_tz::Tz) -> Self {
+        self.config.data_source.timezone = timezone;
+        self
+    }
+
+    /// Classify participants by the `high_contributor` thresholds rather than the defaults,
+    /// e.g. `HighContributorConfig { high_author_min_prs: 5, ..Default::default() }`.
+    pub fn high_contributor(mut self, high_contributor: HighContributorConfig) -> Self {
+        self.config.high_contributor = high_contributor;
+        self
+    }
+
+    /// Write CSV files with `csv` rather than the default options.
+    pub fn csv(mut self, csv: CsvOptions) -> Self {
+        self.config.csv = csv;
+        self
+    }
+
+    /// Let each producer get at most `channel_capacity` rows ahead of its output.
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.config.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// The finished configuration; it is validated when the report runs.
+    pub fn build(self) -> ReportConfig {
+        self.config
+    }
 }
 
+/// Thresholds for classifying participants as "high contributors".
+///
+/// The default matches `report-template.toml`.
 #[derive(Deserialize, Debug)]
-struct HighContributorConfig {
-    /// Number of categories one must be "high" in
-    /// to be considered a "high contributor".
-    high_reviewer_min_percentage: u64,
+pub struct HighContributorConfig {
+    /// Percentage of a repository's PRs one must review
+    /// in order to be considered a "high reviewer".
+    pub high_reviewer_min_percentage: f64,
     /// Number of Pull Requests one must review
     /// in order to be considered a "high contributor"
-    high_reviewer_min_prs: u64,
+    pub high_reviewer_min_prs: u64,
 
-    reviewer_saturation_threshold: u64,
+    pub reviewer_saturation_threshold: f64,
 
-    author_saturation_threshold: u64,
+    pub author_saturation_threshold: f64,
 
-    high_participant_min_percentage: u64,
+    /// Whether the saturation thresholds must be reached or exceeded.
+    #[serde(default)]
+    pub saturation_comparison: SaturationComparison,
 
-    high_participant_min_prs: u64,
+    pub high_participant_min_percentage: f64,
 
-    high_author_min_percentage: u64,
+    pub high_participant_min_prs: u64,
 
-    high_author_min_prs: u64,
+    pub high_author_min_percentage: f64,
+
+    pub high_author_min_prs: u64,
     /// Number of categories one must be "high" in
     /// to be considered a "high contributor".
-    high_contributor_categories_threshold: u64,
+    pub high_contributor_categories_threshold: u64,
+
+    /// Which columns to write to `high-contributors.csv`, and in what order.
+    /// All columns are written if this is empty.
+    #[serde(default)]
+    pub columns: Vec<String>,
+}
+
+impl Default for HighContributorConfig {
+    fn default() -> Self {
+        HighContributorConfig {
+            high_reviewer_min_percentage: 10.0,
+            high_reviewer_min_prs: 2,
+            reviewer_saturation_threshold: 50.0,
+            author_saturation_threshold: 50.0,
+            saturation_comparison: Default::default(),
+            high_participant_min_percentage: 15.0,
+            high_participant_min_prs: 2,
+            high_author_min_percentage: 10.0,
+            high_author_min_prs: 2,
+            high_contributor_categories_threshold: 2,
+            columns: vec![],
+        }
+    }
+}
+
+impl DataSourceConfig {
+    /// Checks that both dates are real calendar dates and that they form a non-empty range.
+    #[throws]
+    fn validate(&self) {
+        let start = util::timestamp(&self.start_date).wrap_err("Invalid start_date")?;
+        let end = util::timestamp(&self.end_date).wrap_err("Invalid end_date")?;
+        if start > end {
+            eyre::bail!(
+                "start_date ({}) is after end_date ({})",
+                self.start_date,
+                self.end_date
+            );
+        }
+    }
+
+    /// When the time span ends. A plain `end_date` includes that whole day.
+    #[throws]
+    fn end(&self) -> DateTime<Utc> {
+        let end = util::timestamp(&self.end_date)?;
+        if self.end_date.time.is_some() {
+            end
+        } else {
+            end + Duration::days(1)
+        }
+    }
 }
 
 impl Report {
@@ -80,25 +785,170 @@ impl Report {
     ///
     /// # Arguments
     /// - `data_dir` — A path to the directory containing `report.toml`;
-    ///    this is where data will be generated
+    ///   this is where data will be generated
     /// - `replay_graphql` — A boolean indicating whether to load previous GQL response data from disk
-    pub fn new(data_dir: PathBuf, replay_graphql: bool) -> Self {
+    /// - `compress_replay` — A boolean indicating whether to gzip the GQL response data saved to disk
+    pub fn new(data_dir: PathBuf, replay_graphql: bool, compress_replay: bool) -> Self {
         Report {
             data_dir,
             replay_graphql,
+            compress_replay,
+            org: None,
+            repos: vec![],
+            limit: None,
+            cancel: CancellationToken::new(),
+            config: None,
+            config_path: None,
+            scratch_graphql: false,
+            format: OutputFormat::default(),
+            timeout: metrics::DEFAULT_TIMEOUT,
+            cache_fresh_after: None,
+            page_size: None,
+            query_overrides: Default::default(),
+            warnings: metrics::Warnings::new(),
+            append: false,
+            workbook: None,
         }
     }
 
+    /// Analyze `org` instead of the organization configured in `report.toml`.
+    ///
+    /// The configured `repos` belong to the configured organization, so they are
+    /// ignored as well and all of `org`'s repositories are analyzed instead.
+    pub fn with_org(mut self, org: Option<String>) -> Self {
+        self.org = org;
+        self
+    }
+
+    /// Analyze only `repos` instead of the repositories configured in `report.toml`.
+    /// Has no effect if `repos` is empty.
+    pub fn with_repos(mut self, repos: Vec<String>) -> Self {
+        self.repos = repos;
+        self
+    }
+
+    /// Analyze only the first `limit` repositories, once the repository list has been
+    /// worked out (and filtered), e.g. for a quick trial of a configuration change.
+    /// A warning says so, so that the report isn't taken for a full one.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Stop the report when `cancel` is cancelled.
+    ///
+    /// The metric being produced at that point is cut short, but the rows it
+    /// produced so far are still written out; the run then fails as interrupted.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Write the input files, and the outputs streamed straight from producers, in
+    /// `format` rather than as CSV. The other outputs are always CSV.
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Append the rows of each output streamed straight from a producer to the file
+    /// of earlier runs, after a "Run date" column, rather than overwriting it; the header
+    /// is only written to new files. Only CSV files can be appended to.
+    pub fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Also write the outputs streamed straight from producers to an Excel workbook,
+    /// `$DATA_DIR/output/report.xlsx`, with a worksheet for each.
+    pub fn with_xlsx(mut self, xlsx: bool) -> Self {
+        self.workbook = xlsx.then(metrics::Workbook::new);
+        self
+    }
+
+    /// Give up on each attempt at a GraphQL query after `timeout`, rather than
+    /// after `metrics::DEFAULT_TIMEOUT`; failed attempts are retried as usual.
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Read the configuration from `config_path` instead of `report.toml` in the data directory.
+    /// Inputs and outputs are still written to the data directory.
+    pub fn with_config_path(mut self, config_path: Option<PathBuf>) -> Self {
+        self.config_path = config_path;
+        self
+    }
+
+    /// Use `config` instead of reading `report.toml` from the data directory.
+    ///
+    /// Command-line style overrides (`with_org`, `with_repos`) still apply on top of it.
+    pub fn with_config(mut self, config: ReportConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Checks the configuration without producing anything: loads and validates
+    /// `report.toml` (or the configuration given with `with_config`), and checks that
+    /// the organization can be reached and has all of the configured repositories.
+    ///
+    /// Nothing is written to the data directory.
+    #[throws]
+    pub async fn validate(mut self) -> ReportConfig {
+        self.scratch_graphql = true;
+        self.load_query_overrides()?;
+        let config = self.load_config().await.wrap_err("Failed to load config")?;
+
+        let graphql = &mut self.graphql("all-repos");
+        let all_repos = metrics::all_repos(graphql, &config.github.org)
+            .await
+            .wrap_err_with(|| format!("Failed to reach {}", config.github.org))?;
+        let unknown: Vec<_> = config
+            .github
+            .repos
+            .iter()
+            .filter(|repo| !all_repos.contains(repo))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            eyre::bail!(
+                "{} has no repositories named {}",
+                config.github.org,
+                unknown.join(", ")
+            );
+        }
+
+        config
+    }
+
     /// The driving function for the logic side of our app.
     ///
     /// - loads configuration from the data directory
     /// - handle I/O for folder/file creation
     /// - produces relevant input data and its associated files
     /// - generate output data and associated files for each optopodi metric
+    ///
+    /// If only some of the metrics fail, the rest are still produced, and the
+    /// error is (or wraps) a `MetricsFailed`.
     #[throws]
     pub async fn run(mut self) {
+        if self.append && self.format != OutputFormat::Csv {
+            eyre::bail!("only CSV outputs can be appended to");
+        }
+
+        self.load_query_overrides()?;
+
         // Load the report configuration from the data directory.
         let config = Arc::new(self.load_config().await.wrap_err("Failed to load config")?);
+        let business_hours = if config.business_hours.enabled {
+            Some(
+                config
+                    .business_hours
+                    .calendar(config.data_source.timezone)?,
+            )
+        } else {
+            None
+        };
 
         // attempt to create all relevant directories
         tokio::fs::create_dir_all(self.graphql_dir())
@@ -111,6 +961,10 @@ impl Report {
             .await
             .wrap_err("Failed to create Output Directory")?;
 
+        // A metric that fails is logged and recorded in `failures`, and the report
+        // carries on with the rest; the run as a whole fails at the end if any did.
+        let mut failures = vec![];
+
         // generate relevant input data
         //
         // the following function calls will...
@@ -121,52 +975,810 @@ impl Report {
         //
         // the result is this in-memory database, of sorts, with all of the data we
         // will later use for our customized metrics
-        let data = Arc::new(ReportData {
-            top_crates: self
+        // Who we are and what we're looking at; producers that need this get a clone of the `Arc`.
+        let context = GithubContext::load(
+            &mut self.graphql("context"),
+            &config.github.org,
+            config.github.fetch_members,
+        )
+        .await
+        .wrap_err("Failed to load GitHub context")?;
+        tracing::info!(
+            "running as {} against {}",
+            context.viewer_login,
+            match &context.org {
+                Some(org) => org.name.as_deref().unwrap_or(&org.login),
+                None => &config.github.org,
+            }
+        );
+
+        self.page_size = config.github.page_size;
+
+        // Results about a time span that has ended can be reused from the cache, as long
+        // as they were fetched after it ended.
+        self.cache_fresh_after = Some(
+            config
+                .data_source
+                .end()
+                .wrap_err("Invalid data_source.end_date")?,
+        );
+
+        // Inputs are only gathered if a metric derived from them is to be produced;
+        // otherwise they are left empty, which saves their queries.
+        let top_crates = if config.metrics.enabled("top-crates") {
+            let top_crates = self
                 .top_crates(&config)
                 .await
-                .wrap_err("Failed to parse Top Crates")?,
-            repo_participants: self
-                .repo_participants(&config)
+                .wrap_err("Failed to parse Top Crates");
+            self.record(&mut failures, "top-crates", top_crates)?
+        } else {
+            Some(vec![])
+        };
+        let repo_participants = if config.metrics.any_enabled(&[
+            "high-contributors",
+            "contributor-overlap",
+            "reviewer-concentration",
+            "contributor-balance",
+            "bus-factor",
+            "health-score",
+        ]) {
+            let repo_participants = self
+                .repo_participants(&config, &context)
                 .await
-                .wrap_err("Failed to gather Repo Participants")?,
-            repo_infos: self
-                .repo_infos(&config)
+                .wrap_err("Failed to gather Repo Participants");
+            self.record(&mut failures, "repo-participants", repo_participants)?
+        } else {
+            Some(Default::default())
+        };
+        let repo_infos =
+            if config
+                .metrics
+                .any_enabled(&["high-contributors", "issue-closures", "health-score"])
+            {
+                let repo_infos = self
+                    .repo_infos(&config)
+                    .await
+                    .wrap_err("Failed to gather Repo Infos");
+                self.record(&mut failures, "repo-infos", repo_infos)?
+            } else {
+                Some(Default::default())
+            };
+        let data = match (top_crates, repo_participants, repo_infos) {
+            (Some(top_crates), Some(repo_participants), Some(repo_infos)) => {
+                Some(Arc::new(ReportData {
+                    top_crates,
+                    repo_participants,
+                    repo_infos,
+                }))
+            }
+            _ => None,
+        };
+
+        if config.pull_request_list.enabled {
+            let result = self
+                .produce_input(
+                    &config,
+                    "pull-requests",
+                    metrics::PullRequestList::new(
+                        self.graphql("pull-requests"),
+                        config.github.org.clone(),
+                        config.github.repos.clone(),
+                        config.data_source.start_date.clone(),
+                        config.data_source.end_date.clone(),
+                        config.github.bots.clone(),
+                    ),
+                )
                 .await
-                .wrap_err("Failed to gather Repo Infos")?,
-        });
+                .wrap_err("Failed to produce input data for pull-requests");
+            self.record(&mut failures, "pull-requests", result)?;
+        }
+
+        if config.comment_export.enabled {
+            let result = self
+                .produce_input(
+                    &config,
+                    "comments",
+                    metrics::CommentExport::new(
+                        self.graphql("comments"),
+                        config.github.org.clone(),
+                        config.github.repos.clone(),
+                        config.data_source.start_date.clone(),
+                        config.data_source.end_date.clone(),
+                        config.github.bots.clone(),
+                    ),
+                )
+                .await
+                .wrap_err("Failed to produce input data for comments");
+            self.record(&mut failures, "comments", result)?;
+        }
+
+        // Some metrics need no post-processing; their producers stream
+        // straight into CSV files in `$DATA_DIR/output/`.
+        let result = self
+            .produce_output(
+                &config,
+                "commit-counts",
+                metrics::CommitCounts::new(
+                    self.graphql("commit-counts"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.github.branches.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce commit counts");
+        self.record(&mut failures, "commit-counts", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "pr-sizes",
+                metrics::PrSizes::new(
+                    self.graphql("pr-sizes"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce PR sizes");
+        self.record(&mut failures, "pr-sizes", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "closed-without-pr",
+                metrics::ClosedWithoutPr::new(
+                    self.graphql("closed-without-pr"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce issues closed without a PR");
+        self.record(&mut failures, "closed-without-pr", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "issue-reactions",
+                metrics::IssueReactions::new(
+                    self.graphql("issue-reactions"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce issue reactions");
+        self.record(&mut failures, "issue-reactions", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "reviewer-latency",
+                metrics::ReviewerLatency::new(
+                    self.graphql("reviewer-latency"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce reviewer latency");
+        self.record(&mut failures, "reviewer-latency", result)?;
+        if let Some(calendar) = &business_hours {
+            let result = self
+                .produce_output(
+                    &config,
+                    "reviewer-latency-business-hours",
+                    metrics::ReviewerLatency::new(
+                        self.graphql("reviewer-latency-business-hours"),
+                        config.github.org.clone(),
+                        config.github.repos.clone(),
+                        config.data_source.start_date.clone(),
+                        config.data_source.end_date.clone(),
+                        config.github.bots.clone(),
+                    )
+                    .with_business_hours(calendar.clone()),
+                )
+                .await
+                .wrap_err("Failed to produce reviewer latency in business hours");
+            self.record(&mut failures, "reviewer-latency-business-hours", result)?;
+        }
+        let result = self
+            .produce_output(
+                &config,
+                "time-to-label",
+                metrics::TimeToLabel::new(
+                    self.graphql("time-to-label"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce time to label");
+        self.record(&mut failures, "time-to-label", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "author-diversity",
+                metrics::AuthorDiversity::new(
+                    self.graphql("author-diversity"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce author diversity");
+        self.record(&mut failures, "author-diversity", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "issue-trend",
+                metrics::IssueTrend::new(
+                    self.graphql("issue-trend"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.data_source.trend_bucket,
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce issue trend");
+        self.record(&mut failures, "issue-trend", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "review-comment-volume",
+                metrics::ReviewCommentVolume::new(
+                    self.graphql("review-comment-volume"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce review comment volume");
+        self.record(&mut failures, "review-comment-volume", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "file-hotspots",
+                metrics::FileHotspots::new(
+                    self.graphql("file-hotspots"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.file_hotspots.top_n,
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce file hotspots");
+        self.record(&mut failures, "file-hotspots", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "labeled-pr-counts",
+                metrics::LabeledPrCounts::new(
+                    self.graphql("labeled-pr-counts"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.labeled_prs.labels.clone(),
+                    config.labeled_prs.label_match,
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce labeled PR counts");
+        self.record(&mut failures, "labeled-pr-counts", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "merge-methods",
+                metrics::MergeMethods::new(
+                    self.graphql("merge-methods"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce merge methods");
+        self.record(&mut failures, "merge-methods", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "first-response-time",
+                metrics::FirstResponseTime::new(
+                    self.graphql("first-response-time"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce first response time");
+        self.record(&mut failures, "first-response-time", result)?;
+        if let Some(calendar) = &business_hours {
+            let result = self
+                .produce_output(
+                    &config,
+                    "first-response-time-business-hours",
+                    metrics::FirstResponseTime::new(
+                        self.graphql("first-response-time-business-hours"),
+                        config.github.org.clone(),
+                        config.github.repos.clone(),
+                        config.data_source.start_date.clone(),
+                        config.data_source.end_date.clone(),
+                        config.github.bots.clone(),
+                    )
+                    .with_business_hours(calendar.clone()),
+                )
+                .await
+                .wrap_err("Failed to produce first response time in business hours");
+            self.record(&mut failures, "first-response-time-business-hours", result)?;
+        }
+        let result = self
+            .produce_output(
+                &config,
+                "review-request-gap",
+                metrics::ReviewRequestGap::new(
+                    self.graphql("review-request-gap"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce review request gap");
+        self.record(&mut failures, "review-request-gap", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "pr-outcomes",
+                metrics::PrOutcomes::new(
+                    // whether PRs are still open is as of now, not the end of the time span
+                    self.graphql("pr-outcomes").without_cache(),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce PR outcomes");
+        self.record(&mut failures, "pr-outcomes", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "pr-discussion",
+                metrics::PrDiscussion::new(
+                    self.graphql("pr-discussion"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce PR discussion");
+        self.record(&mut failures, "pr-discussion", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "open-issue-ages",
+                metrics::OpenIssueAges::new(
+                    // open issues change whether or not the time span has ended
+                    self.graphql("open-issue-ages").without_cache(),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce open issue ages");
+        self.record(&mut failures, "open-issue-ages", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "cross-repo-references",
+                metrics::CrossRepoReferences::new(
+                    self.graphql("cross-repo-references"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce cross-repo references");
+        self.record(&mut failures, "cross-repo-references", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "off-hours-activity",
+                metrics::OffHoursActivity::new(
+                    self.graphql("off-hours-activity"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.data_source.timezone,
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce off-hours activity");
+        self.record(&mut failures, "off-hours-activity", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "approval-to-merge",
+                metrics::ApprovalToMerge::new(
+                    self.graphql("approval-to-merge"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce approval-to-merge latency");
+        self.record(&mut failures, "approval-to-merge", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "label-cooccurrence",
+                metrics::LabelCooccurrence::new(
+                    self.graphql("label-cooccurrence"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce label co-occurrence");
+        self.record(&mut failures, "label-cooccurrence", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "draft-pr-usage",
+                metrics::DraftPrUsage::new(
+                    // whether PRs are still drafts is as of now, not the end of the time span
+                    self.graphql("draft-pr-usage").without_cache(),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce draft PR usage");
+        self.record(&mut failures, "draft-pr-usage", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "self-merges",
+                metrics::SelfMerges::new(
+                    self.graphql("self-merges"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce self merges");
+        self.record(&mut failures, "self-merges", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "triage-latency",
+                metrics::TriageLatency::new(
+                    self.graphql("triage-latency"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce triage latency");
+        self.record(&mut failures, "triage-latency", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "merge-throughput",
+                metrics::MergeThroughput::new(
+                    self.graphql("merge-throughput"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce merge throughput");
+        self.record(&mut failures, "merge-throughput", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "queue-depth",
+                metrics::QueueDepth::new(
+                    self.graphql("queue-depth"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce queue depth");
+        self.record(&mut failures, "queue-depth", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "approval-counts",
+                metrics::ApprovalCounts::new(
+                    self.graphql("approval-counts"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce approval counts");
+        self.record(&mut failures, "approval-counts", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "label-churn",
+                metrics::LabelChurn::new(
+                    self.graphql("label-churn"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce label churn");
+        self.record(&mut failures, "label-churn", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "ramp-time",
+                metrics::RampTime::new(
+                    self.graphql("ramp-time"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce ramp time");
+        self.record(&mut failures, "ramp-time", result)?;
+        let result = self
+            .produce_output(
+                &config,
+                "reopens",
+                metrics::Reopens::new(
+                    self.graphql("reopens"),
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                    config.github.bots.clone(),
+                ),
+            )
+            .await
+            .wrap_err("Failed to produce reopens");
+        self.record(&mut failures, "reopens", result)?;
+        let mut review_sla = metrics::ReviewSla::new(
+            // unreviewed PRs miss the SLA as time passes
+            self.graphql("review-sla").without_cache(),
+            config.github.org.clone(),
+            config.github.repos.clone(),
+            config.data_source.start_date.clone(),
+            config.data_source.end_date.clone(),
+            config.review_sla.sla_hours,
+            config.github.bots.clone(),
+        );
+        if config.review_sla.business_hours {
+            review_sla = review_sla.with_business_hours(
+                config
+                    .business_hours
+                    .calendar(config.data_source.timezone)?,
+            );
+        }
+        let result = self
+            .produce_output(&config, "review-sla", review_sla)
+            .await
+            .wrap_err("Failed to produce review SLA");
+        self.record(&mut failures, "review-sla", result)?;
+
+        if let Some(workbook) = &self.workbook {
+            if !workbook.is_empty() {
+                let result = workbook
+                    .save(&self.output_dir().join("report.xlsx"))
+                    .wrap_err("Failed to write Excel workbook");
+                self.record(&mut failures, "xlsx", result)?;
+            }
+        }
+
+        let warnings = self.warnings.clone();
+        let warnings_path = self.output_dir().join("warnings.csv");
+        let warnings_csv = config.csv.clone();
 
         // Finally, we call all of our 'write' functions which produce
         // output data in `$DATA_DIR/output/` folder.
         // Each function will handle its own logic for consuming and manipulating data
-        tokio::task::spawn_blocking(move || -> eyre::Result<()> {
-            self.write_top_crates(&config, &data)
-                .wrap_err("Failed to write Top Crates")?;
-            self.write_high_contributors(&config, &data)
-                .wrap_err("Failed to write High Contributors")?;
-            self.write_issue_closures(&config, &data)
-                .wrap_err("Failed to write issue closures")?;
-            Ok(())
-        })
-        .await
-        .wrap_err("Failed to spawn blocking task while writing metrics output")?
-        .wrap_err("Failed to generate output data for metrics")?;
+        let failures = match data {
+            Some(data) => tokio::task::spawn_blocking(move || -> eyre::Result<Vec<String>> {
+                if config.metrics.enabled("top-crates") {
+                    let result = self
+                        .write_top_crates(&config, &data)
+                        .wrap_err("Failed to write Top Crates");
+                    self.record(&mut failures, "top-crates", result)?;
+                }
+                if config.metrics.enabled("high-contributors") {
+                    let result = self
+                        .write_high_contributors(&config, &data)
+                        .wrap_err("Failed to write High Contributors");
+                    self.record(&mut failures, "high-contributors", result)?;
+                }
+                if config.metrics.enabled("issue-closures") {
+                    let result = self
+                        .write_issue_closures(&config, &data)
+                        .wrap_err("Failed to write issue closures");
+                    self.record(&mut failures, "issue-closures", result)?;
+                }
+                if config.metrics.enabled("contributor-overlap") {
+                    let result = self
+                        .write_contributor_overlap(&config, &data)
+                        .wrap_err("Failed to write contributor overlap");
+                    self.record(&mut failures, "contributor-overlap", result)?;
+                }
+                if config.metrics.enabled("reviewer-concentration") {
+                    let result = self
+                        .write_reviewer_concentration(&config, &data)
+                        .wrap_err("Failed to write reviewer concentration");
+                    self.record(&mut failures, "reviewer-concentration", result)?;
+                }
+                if config.metrics.enabled("contributor-balance") {
+                    let result = self
+                        .write_contributor_balance(&config, &data)
+                        .wrap_err("Failed to write contributor balance");
+                    self.record(&mut failures, "contributor-balance", result)?;
+                }
+                if config.metrics.enabled("bus-factor") {
+                    let result = self
+                        .write_bus_factor(&config, &data)
+                        .wrap_err("Failed to write bus factor");
+                    self.record(&mut failures, "bus-factor", result)?;
+                }
+                if config.metrics.enabled("health-score") {
+                    let result = self
+                        .write_health_score(&config, &data)
+                        .wrap_err("Failed to write health score");
+                    self.record(&mut failures, "health-score", result)?;
+                }
+                Ok(failures)
+            })
+            .await
+            .wrap_err("Failed to spawn blocking task while writing metrics output")??,
+            None => {
+                warnings.for_metric("report").warn(
+                    None,
+                    "skipping the outputs derived from the input data, \
+                     as it could not all be gathered",
+                );
+                failures
+            }
+        };
+
+        warnings::write_warnings(&warnings_path, &warnings_csv, &warnings.all())
+            .wrap_err("Failed to write warnings")?;
+
+        if !failures.is_empty() {
+            Err(MetricsFailed { metrics: failures })?;
+        }
     }
 
     /// Load and parse the configuration file from `$DATA_DIR/report.toml`
+    /// (unless one was given with `with_config`), and apply overrides.
     #[throws]
     async fn load_config(&mut self) -> ReportConfig {
-        let report_config_file = self.data_dir.join("report.toml");
-        let report_config_bytes = tokio::fs::read_to_string(report_config_file.clone())
-            .await
-            .wrap_err_with(|| {
-                format!(
-                    "Failed to read Report Config from path {:?}",
-                    report_config_file
-                )
-            })?;
-        let mut config: ReportConfig =
-            toml::from_str(&report_config_bytes).wrap_err("Failed to parse Report Config")?;
+        let mut config = match self.config.take() {
+            Some(config) => config,
+            None => self.read_config().await?,
+        };
+        high_contributor::validate_columns(&config.high_contributor.columns)
+            .wrap_err("Invalid high_contributor.columns")?;
+        config
+            .data_source
+            .validate()
+            .wrap_err("Invalid data_source")?;
+        config.csv.validate().wrap_err("Invalid csv options")?;
+        config
+            .health_score
+            .validate()
+            .wrap_err("Invalid health_score")?;
+        config
+            .business_hours
+            .calendar(config.data_source.timezone)
+            .wrap_err("Invalid business_hours")?;
+        if !(config.review_sla.sla_hours.is_finite() && config.review_sla.sla_hours > 0.0) {
+            eyre::bail!("review_sla.sla_hours must be a number above 0");
+        }
+        config.metrics.warn_unknown();
+        match config.github.page_size {
+            Some(0) => eyre::bail!("github.page_size must be at least 1"),
+            Some(page_size) if page_size > metrics::MAX_PAGE_SIZE => tracing::warn!(
+                "github.page_size is {}, but GitHub returns at most {} results per page; using {}",
+                page_size,
+                metrics::MAX_PAGE_SIZE,
+                metrics::MAX_PAGE_SIZE
+            ),
+            _ => {}
+        }
+        if config.channel_capacity == 0 {
+            eyre::bail!("channel_capacity must be at least 1");
+        }
+
+        // command-line overrides take precedence over `report.toml`
+        if let Some(org) = &self.org {
+            config.github.org = org.clone();
+            config.github.repos.clear();
+        }
+        if !self.repos.is_empty() {
+            config.github.repos = self.repos.clone();
+            self.warn_unknown_repos(&config.github)
+                .await
+                .wrap_err("Failed to check repos")?;
+        }
 
         // if user specified an empty list of repos in `report.toml`, then assume
         // they wish to analyze all repositories within the specified `organization`
@@ -175,21 +1787,173 @@ impl Report {
             config.github.repos = metrics::all_repos(graphql, &config.github.org)
                 .await
                 .wrap_err("Failed to gather all repos")?;
+            config.github.all_repos = true;
+        }
+
+        let requested_repos = config.github.repos.len();
+        let exclude_repos = &config.github.exclude_repos;
+        config
+            .github
+            .repos
+            .retain(|repo| !exclude_repos.contains(repo));
+
+        // Every output is still written, but with no rows, so say why.
+        if config.github.repos.is_empty() {
+            let selected = if config.github.all_repos {
+                "all of its repositories".to_string()
+            } else {
+                format!("{} named repositories", requested_repos)
+            };
+            let excluded = if exclude_repos.is_empty() {
+                "none excluded".to_string()
+            } else {
+                format!("excluding {}", exclude_repos.join(", "))
+            };
+            self.warnings.for_metric("config").warn(
+                None,
+                format!(
+                    "no repositories matched: org {}, filters: {}, {}",
+                    config.github.org, selected, excluded
+                ),
+            );
+        }
+
+        if let Some(limit) = self.limit {
+            if limit == 0 {
+                eyre::bail!("--limit must be at least 1");
+            }
+            if config.github.repos.len() > limit {
+                self.warnings.for_metric("config").warn(
+                    None,
+                    format!(
+                        "--limit {} applied: analyzing only {} of {} repositories; this is not a full report",
+                        limit,
+                        limit,
+                        config.github.repos.len()
+                    ),
+                );
+                config.github.repos.truncate(limit);
+            }
         }
 
         config
     }
 
+    /// Load the queries in `$DATA_DIR/gql/`, if any, to be sent instead of the compiled-in ones.
+    #[throws]
+    fn load_query_overrides(&mut self) {
+        let query_overrides = metrics::load_query_overrides(&self.query_overrides_dir())
+            .wrap_err("Failed to load query overrides")?;
+        self.query_overrides = Arc::new(query_overrides);
+    }
+
+    /// Read and parse `$DATA_DIR/report.toml`, or the file given with `with_config_path`.
+    ///
+    /// A path of `-` reads the configuration from stdin instead, and the configuration
+    /// can also be given directly in the `REPORT_TOML` environment variable, for when
+    /// mounting a file is awkward (e.g. in a container). Only one of these may be used.
+    #[throws]
+    async fn read_config(&self) -> ReportConfig {
+        self.read_config_from(std::env::var_os(REPORT_TOML_VAR), tokio::io::stdin())
+            .await?
+    }
+
+    /// `read_config`, given the value of `REPORT_TOML` (if set) and what to read as stdin.
+    #[throws]
+    async fn read_config_from(
+        &self,
+        from_env: Option<std::ffi::OsString>,
+        mut stdin: impl tokio::io::AsyncRead + Unpin,
+    ) -> ReportConfig {
+        let report_config_bytes = match (&self.config_path, from_env) {
+            (Some(path), Some(_)) => eyre::bail!(
+                "the configuration was given both in {} and with --config {:?}; use only one",
+                REPORT_TOML_VAR,
+                path
+            ),
+            (None, Some(toml)) => toml.into_string().map_err(|_| {
+                eyre::eyre!("the {} environment variable is not UTF-8", REPORT_TOML_VAR)
+            })?,
+            (Some(path), None) if path.as_os_str() == "-" => {
+                let mut toml = String::new();
+                tokio::io::AsyncReadExt::read_to_string(&mut stdin, &mut toml)
+                    .await
+                    .wrap_err("Failed to read Report Config from stdin")?;
+                toml
+            }
+            (path, None) => {
+                let report_config_file = match path {
+                    Some(path) => path.clone(),
+                    None => self.data_dir.join("report.toml"),
+                };
+                tokio::fs::read_to_string(report_config_file.clone())
+                    .await
+                    .wrap_err_with(|| {
+                        format!(
+                            "Failed to read Report Config from path {:?}",
+                            report_config_file
+                        )
+                    })?
+            }
+        };
+        ReportConfig::from_toml(&report_config_bytes)?
+    }
+
+    /// Warns about any of `github.repos` that don't exist in `github.org`.
+    ///
+    /// Repositories named on the command line are taken as-is, so a typo would
+    /// otherwise only show up as missing rows in the output.
+    #[throws]
+    async fn warn_unknown_repos(&self, github: &GithubConfig) {
+        let graphql = &mut self.graphql("all-repos");
+        let all_repos = metrics::all_repos(graphql, &github.org).await?;
+        for repo in &github.repos {
+            if !all_repos.contains(repo) {
+                self.warnings
+                    .for_metric("config")
+                    .warn(Some(repo), format!("not found in {}", github.org));
+            }
+        }
+    }
+
     /// get a `Graphql` struct given the associated directory where
     /// GQL response data will be stored
     fn graphql(&self, dir_name: &str) -> Graphql {
         let graphql_dir = self.graphql_dir().join(dir_name);
-        Graphql::new(graphql_dir, self.replay_graphql)
+        let graphql = Graphql::new(
+            graphql_dir,
+            self.replay_graphql,
+            DEFAULT_MAX_ATTEMPTS,
+            self.compress_replay,
+        )
+        .with_timeout(self.timeout)
+        .with_query_overrides(self.query_overrides.clone())
+        .with_page_size(self.page_size)
+        .with_warnings(self.warnings.for_metric(dir_name));
+        match self.cache_fresh_after {
+            Some(fresh_after) => graphql.with_cache(self.cache_dir(), fresh_after),
+            None => graphql,
+        }
     }
 
     /// get the path to the `$DATA_DIR/graphql/` directory
+    /// (or the scratch directory used instead when validating)
     fn graphql_dir(&self) -> PathBuf {
-        self.data_dir.join("graphql")
+        if self.scratch_graphql {
+            std::env::temp_dir().join("optopodi").join("validate")
+        } else {
+            self.data_dir.join("graphql")
+        }
+    }
+
+    /// get the path to the `$DATA_DIR/gql/` directory, of queries overriding the compiled-in ones
+    fn query_overrides_dir(&self) -> PathBuf {
+        self.data_dir.join("gql")
+    }
+
+    /// get the path to the `$DATA_DIR/cache/` directory
+    fn cache_dir(&self) -> PathBuf {
+        self.data_dir.join("cache")
     }
 
     /// get the path to the `$DATA_DIR/inputs/` directory
@@ -202,16 +1966,736 @@ impl Report {
         self.data_dir.join("output")
     }
 
-    /// Produce a properly formatted CSV file with input data and column names
-    /// given (1) the path to the file and (2) the producer of the data
+    /// Produce `$DATA_DIR/inputs/{name}` with the data from the given producer,
+    /// in the report's format, returning the path of the file
     #[throws]
-    async fn produce_input(&self, path: &Path, producer: impl metrics::Producer + Send + 'static) {
-        let (column_names, mut rx) = metrics::run_producer(producer);
-        let f = File::create(&path)
-            .wrap_err_with(|| format!("Failed to create file from path {:?}", path))?;
-        metrics::Print::new(f)
-            .consume(&mut rx, column_names)
+    async fn produce_input(
+        &self,
+        config: &ReportConfig,
+        name: &str,
+        producer: impl metrics::Producer + Send + 'static,
+    ) -> PathBuf {
+        let path = self
+            .input_dir()
+            .join(format!("{}.{}", name, self.format.extension()));
+        // inputs are read back in, so they are written without the configured CSV
+        // options or column labels, with the columns named as the parsers expect
+        self.produce(config, &path, None, producer)
             .await
             .wrap_err("Failed to produce report")?;
+        path
+    }
+
+    /// Produce `$DATA_DIR/output/{name}` directly from the given producer, in the
+    /// report's format (and, for CSV, formatted according to the configured CSV options).
+    /// Does nothing if the metric `name` is disabled in the config.
+    #[throws]
+    async fn produce_output(
+        &self,
+        config: &ReportConfig,
+        name: &str,
+        producer: impl metrics::Producer + Send + 'static,
+    ) {
+        if !config.metrics.enabled(name) {
+            tracing::debug!("skipping {}, as it is disabled", name);
+            return;
+        }
+        let path = self
+            .output_dir()
+            .join(format!("{}.{}", name, self.format.extension()));
+        self.produce(config, &path, Some(name), producer)
+            .await
+            .wrap_err_with(|| format!("Failed to produce output data for {:?}", path))?;
+    }
+
+    /// Writes the data from `producer` to `path` in the report's format.
+    ///
+    /// The output of a metric (`output` names it) is formatted according to the CSV
+    /// options, has its columns renamed by `column_labels`, is appended to if the report
+    /// appends, and is added to the report's workbook, if any, as a worksheet. An input
+    /// (`output` is `None`) is written as it is, with the default CSV options if it is CSV.
+    #[throws]
+    async fn produce(
+        &self,
+        config: &ReportConfig,
+        path: &Path,
+        output: Option<&str>,
+        producer: impl metrics::Producer + Send + 'static,
+    ) {
+        let plain_csv = CsvOptions::default();
+        let no_labels = HashMap::new();
+        let (csv_options, column_labels, append) = match output {
+            Some(_) => (&config.csv, &config.column_labels, self.append),
+            None => (&plain_csv, &no_labels, false),
+        };
+        let xlsx = output
+            .zip(self.workbook.clone())
+            .map(|(name, workbook)| metrics::Xlsx::new(workbook, name));
+        let (column_names, mut rx, producer) = metrics::run_producer_with_capacity(
+            producer,
+            config.channel_capacity,
+            self.cancel.clone(),
+        );
+        // decided before the columns are relabeled, which may hide what they hold
+        let identifier_columns = column_names
+            .iter()
+            .map(|name| metrics::is_identifier_column(name))
+            .collect();
+        let column_names = column_names
+            .into_iter()
+            .map(|name| column_labels.get(&name).cloned().unwrap_or(name))
+            .collect();
+        let f = if append {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .wrap_err_with(|| format!("Failed to open file from path {:?}", path))?
+        } else {
+            File::create(path)
+                .wrap_err_with(|| format!("Failed to create file from path {:?}", path))?
+        };
+        match self.format {
+            OutputFormat::Csv => {
+                let mut print = metrics::Print::with_options(f, csv_options)
+                    .with_identifier_columns(identifier_columns);
+                if append {
+                    // there's a header already unless the file was only just created
+                    let new_file = std::fs::metadata(path)
+                        .wrap_err_with(|| format!("Failed to read metadata of {:?}", path))?
+                        .len()
+                        == 0;
+                    print = print.appending(Utc::now().date_naive().to_string(), new_file);
+                }
+                consume(print, xlsx, &mut rx, column_names).await?
+            }
+            OutputFormat::Json => {
+                consume(metrics::Json::new(f), xlsx, &mut rx, column_names).await?
+            }
+            OutputFormat::NdJson => {
+                consume(metrics::NdJson::new(f), xlsx, &mut rx, column_names).await?
+            }
+        }
+        self.check_cancelled()?;
+        // The rows stop coming when the producer fails too, so the output is only
+        // complete if it succeeded.
+        producer.await.wrap_err("Producer task panicked")??;
+    }
+
+    /// Records the outcome of producing one metric, so that a failure doesn't stop the
+    /// rest of the report: on error, logs it, adds `metric` to `failures` and returns `None`.
+    ///
+    /// Cancellation is not a per-metric failure, so this fails if the report has been cancelled.
+    #[throws]
+    fn record<T>(
+        &self,
+        failures: &mut Vec<String>,
+        metric: &str,
+        result: eyre::Result<T>,
+    ) -> Option<T> {
+        self.check_cancelled()?;
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                tracing::error!("{}: {:?}", metric, error);
+                self.warnings
+                    .for_metric(metric)
+                    .add(None, format!("failed: {:#}", error));
+                failures.push(metric.to_string());
+                None
+            }
+        }
+    }
+
+    /// Fails if the report has been cancelled, so that nothing further is produced.
+    #[throws]
+    fn check_cancelled(&self) {
+        if self.cancel.is_cancelled() {
+            eyre::bail!("Interrupted; output written so far has been kept");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh data directory holding `report.toml`.
+    fn data_dir(name: &str, report_toml: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("optopodi-report-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.toml"), report_toml).unwrap();
+        dir
+    }
+
+    /// A `report.toml` for `repos` of rust-lang (all of them if empty), with every metric
+    /// but `enabled` turned off, and the sections in `extra`.
+    fn report_toml(repos: &[&str], enabled: &[&str], extra: &str) -> String {
+        let metrics: String = METRICS
+            .iter()
+            .map(|metric| {
+                format!(
+                    "{} = {}\n",
+                    metric.replace('-', "_"),
+                    enabled.contains(metric)
+                )
+            })
+            .collect();
+        format!(
+            r#"
+[github]
+org = "rust-lang"
+repos = {:?}
+bots = ["bors"]
+
+[data_source]
+start_date = 2024-01-01
+end_date = 2024-01-31
+
+[high_contributor]
+high_reviewer_min_percentage = 10
+high_reviewer_min_prs = 2
+high_participant_min_percentage = 15
+high_participant_min_prs = 2
+high_author_min_percentage = 10
+high_author_min_prs = 2
+high_contributor_categories_threshold = 2
+reviewer_saturation_threshold = 50
+author_saturation_threshold = 50
+
+{}
+
+[metrics]
+{}"#,
+            repos, extra, metrics
+        )
+    }
+
+    /// Saves `responses` for the queries made under `dir_name` (see `Report::graphql`)
+    /// to replay, in order.
+    fn replay(data_dir: &Path, dir_name: &str, responses: &[serde_json::Value]) {
+        let dir = data_dir.join("graphql").join(dir_name);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, response) in responses.iter().enumerate() {
+            std::fs::write(dir.join(format!("{}.json", i)), response.to_string()).unwrap();
+        }
+    }
+
+    /// Saves the response to the query every run starts with, of who is running it.
+    fn replay_context(data_dir: &Path) {
+        replay(
+            data_dir,
+            "context",
+            &[serde_json::json!({ "data": {
+                "viewer": { "login": "me" },
+                "organization": { "login": "rust-lang", "name": "The Rust Programming Language" },
+            } })],
+        );
+    }
+
+    /// A page of search results holding `nodes`, and no more.
+    fn search(nodes: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "data": { "search": {
+            "pageInfo": { "hasNextPage": false, "endCursor": null },
+            "nodes": nodes,
+        } } })
+    }
+
+    fn pull_request(number: u64, author: &str) -> serde_json::Value {
+        serde_json::json!({
+            "__typename": "PullRequest",
+            "number": number,
+            "title": format!("PR {}", number),
+            "author": { "__typename": "User", "login": author },
+            "state": "MERGED",
+            "createdAt": "2024-01-02T00:00:00Z",
+            "mergedAt": "2024-01-03T00:00:00Z",
+        })
+    }
+
+    #[tokio::test]
+    async fn inputs_are_written_in_the_chosen_format() {
+        for format in [OutputFormat::Json, OutputFormat::NdJson] {
+            let dir = data_dir(
+                &format!("format-{}", format.extension()),
+                &report_toml(&["rust"], &[], "[pull_request_list]\nenabled = true"),
+            );
+            replay_context(&dir);
+            replay(
+                &dir,
+                "pull-requests",
+                &[search(vec![
+                    pull_request(1, "alice"),
+                    pull_request(2, "bob"),
+                ])],
+            );
+            Report::new(dir.clone(), true, false)
+                .with_format(format)
+                .run()
+                .await
+                .unwrap();
+
+            let path = dir
+                .join("inputs")
+                .join(format!("pull-requests.{}", format.extension()));
+            let text = std::fs::read_to_string(&path).unwrap();
+            let rows: Vec<serde_json::Map<String, serde_json::Value>> = match format {
+                OutputFormat::Json => serde_json::from_str(&text).unwrap(),
+                _ => text
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect(),
+            };
+            assert_eq!(rows.len(), 2);
+            for row in &rows {
+                assert_eq!(
+                    row.keys().collect::<Vec<_>>(),
+                    [
+                        "Repository",
+                        "Number",
+                        "Title",
+                        "Author",
+                        "State",
+                        "Created",
+                        "Merged"
+                    ]
+                );
+            }
+            assert_eq!(rows[1]["Author"], "bob");
+            assert!(!dir.join("inputs").join("pull-requests.csv").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn start_and_end_dates_are_checked() {
+        let toml = report_toml(&["rust"], &[], "");
+
+        let dir = data_dir(
+            "dates-reversed",
+            &toml.replace("end_date = 2024-01-31", "end_date = 2023-12-31"),
+        );
+        let error = Report::new(dir, true, false)
+            .load_config()
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", error)
+                .contains("start_date (2024-01-01) is after end_date (2023-12-31)"),
+            "{:#}",
+            error
+        );
+
+        let dir = data_dir("dates-plain", &toml);
+        let config = Report::new(dir, true, false).load_config().await.unwrap();
+        assert_eq!(
+            config.data_source.end().unwrap(),
+            "2024-02-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+
+        let dir = data_dir(
+            "dates-datetime",
+            &toml.replace("end_date = 2024-01-31", "end_date = 2024-01-31T12:30:00Z"),
+        );
+        let config = Report::new(dir, true, false).load_config().await.unwrap();
+        assert_eq!(
+            config.data_source.end().unwrap(),
+            "2024-01-31T12:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    /// A page listing `repos` as all of rust-lang's repositories.
+    fn org_repos(repos: &[&str]) -> serde_json::Value {
+        let edges: Vec<_> = repos
+            .iter()
+            .map(|name| serde_json::json!({ "node": { "name": name } }))
+            .collect();
+        serde_json::json!({ "data": { "repositoryOwner": {
+            "__typename": "Organization",
+            "repositories": {
+                "edges": edges,
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+            },
+        } } })
+    }
+
+    #[tokio::test]
+    async fn excluded_repos_are_removed_from_all_of_the_orgs_repos() {
+        let toml = report_toml(&[], &[], "").replace(
+            "bots = [\"bors\"]",
+            "bots = [\"bors\"]\nexclude_repos = [\"cargo\", \"archived\"]",
+        );
+        let dir = data_dir("exclude-repos", &toml);
+        replay(
+            &dir,
+            "all-repos",
+            &[org_repos(&["rust", "cargo", "rustup", "archived"])],
+        );
+        let config = Report::new(dir, true, false).load_config().await.unwrap();
+        assert_eq!(config.github.repos, ["rust", "rustup"]);
+        assert!(config.github.all_repos);
+    }
+
+    #[tokio::test]
+    async fn another_org_is_analyzed_in_full() {
+        let dir = data_dir("with-org", &report_toml(&["rust", "cargo"], &[], ""));
+        replay(&dir, "all-repos", &[org_repos(&["tokio", "mio"])]);
+        let config = Report::new(dir, true, false)
+            .with_org(Some("tokio-rs".to_string()))
+            .load_config()
+            .await
+            .unwrap();
+        assert_eq!(config.github.org, "tokio-rs");
+        assert_eq!(config.github.repos, ["tokio", "mio"]);
+        assert!(config.github.all_repos);
+    }
+
+    #[tokio::test]
+    async fn named_repos_replace_the_configured_ones() {
+        let dir = data_dir("with-repos", &report_toml(&["rust", "cargo"], &[], ""));
+        replay(
+            &dir,
+            "all-repos",
+            &[org_repos(&["rust", "cargo", "rustup"])],
+        );
+        let mut report = Report::new(dir, true, false)
+            .with_repos(vec!["rustup".to_string(), "rustc-perf".to_string()]);
+        let config = report.load_config().await.unwrap();
+        assert_eq!(config.github.repos, ["rustup", "rustc-perf"]);
+        assert!(!config.github.all_repos);
+
+        let warnings = report.warnings.all();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].repo.as_deref(), Some("rustc-perf"));
+        assert_eq!(warnings[0].reason, "not found in rust-lang");
+    }
+
+    #[tokio::test]
+    async fn the_config_can_be_read_from_elsewhere() {
+        let dir = data_dir("config-path", &report_toml(&["rust"], &[], ""));
+        let elsewhere = dir.join("staging.toml");
+        std::fs::write(&elsewhere, report_toml(&["cargo"], &[], "")).unwrap();
+
+        let config = Report::new(dir.clone(), true, false)
+            .with_config_path(Some(elsewhere))
+            .load_config()
+            .await
+            .unwrap();
+        assert_eq!(config.github.repos, ["cargo"]);
+
+        let error = Report::new(dir.clone(), true, false)
+            .with_config_path(Some(dir.join("missing.toml")))
+            .load_config()
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("missing.toml"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn the_config_can_be_given_on_stdin_or_in_the_environment() {
+        let dir = data_dir("config-stdin", &report_toml(&["rust"], &[], ""));
+        let stdin_toml = report_toml(&["cargo"], &[], "");
+        let env_toml = report_toml(&["rustup"], &[], "");
+
+        let config = Report::new(dir.clone(), true, false)
+            .with_config_path(Some(PathBuf::from("-")))
+            .read_config_from(None, stdin_toml.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(config.github.repos, ["cargo"]);
+
+        let config = Report::new(dir.clone(), true, false)
+            .read_config_from(Some(env_toml.clone().into()), &b""[..])
+            .await
+            .unwrap();
+        assert_eq!(config.github.repos, ["rustup"]);
+
+        // without either, report.toml is read
+        let config = Report::new(dir.clone(), true, false)
+            .read_config_from(None, &b""[..])
+            .await
+            .unwrap();
+        assert_eq!(config.github.repos, ["rust"]);
+
+        let error = Report::new(dir, true, false)
+            .with_config_path(Some(PathBuf::from("-")))
+            .read_config_from(Some(env_toml.into()), stdin_toml.as_bytes())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("use only one"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn a_limit_keeps_only_the_first_repos() {
+        let dir = data_dir("limit", &report_toml(&["rust", "cargo", "rustup"], &[], ""));
+        let mut report = Report::new(dir, true, false).with_limit(Some(2));
+        let config = report.load_config().await.unwrap();
+        assert_eq!(config.github.repos, ["rust", "cargo"]);
+
+        let warnings = report.warnings.all();
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0]
+                .reason
+                .contains("analyzing only 2 of 3 repositories"),
+            "{}",
+            warnings[0].reason
+        );
+
+        // a limit that isn't reached changes nothing
+        let dir = data_dir("limit-unreached", &report_toml(&["rust"], &[], ""));
+        let mut report = Report::new(dir, true, false).with_limit(Some(2));
+        let config = report.load_config().await.unwrap();
+        assert_eq!(config.github.repos, ["rust"]);
+        assert!(report.warnings.all().is_empty());
+    }
+
+    /// The records of the CSV file at `path`, header first.
+    fn csv_records(path: &Path) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(path)
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn no_matching_repos_gives_empty_outputs_and_a_warning() {
+        let toml = report_toml(
+            &["cargo"],
+            &["commit-counts", "high-contributors", "issue-closures"],
+            "",
+        )
+        .replace(
+            "bots = [\"bors\"]",
+            "bots = [\"bors\"]\nexclude_repos = [\"cargo\"]",
+        );
+        let dir = data_dir("no-repos", &toml);
+        replay_context(&dir);
+        Report::new(dir.clone(), true, false).run().await.unwrap();
+
+        let output = dir.join("output");
+        for name in ["commit-counts", "high-contributors", "issue-closures"] {
+            let records = csv_records(&output.join(format!("{}.csv", name)));
+            assert_eq!(records.len(), 1, "{} should be only a header", name);
+        }
+        let warnings = csv_records(&output.join("warnings.csv"));
+        assert_eq!(
+            warnings[1..],
+            [vec![
+                "config".to_string(),
+                String::new(),
+                "no repositories matched: org rust-lang, filters: 1 named repositories, \
+                 excluding cargo"
+                    .to_string(),
+            ]]
+        );
+    }
+
+    /// A page of a commit history, of a commit by each of `logins`.
+    fn commits(logins: &[&str]) -> serde_json::Value {
+        let nodes: Vec<_> = logins
+            .iter()
+            .map(|login| serde_json::json!({ "author": { "name": login, "user": { "login": login } } }))
+            .collect();
+        serde_json::json!({ "data": { "repository": { "object": {
+            "__typename": "Commit",
+            "history": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            },
+        } } } })
+    }
+
+    #[tokio::test]
+    async fn disabled_metrics_write_no_output() {
+        let dir = data_dir(
+            "disabled-metrics",
+            &report_toml(&["rust"], &["commit-counts"], ""),
+        );
+        replay_context(&dir);
+        replay(
+            &dir,
+            "commit-counts",
+            &[commits(&["alice", "alice", "bob"])],
+        );
+        Report::new(dir.clone(), true, false).run().await.unwrap();
+
+        let output = dir.join("output");
+        assert_eq!(
+            csv_records(&output.join("commit-counts.csv"))[1..],
+            [
+                ["1", "rust", "alice", "2"].map(String::from).to_vec(),
+                ["2", "rust", "bob", "1"].map(String::from).to_vec(),
+            ]
+        );
+        let mut outputs: Vec<_> = std::fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, ["commit-counts.csv", "warnings.csv"]);
+        // nor makes any queries
+        assert!(!dir.join("graphql").join("pr-sizes").exists());
+    }
+
+    #[tokio::test]
+    async fn a_failing_metric_does_not_stop_the_others() {
+        let dir = data_dir(
+            "failing-metric",
+            &report_toml(
+                &["rust"],
+                &["commit-counts"],
+                "[pull_request_list]\nenabled = true",
+            ),
+        );
+        replay_context(&dir);
+        // there are no responses to replay for the pull requests, which are gathered
+        // first, so they fail
+        replay(&dir, "commit-counts", &[commits(&["alice"])]);
+        assert!(Report::new(dir.clone(), true, false).run().await.is_err());
+
+        let output = dir.join("output");
+        assert_eq!(csv_records(&output.join("commit-counts.csv")).len(), 2);
+        let warnings = csv_records(&output.join("warnings.csv"));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1][0], "pull-requests");
+        assert!(warnings[1][2].starts_with("failed: "), "{:?}", warnings[1]);
+    }
+
+    #[tokio::test]
+    async fn failed_metrics_are_named_in_the_error() {
+        let dir = data_dir(
+            "metrics-failed",
+            &report_toml(&["rust"], &["commit-counts", "pr-sizes"], ""),
+        );
+        replay_context(&dir);
+        // neither has responses to replay
+        let error = Report::new(dir, true, false).run().await.unwrap_err();
+        let failed = error
+            .downcast_ref::<MetricsFailed>()
+            .expect("a MetricsFailed error");
+        assert_eq!(failed.metrics, ["commit-counts", "pr-sizes"]);
+        assert_eq!(
+            error.to_string(),
+            "Failed to produce 2 metric(s): commit-counts, pr-sizes"
+        );
+    }
+
+    #[tokio::test]
+    async fn column_labels_rename_only_the_header() {
+        let dir = data_dir(
+            "column-labels",
+            &report_toml(
+                &["rust"],
+                &["commit-counts"],
+                "[column_labels]\nAuthor = \"Contributor\"\nCommits = \"Commits in January\"",
+            ),
+        );
+        replay_context(&dir);
+        replay(
+            &dir,
+            "commit-counts",
+            &[commits(&["alice", "bob", "alice"])],
+        );
+        Report::new(dir.clone(), true, false).run().await.unwrap();
+
+        let records = csv_records(&dir.join("output").join("commit-counts.csv"));
+        assert_eq!(
+            records,
+            [
+                ["#", "Repository", "Contributor", "Commits in January"],
+                ["1", "rust", "alice", "2"],
+                ["2", "rust", "bob", "1"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_configs_fail_validation_before_any_query() {
+        let toml = report_toml(&["rust"], &[], "")
+            .replace("bots = [\"bors\"]", "bots = [\"bors\"]\npage_size = 0");
+        let dir = data_dir("validate-invalid", &toml);
+        // nothing to replay, so any query would fail differently
+        let error = Report::new(dir.clone(), true, false)
+            .validate()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to load config: github.page_size must be at least 1"
+        );
+        assert!(!dir.join("graphql").exists());
+        assert!(!dir.join("output").exists());
+    }
+
+    #[tokio::test]
+    async fn page_sizes_above_the_maximum_are_accepted() {
+        let toml = report_toml(&["rust"], &[], "")
+            .replace("bots = [\"bors\"]", "bots = [\"bors\"]\npage_size = 250");
+        let dir = data_dir("page-size", &toml);
+        let config = Report::new(dir, true, false).load_config().await.unwrap();
+        assert_eq!(config.github.page_size, Some(250));
+    }
+
+    #[tokio::test]
+    async fn top_crates_come_from_the_configured_mirror() {
+        use std::io::{BufRead, Write};
+
+        // a mirror serving `crate-information.json`, which reports what was requested
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/mirror/crate-information.json",
+            listener.local_addr().unwrap()
+        );
+        let (requested_tx, requested) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            std::io::BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            requested_tx.send(request_line).unwrap();
+            let body = r#"[{"version":"1.0.2"},{"version":"0.4.1"},{"version":"2.1.0"},{"version":"1.5.0"}]"#;
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let dir = data_dir(
+            "top-crates-mirror",
+            &report_toml(
+                &["rust"],
+                &["top-crates"],
+                &format!("[top_crates]\nsource = {:?}", url),
+            ),
+        );
+        replay_context(&dir);
+        Report::new(dir.clone(), true, false).run().await.unwrap();
+
+        assert!(requested
+            .recv()
+            .unwrap()
+            .starts_with("GET /mirror/crate-information.json "));
+        assert!(dir.join("crate-information.json").exists());
+        assert_eq!(
+            csv_records(&dir.join("output").join("top_crate.csv")),
+            [
+                ["Measurement", "Value"],
+                ["Total 'significant' crates", "4"],
+                ["crates at 1.0 or higher", "75"],
+            ]
+        );
     }
 }
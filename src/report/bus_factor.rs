@@ -0,0 +1,112 @@
+use std::fs::File;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use super::high_contributor::{
+    joined_with_percentages, saturating_participants, SaturationComparison, Share,
+};
+use super::repo_participant::RepoParticipant;
+use super::{Report, ReportConfig, ReportData};
+
+/// The share of a repository's activity that the bus factor's contributors account for.
+const BUS_FACTOR_PERCENTAGE: f64 = 50.0;
+
+impl Report {
+    /// Writes, for each repository, its bus factor: the fewest contributors who together
+    /// account for more than half of its activity, counting the PRs each authored plus
+    /// those they reviewed (or resolved).
+    #[throws]
+    pub(super) fn write_bus_factor(&self, config: &ReportConfig, data: &ReportData) {
+        let output = self.output_dir().join("bus-factor.csv");
+        let output = File::create(output).wrap_err("Failed to create file 'bus-factor.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing bus factor")?;
+        csv.write_record(["Repository", "Bus factor", "Top contributors"])
+            .wrap_err("Failed to write headers while writing bus factor")?;
+
+        for repo in &config.github.repos {
+            let top = bus_factor(data, repo);
+            csv.write_record(&[
+                repo.clone(),
+                config.csv.format_number(top.len().to_string()),
+                joined_with_percentages(&top),
+            ])
+            .wrap_err_with(|| format!("Failed to write bus factor for {}", repo))?;
+        }
+    }
+}
+
+/// The contributors who make up `repo`'s bus factor, with their shares of its activity.
+pub(super) fn bus_factor(data: &ReportData, repo: &str) -> Vec<Share> {
+    let activity = |p: &RepoParticipant| p.authored + p.reviewed_or_resolved();
+    let participants = data
+        .repo_participants
+        .participants
+        .iter()
+        .filter(|p| p.repo == repo);
+    let total = participants.clone().map(activity).sum();
+    saturating_participants(
+        participants.map(|p| (activity(p), &p.participant)),
+        total,
+        BUS_FACTOR_PERCENTAGE,
+        SaturationComparison::Exceed,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::repo_participant::RepoParticipants;
+    use super::*;
+
+    fn data(participants: &[(&str, &str, u64, u64)]) -> ReportData {
+        ReportData {
+            repo_participants: RepoParticipants {
+                participants: participants
+                    .iter()
+                    .map(|&(login, repo, authored, reviewed)| RepoParticipant {
+                        participant: login.to_string(),
+                        repo: repo.to_string(),
+                        participated_in: authored + reviewed,
+                        authored,
+                        reviewed,
+                        resolved: 0,
+                    })
+                    .collect(),
+            },
+            repo_infos: Default::default(),
+            top_crates: vec![],
+        }
+    }
+
+    #[test]
+    fn a_single_maintainer_is_a_bus_factor_of_one() {
+        let data = data(&[
+            ("alice", "rust", 10, 12),
+            ("bob", "rust", 2, 0),
+            ("carol", "rust", 1, 1),
+            // another repository's activity doesn't count
+            ("dave", "cargo", 50, 50),
+        ]);
+        let top = bus_factor(&data, "rust");
+        assert_eq!(joined_with_percentages(&top), "alice (85%)");
+    }
+
+    #[test]
+    fn well_distributed_activity_needs_more_than_half_of_it() {
+        let data = data(&[
+            ("alice", "rust", 2, 3),
+            ("bob", "rust", 3, 2),
+            ("carol", "rust", 1, 4),
+            ("dave", "rust", 4, 1),
+        ]);
+        // two of them have exactly half, which isn't more than half
+        let top = bus_factor(&data, "rust");
+        assert_eq!(
+            joined_with_percentages(&top),
+            "alice (25%), bob (25%), carol (25%)"
+        );
+    }
+}
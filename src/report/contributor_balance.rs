@@ -0,0 +1,122 @@
+use std::fs::File;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use super::{repo_participant::RepoParticipant, Report, ReportConfig, ReportData};
+
+/// The ratio written for participants who authored PRs but reviewed none.
+const ONLY_AUTHORED: &str = "inf";
+
+impl Report {
+    /// Writes, for each participant in each repository, how many PRs they authored
+    /// and reviewed, with the most lopsided first.
+    ///
+    /// Lopsidedness is the larger count over the smaller, each plus one, so that someone
+    /// who only authors (or only reviews) many PRs ranks above a few-to-one split.
+    #[throws]
+    pub(super) fn write_contributor_balance(&self, config: &ReportConfig, data: &ReportData) {
+        let output = self.output_dir().join("contributor-balance.csv");
+        let output =
+            File::create(output).wrap_err("Failed to create file 'contributor-balance.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing contributor balance")?;
+        csv.write_record([
+            "Participant",
+            "Repository",
+            "Authored",
+            "Reviewed",
+            "Author/Review ratio",
+        ])
+        .wrap_err("Failed to write headers while writing contributor balance")?;
+
+        for (participant, repo, authored, reviewed) in
+            balances(&data.repo_participants.participants)
+        {
+            csv.write_record(&[
+                participant.to_string(),
+                repo.to_string(),
+                config.csv.format_number(authored.to_string()),
+                config.csv.format_number(reviewed.to_string()),
+                config.csv.format_number(ratio(authored, reviewed)),
+            ])
+            .wrap_err_with(|| format!("Failed to write contributor balance for {}", participant))?;
+        }
+    }
+}
+
+/// Each participant's authored and reviewed counts in each repository they were active
+/// in, with the most lopsided first (see `write_contributor_balance`).
+fn balances(participants: &[RepoParticipant]) -> Vec<(&str, &str, u64, u64)> {
+    let mut rows: Vec<(&str, &str, u64, u64)> = participants
+        .iter()
+        .map(|p| {
+            let reviewed = p.reviewed_or_resolved();
+            (
+                p.participant.as_str(),
+                p.repo.as_str(),
+                p.authored,
+                reviewed,
+            )
+        })
+        .filter(|&(_, _, authored, reviewed)| authored > 0 || reviewed > 0)
+        .collect();
+    let lopsidedness = |authored: u64, reviewed: u64| {
+        (authored.max(reviewed) + 1) as f64 / (authored.min(reviewed) + 1) as f64
+    };
+    rows.sort_by(|a, b| {
+        lopsidedness(b.2, b.3)
+            .total_cmp(&lopsidedness(a.2, a.3))
+            .then_with(|| (a.0, a.1).cmp(&(b.0, b.1)))
+    });
+    rows
+}
+
+/// `authored / reviewed` to two decimal places, or `ONLY_AUTHORED` if nothing was reviewed.
+fn ratio(authored: u64, reviewed: u64) -> String {
+    if reviewed == 0 {
+        ONLY_AUTHORED.to_string()
+    } else {
+        format!("{:.2}", authored as f64 / reviewed as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participant(login: &str, authored: u64, reviewed: u64) -> RepoParticipant {
+        RepoParticipant {
+            participant: login.to_string(),
+            repo: "rust".to_string(),
+            participated_in: authored + reviewed,
+            authored,
+            reviewed,
+            resolved: 0,
+        }
+    }
+
+    #[test]
+    fn pure_authors_and_reviewers_are_the_most_lopsided() {
+        let participants = [
+            participant("alice", 3, 2),
+            participant("bob", 0, 6),
+            participant("carol", 0, 0),
+            participant("dave", 4, 0),
+        ];
+        let rows: Vec<_> = balances(&participants)
+            .into_iter()
+            .map(|(login, _, authored, reviewed)| (login, ratio(authored, reviewed)))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("bob", "0.00".to_string()),
+                ("dave", "inf".to_string()),
+                ("alice", "1.50".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use super::{repo_participant::RepoParticipant, Report, ReportConfig, ReportData};
+use crate::util::ranking;
+
+impl Report {
+    /// Writes, for each participant, the repositories they took part in,
+    /// with those active in the most repositories first.
+    #[throws]
+    pub(super) fn write_contributor_overlap(&self, config: &ReportConfig, data: &ReportData) {
+        let output = self.output_dir().join("contributor-overlap.csv");
+        let output =
+            File::create(output).wrap_err("Failed to create file 'contributor-overlap.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing contributor overlap")?;
+        csv.write_record(["Participant", "Repo count", "Repos"])
+            .wrap_err("Failed to write headers while writing contributor overlap")?;
+
+        for (participant, repos) in overlap(&data.repo_participants.participants) {
+            csv.write_record(&[
+                participant.to_string(),
+                config.csv.format_number(repos.len().to_string()),
+                repos.into_iter().collect::<Vec<_>>().join(", "),
+            ])
+            .wrap_err_with(|| format!("Failed to write contributor overlap for {}", participant))?;
+        }
+    }
+}
+
+/// The repositories each participant took part in, with those active in the most
+/// repositories first.
+fn overlap(participants: &[RepoParticipant]) -> Vec<(&str, BTreeSet<&str>)> {
+    let mut repos_by_participant: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for p in participants {
+        repos_by_participant
+            .entry(&p.participant)
+            .or_default()
+            .insert(&p.repo);
+    }
+
+    let mut rows: Vec<_> = repos_by_participant.into_iter().collect();
+    rows.sort_by_key(|(participant, repos)| ranking(repos.len() as u64, *participant));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participant(login: &str, repo: &str) -> RepoParticipant {
+        RepoParticipant {
+            participant: login.to_string(),
+            repo: repo.to_string(),
+            participated_in: 1,
+            authored: 1,
+            reviewed: 0,
+            resolved: 0,
+        }
+    }
+
+    #[test]
+    fn shared_contributors_come_first() {
+        let participants = [
+            participant("carol", "rust"),
+            participant("alice", "rust"),
+            participant("bob", "cargo"),
+            participant("alice", "cargo"),
+            participant("bob", "rust"),
+            participant("alice", "miri"),
+        ];
+        let overlap: Vec<(&str, Vec<&str>)> = overlap(&participants)
+            .into_iter()
+            .map(|(participant, repos)| (participant, repos.into_iter().collect()))
+            .collect();
+        assert_eq!(
+            overlap,
+            [
+                ("alice", vec!["cargo", "miri", "rust"]),
+                ("bob", vec!["cargo", "rust"]),
+                ("carol", vec!["rust"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use crate::util::percentage_f64;
+
+use super::bus_factor::bus_factor;
+use super::repo_info::RepoInfo;
+use super::{Report, ReportConfig, ReportData};
+
+impl Report {
+    /// Writes, for each repository, a health score from 0 to 100 blending four
+    /// subscores, each also from 0 to 100:
+    ///
+    /// - review coverage: the PRs reviewed (summed over reviewers) as a percentage of
+    ///   the PRs opened, capped at 100
+    /// - issue closure: the issues closed as a percentage of those opened, capped at 100
+    /// - bus factor: the bus factor as a percentage of `bus_factor_target`, capped at 100
+    /// - stale PRs: 100 minus the stale PRs as a percentage of the PRs opened
+    ///
+    /// The score is the average of the subscores weighted by the `[health_score]`
+    /// weights, so the weights needn't add up to 100. A subscore that can't be worked
+    /// out, e.g. issue closure for a repository where no issues were opened, is left
+    /// blank and left out of the average.
+    #[throws]
+    pub(super) fn write_health_score(&self, config: &ReportConfig, data: &ReportData) {
+        let output = self.output_dir().join("health-score.csv");
+        let output = File::create(output).wrap_err("Failed to create file 'health-score.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing health score")?;
+        csv.write_record([
+            "Repository",
+            "Score",
+            "Review coverage",
+            "Issue closure",
+            "Bus factor",
+            "Stale PRs",
+        ])
+        .wrap_err("Failed to write headers while writing health score")?;
+
+        let hs = &config.health_score;
+        for repo in &config.github.repos {
+            let repo_info = data.repo_infos.get(repo);
+            let subscores = [
+                (
+                    hs.review_coverage_weight,
+                    repo_info.and_then(|info| review_coverage(data, info)),
+                ),
+                (hs.issue_closure_weight, repo_info.and_then(issue_closure)),
+                (
+                    hs.bus_factor_weight,
+                    bus_factor_subscore(data, repo, hs.bus_factor_target),
+                ),
+                (hs.stale_prs_weight, repo_info.and_then(stale_prs)),
+            ];
+
+            let format = |subscore: Option<f64>| match subscore {
+                Some(subscore) => config.csv.format_number(format!("{:.0}", subscore)),
+                None => String::new(),
+            };
+            let mut record = vec![repo.clone(), format(weighted_score(&subscores))];
+            record.extend(subscores.iter().map(|&(_, subscore)| format(subscore)));
+            csv.write_record(&record)
+                .wrap_err_with(|| format!("Failed to write health score for {}", repo))?;
+        }
+    }
+}
+
+/// The average of the `(weight, subscore)` pairs with a subscore, weighted by their
+/// weights; `None` if there are none (or their weights are all zero).
+fn weighted_score(subscores: &[(f64, Option<f64>)]) -> Option<f64> {
+    let (weighted, weights) = subscores
+        .iter()
+        .filter_map(|&(weight, subscore)| Some((weight * subscore?, weight)))
+        .fold((0.0, 0.0), |(weighted, weights), (w_s, w)| {
+            (weighted + w_s, weights + w)
+        });
+    if weights > 0.0 {
+        Some(weighted / weights)
+    } else {
+        None
+    }
+}
+
+fn review_coverage(data: &ReportData, repo_info: &RepoInfo) -> Option<f64> {
+    if repo_info.num_prs == 0 {
+        return None;
+    }
+    let reviewed = data
+        .repo_participants
+        .in_repo(repo_info)
+        .map(|p| p.reviewed)
+        .sum();
+    Some(percentage_f64(reviewed, repo_info.num_prs).min(100.0))
+}
+
+fn issue_closure(repo_info: &RepoInfo) -> Option<f64> {
+    if repo_info.num_opened == 0 {
+        return None;
+    }
+    Some(percentage_f64(repo_info.num_closed, repo_info.num_opened).min(100.0))
+}
+
+fn bus_factor_subscore(data: &ReportData, repo: &str, target: u64) -> Option<f64> {
+    let bus_factor = bus_factor(data, repo).len() as u64;
+    if bus_factor == 0 {
+        // nobody was active, so there's nothing to go by
+        return None;
+    }
+    Some(percentage_f64(bus_factor, target).min(100.0))
+}
+
+fn stale_prs(repo_info: &RepoInfo) -> Option<f64> {
+    if repo_info.num_prs == 0 {
+        return None;
+    }
+    Some(100.0 - percentage_f64(repo_info.num_stale_prs, repo_info.num_prs).min(100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::repo_info::RepoInfos;
+    use super::super::repo_participant::{RepoParticipant, RepoParticipants};
+    use super::*;
+
+    fn participant(login: &str, authored: u64, reviewed: u64) -> RepoParticipant {
+        RepoParticipant {
+            participant: login.to_string(),
+            repo: "rust".to_string(),
+            participated_in: authored + reviewed,
+            authored,
+            reviewed,
+            resolved: 0,
+        }
+    }
+
+    fn data() -> ReportData {
+        let repo_info = RepoInfo {
+            org: "rust-lang".to_string(),
+            repo: "rust".to_string(),
+            num_prs: 10,
+            num_opened: 8,
+            num_closed: 6,
+            num_stale_prs: 2,
+            start: "2024-01-01".to_string(),
+            end: "2024-01-31".to_string(),
+        };
+        ReportData {
+            repo_participants: RepoParticipants {
+                participants: vec![participant("alice", 6, 5), participant("bob", 4, 3)],
+            },
+            repo_infos: RepoInfos {
+                repos: [("rust".to_string(), repo_info)].into(),
+            },
+            top_crates: vec![],
+        }
+    }
+
+    fn rounded(score: Option<f64>) -> Option<f64> {
+        score.map(|score| (score * 100.0).round() / 100.0)
+    }
+
+    #[test]
+    fn subscores_of_a_repo() {
+        let data = data();
+        let repo_info = data.repo_infos.get("rust").unwrap();
+        assert_eq!(review_coverage(&data, repo_info), Some(80.0));
+        assert_eq!(issue_closure(repo_info), Some(75.0));
+        // alice alone has 11 of the 18 PRs of activity
+        assert_eq!(rounded(bus_factor_subscore(&data, "rust", 3)), Some(33.33));
+        assert_eq!(stale_prs(repo_info), Some(80.0));
+    }
+
+    #[test]
+    fn weights_are_relative_to_each_other() {
+        let subscores = |weights: [f64; 4]| {
+            [
+                (weights[0], Some(80.0)),
+                (weights[1], Some(75.0)),
+                (weights[2], Some(40.0)),
+                (weights[3], Some(80.0)),
+            ]
+        };
+        assert_eq!(
+            rounded(weighted_score(&subscores([25.0, 25.0, 25.0, 25.0]))),
+            Some(68.75)
+        );
+        assert_eq!(
+            rounded(weighted_score(&subscores([1.0, 1.0, 1.0, 1.0]))),
+            Some(68.75)
+        );
+        assert_eq!(
+            weighted_score(&subscores([50.0, 0.0, 25.0, 25.0])),
+            weighted_score(&subscores([2.0, 0.0, 1.0, 1.0]))
+        );
+        assert_eq!(
+            rounded(weighted_score(&subscores([2.0, 0.0, 1.0, 1.0]))),
+            Some(70.0)
+        );
+    }
+
+    #[test]
+    fn missing_subscores_are_left_out_of_the_average() {
+        assert_eq!(
+            weighted_score(&[(25.0, Some(80.0)), (25.0, None), (50.0, Some(20.0))]),
+            Some(40.0)
+        );
+        assert_eq!(weighted_score(&[(25.0, None), (0.0, Some(50.0))]), None);
+    }
+}
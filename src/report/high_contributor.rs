@@ -1,11 +1,70 @@
 use super::{
     repo_info::RepoInfo, repo_participant::RepoParticipant, Report, ReportConfig, ReportData,
 };
+use crate::metrics::CsvOptions;
+use crate::util::ranking;
 use fehler::throws;
-use serde::Serialize;
-use stable_eyre::eyre::{Error, WrapErr};
+use serde::{Deserialize, Serialize};
+use stable_eyre::eyre::{self, Error, WrapErr};
 use std::fs::File;
 
+/// The columns of `high-contributors.csv`, in their default order.
+const COLUMNS: &[&str] = &[
+    "repo",
+    "number_of_prs",
+    "total_participants",
+    "total_authors",
+    "total_reviewers",
+    "top_author",
+    "top_author_percentage",
+    "top_reviewer",
+    "top_reviewer_percentage",
+    "top_participant",
+    "top_participant_percentage",
+    "saturation_authors",
+    "saturation_author_names",
+    "saturation_reviewers",
+    "saturation_reviewer_names",
+    "high_contributors",
+    "high_contributor_names",
+];
+
+/// When a group of participants counts as having "reached" a saturation threshold.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaturationComparison {
+    /// Their share of the PRs is at least the threshold.
+    AtLeast,
+    /// Their share of the PRs is strictly more than the threshold.
+    #[default]
+    Exceed,
+}
+
+impl SaturationComparison {
+    fn reached(self, percentage: f64, threshold: f64) -> bool {
+        match self {
+            SaturationComparison::AtLeast => percentage >= threshold,
+            SaturationComparison::Exceed => percentage > threshold,
+        }
+    }
+}
+
+/// A participant, with the share of the repository's PRs that puts them in a list.
+#[derive(Debug, Serialize)]
+pub(super) struct Share {
+    login: String,
+    percentage: u64,
+}
+
+/// Formats `shares` for a single CSV field, e.g. `alice (40%), bob (20%)`.
+pub(super) fn joined_with_percentages(shares: &[Share]) -> String {
+    shares
+        .iter()
+        .map(|s| format!("{} ({}%)", s.login, s.percentage))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Debug, Serialize)]
 struct HighContributorRow {
     repo: String,
@@ -20,23 +79,36 @@ struct HighContributorRow {
     top_participant: String,
     top_participant_percentage: u64,
     saturation_authors: u64,
-    saturation_author_names: String,
+    saturation_author_names: Vec<Share>,
     saturation_reviewers: u64,
-    saturation_reviewer_names: String,
+    saturation_reviewer_names: Vec<Share>,
     high_contributors: u64,
-    high_contributor_names: String,
+    /// Each with the share of the PRs they participated in.
+    high_contributor_names: Vec<Share>,
 }
 
 impl Report {
     #[throws]
     pub(super) fn write_high_contributors(&self, config: &ReportConfig, data: &ReportData) {
-        let high_contributor_rows = self.high_contributor_rows(&config, &data);
+        let high_contributor_rows = self.high_contributor_rows(config, data);
         let output = self.output_dir().join("high-contributors.csv");
         write_high_contributor_rows(
             &mut File::create(output.clone())
                 .wrap_err_with(|| format!("Failed to create output file {:?}", output))?,
+            &config.csv,
+            &selected_columns(&config.high_contributor.columns),
             &high_contributor_rows,
         )?;
+
+        // The lists of names are embedded in single fields in the CSV; the JSON
+        // has them as arrays, for programs to read.
+        let output = self.output_dir().join("high-contributors.json");
+        let mut file = File::create(output.clone())
+            .wrap_err_with(|| format!("Failed to create output file {:?}", output))?;
+        serde_json::to_writer_pretty(&mut file, &high_contributor_rows)
+            .wrap_err("Failed to write high contributors as JSON")?;
+        std::io::Write::write_all(&mut file, b"\n")
+            .wrap_err("Failed to write high contributors as JSON")?;
     }
 
     fn high_contributor_rows(
@@ -48,7 +120,15 @@ impl Report {
             .github
             .repos
             .iter()
-            .map(|repo| self.high_contributor_row(config, data, repo))
+            .filter_map(|repo| match data.repo_infos.get(repo) {
+                Some(repo_info) => Some(self.high_contributor_row(config, data, repo_info)),
+                None => {
+                    self.warnings
+                        .for_metric("high-contributors")
+                        .warn(Some(repo), "skipped, as it has no row in inputs/repo-infos");
+                    None
+                }
+            })
             .collect()
     }
 
@@ -56,10 +136,8 @@ impl Report {
         &self,
         config: &ReportConfig,
         data: &ReportData,
-        repo: &str,
+        repo_info: &RepoInfo,
     ) -> HighContributorRow {
-        let repo_info = &data.repo_infos.get(repo);
-
         let (top_author, top_author_percentage) = data
             .repo_participants
             .top_participant(repo_info, |p| p.authored);
@@ -71,6 +149,7 @@ impl Report {
         let (saturation_reviewer_names, saturation_reviewers) = self.saturation(
             data,
             config.high_contributor.reviewer_saturation_threshold,
+            config.high_contributor.saturation_comparison,
             repo_info,
             |p| p.reviewed_or_resolved(),
         );
@@ -78,6 +157,7 @@ impl Report {
         let (saturation_author_names, saturation_authors) = self.saturation(
             data,
             config.high_contributor.author_saturation_threshold,
+            config.high_contributor.saturation_comparison,
             repo_info,
             |p| p.authored,
         );
@@ -109,7 +189,7 @@ impl Report {
             .collect();
 
         HighContributorRow {
-            repo: repo.to_string(),
+            repo: repo_info.repo.clone(),
             number_of_prs: repo_info.num_prs,
             total_authors,
             total_participants,
@@ -127,54 +207,145 @@ impl Report {
             high_contributors: high_contributors.len() as u64,
             high_contributor_names: high_contributors
                 .iter()
-                .map(|p| p.participant.to_string())
-                .collect::<Vec<_>>()
-                .join(","),
+                .map(|p| Share {
+                    login: p.participant.to_string(),
+                    percentage: crate::util::percentage(p.participated_in, repo_info.num_prs),
+                })
+                .collect(),
         }
     }
 
-    /// Computes the number of participants needed to reach saturation_threshold_percentage% of total PRs.
+    /// Computes the number of participants needed to reach saturation_threshold_percentage% of total PRs,
+    /// counting each participant's `key` (see `saturating_participants`).
     ///
-    /// Returns a string with their names and the number of participants.
+    /// Returns those participants, each with their share of the PRs, and how many there are.
     fn saturation(
         &self,
         data: &ReportData,
-        saturation_threshold_percentage: u64,
+        saturation_threshold_percentage: f64,
+        comparison: SaturationComparison,
         repo_info: &RepoInfo,
         key: impl Fn(&RepoParticipant) -> u64,
-    ) -> (String, u64) {
-        let mut participants: Vec<(u64, &String)> = data
+    ) -> (Vec<Share>, u64) {
+        let counts = data
             .repo_participants
             .in_repo(repo_info)
-            .map(|p| (key(p), &p.participant))
-            .collect();
-        participants.sort();
-        participants.reverse();
-
-        let mut running_total = 0;
-        let target = repo_info.num_prs * saturation_threshold_percentage / 100;
-        let mut output = vec![];
-        for (count, participant) in participants {
-            running_total += count;
-            let percent = crate::util::percentage(count, repo_info.num_prs);
-            output.push(format!("{} ({}%)", participant, percent));
-            if running_total > target {
-                break;
-            }
+            .map(|p| (key(p), &p.participant));
+        let output = saturating_participants(
+            counts,
+            repo_info.num_prs,
+            saturation_threshold_percentage,
+            comparison,
+        );
+        let len = output.len() as u64;
+        (output, len)
+    }
+}
+
+/// Taking participants in decreasing order of their count, the smallest number of them
+/// whose combined count reaches (per `comparison`) `threshold_percentage` of `total`,
+/// each with their count as a share of `total`.
+///
+/// Participants with a count of zero are never included; if the threshold can't be
+/// reached, everyone else is.
+pub(super) fn saturating_participants<'a>(
+    counts: impl Iterator<Item = (u64, &'a String)>,
+    total: u64,
+    threshold_percentage: f64,
+    comparison: SaturationComparison,
+) -> Vec<Share> {
+    let mut participants: Vec<(u64, &String)> = counts.filter(|&(count, _)| count > 0).collect();
+    participants.sort_by_key(|&(count, participant)| ranking(count, participant));
+
+    let mut running_total = 0;
+    let mut output = vec![];
+    for (count, participant) in participants {
+        // Check before adding anyone, so that nobody is needed for a threshold of 0%
+        // and nobody is added once the threshold has been reached.
+        let reached = comparison.reached(
+            crate::util::percentage_f64(running_total, total),
+            threshold_percentage,
+        );
+        if reached {
+            break;
+        }
+        running_total += count;
+        output.push(Share {
+            login: participant.clone(),
+            percentage: crate::util::percentage(count, total),
+        });
+    }
+    output
+}
+
+impl HighContributorRow {
+    /// The value of the column named `column`, which must be one of `COLUMNS`.
+    fn value(&self, column: &str) -> String {
+        match column {
+            "repo" => self.repo.clone(),
+            "number_of_prs" => self.number_of_prs.to_string(),
+            "total_participants" => self.total_participants.to_string(),
+            "total_authors" => self.total_authors.to_string(),
+            "total_reviewers" => self.total_reviewers.to_string(),
+            "top_author" => self.top_author.clone(),
+            "top_author_percentage" => self.top_author_percentage.to_string(),
+            "top_reviewer" => self.top_reviewer.clone(),
+            "top_reviewer_percentage" => self.top_reviewer_percentage.to_string(),
+            "top_participant" => self.top_participant.clone(),
+            "top_participant_percentage" => self.top_participant_percentage.to_string(),
+            "saturation_authors" => self.saturation_authors.to_string(),
+            "saturation_author_names" => joined_with_percentages(&self.saturation_author_names),
+            "saturation_reviewers" => self.saturation_reviewers.to_string(),
+            "saturation_reviewer_names" => joined_with_percentages(&self.saturation_reviewer_names),
+            "high_contributors" => self.high_contributors.to_string(),
+            "high_contributor_names" => joined_with_percentages(&self.high_contributor_names),
+            _ => unreachable!("unknown high contributor column {:?}", column),
+        }
+    }
+}
+
+/// Checks that every configured column is a known high-contributor column.
+#[throws]
+pub(super) fn validate_columns(columns: &[String]) {
+    for column in columns {
+        if !COLUMNS.contains(&column.as_str()) {
+            eyre::bail!(
+                "unknown high contributor column {:?}; expected one of: {}",
+                column,
+                COLUMNS.join(", ")
+            );
         }
+    }
+}
 
-        (output.join(", "), output.len() as u64)
+/// The columns to write: the configured ones, or all of them if none were configured.
+fn selected_columns(columns: &[String]) -> Vec<&str> {
+    if columns.is_empty() {
+        COLUMNS.to_vec()
+    } else {
+        columns.iter().map(|c| c.as_str()).collect()
     }
 }
 
 #[throws]
 fn write_high_contributor_rows(
     out: &mut impl std::io::Write,
+    csv_options: &CsvOptions,
+    columns: &[&str],
     high_contributor_rows: &[HighContributorRow],
 ) {
-    let mut csv = csv::Writer::from_writer(out);
+    let mut csv = csv_options
+        .writer(out)
+        .wrap_err("Failed to start writing high contributors")?;
+    csv.write_record(columns)
+        .wrap_err("Failed to write headers while writing high contributors")?;
     for row in high_contributor_rows {
-        csv.serialize(row).wrap_err_with(|| {
+        csv.write_record(
+            columns
+                .iter()
+                .map(|c| csv_options.format_number(row.value(c))),
+        )
+        .wrap_err_with(|| {
             format!(
                 "Failed to serialize row while writing high contributors: {:?}",
                 row
@@ -182,3 +353,218 @@ fn write_high_contributor_rows(
         })?;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::repo_info::RepoInfos;
+    use super::*;
+
+    fn shares(shares: &[(&str, u64)]) -> Vec<Share> {
+        shares
+            .iter()
+            .map(|&(login, percentage)| Share {
+                login: login.to_string(),
+                percentage,
+            })
+            .collect()
+    }
+
+    fn row() -> HighContributorRow {
+        HighContributorRow {
+            repo: "rust".to_string(),
+            number_of_prs: 10,
+            total_participants: 3,
+            total_authors: 2,
+            total_reviewers: 2,
+            top_author: "alice".to_string(),
+            top_author_percentage: 60,
+            top_reviewer: "bob".to_string(),
+            top_reviewer_percentage: 50,
+            top_participant: "alice".to_string(),
+            top_participant_percentage: 70,
+            saturation_authors: 1,
+            saturation_author_names: shares(&[("alice", 60)]),
+            saturation_reviewers: 2,
+            saturation_reviewer_names: shares(&[("bob", 50), ("carol", 20)]),
+            high_contributors: 2,
+            high_contributor_names: shares(&[("alice", 70), ("bob", 50)]),
+        }
+    }
+
+    fn logins(shares: &[Share]) -> Vec<&str> {
+        shares.iter().map(|share| share.login.as_str()).collect()
+    }
+
+    #[test]
+    fn tied_participants_are_saturated_in_name_order() {
+        let names: Vec<String> = ["dave", "bob", "carol", "alice"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let counts = [3, 3, 2, 2];
+        for order in [[0, 1, 2, 3], [3, 2, 1, 0], [2, 0, 3, 1]] {
+            let participants = saturating_participants(
+                order.iter().map(|&i| (counts[i], &names[i])),
+                10,
+                50.0,
+                SaturationComparison::AtLeast,
+            );
+            assert_eq!(logins(&participants), vec!["bob", "dave"]);
+            let participants = saturating_participants(
+                order.iter().map(|&i| (counts[i], &names[i])),
+                10,
+                70.0,
+                SaturationComparison::AtLeast,
+            );
+            assert_eq!(logins(&participants), vec!["bob", "dave", "alice"]);
+        }
+    }
+
+    #[test]
+    fn json_lists_match_the_csv_fields() {
+        let rows = vec![row()];
+        let mut csv = vec![];
+        write_high_contributor_rows(&mut csv, &CsvOptions::default(), COLUMNS, &rows).unwrap();
+        let mut csv = csv::Reader::from_reader(&csv[..]);
+        let headers = csv.headers().unwrap().clone();
+        let record = csv.records().next().unwrap().unwrap();
+
+        let json: Vec<serde_json::Value> =
+            serde_json::from_str(&serde_json::to_string(&rows).unwrap()).unwrap();
+        for column in [
+            "saturation_author_names",
+            "saturation_reviewer_names",
+            "high_contributor_names",
+        ] {
+            let shares = json[0][column].as_array().unwrap();
+            let joined: Vec<String> = shares
+                .iter()
+                .map(|share| {
+                    format!(
+                        "{} ({}%)",
+                        share["login"].as_str().unwrap(),
+                        share["percentage"]
+                    )
+                })
+                .collect();
+            let field = headers.iter().position(|h| h == column).unwrap();
+            assert_eq!(record[field], joined.join(", "), "{}", column);
+        }
+        assert_eq!(
+            &record[headers
+                .iter()
+                .position(|h| h == "high_contributor_names")
+                .unwrap()],
+            "alice (70%), bob (50%)"
+        );
+    }
+
+    fn repo_info(repo: &str, num_prs: u64) -> RepoInfo {
+        RepoInfo {
+            org: "rust-lang".to_string(),
+            repo: repo.to_string(),
+            num_prs,
+            num_opened: 0,
+            num_closed: 0,
+            num_stale_prs: 0,
+            start: "2024-01-01".to_string(),
+            end: "2024-01-31".to_string(),
+        }
+    }
+
+    #[test]
+    fn repos_without_info_are_skipped_with_a_warning() {
+        let config = ReportConfig::builder(
+            "rust-lang",
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+        )
+        .repos(vec!["rust".to_string(), "cargo".to_string()])
+        .build();
+        let data = ReportData {
+            repo_participants: Default::default(),
+            repo_infos: RepoInfos {
+                repos: [("rust".to_string(), repo_info("rust", 10))].into(),
+            },
+            top_crates: vec![],
+        };
+        let report = Report::new(std::env::temp_dir(), false, false);
+
+        let rows = report.high_contributor_rows(&config, &data);
+        assert_eq!(
+            rows.iter().map(|row| row.repo.as_str()).collect::<Vec<_>>(),
+            ["rust"]
+        );
+        let warnings = report.warnings.all();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].metric, "high-contributors");
+        assert_eq!(warnings[0].repo.as_deref(), Some("cargo"));
+    }
+
+    #[test]
+    fn unknown_columns_fail_validation() {
+        let columns = vec!["repo".to_string(), "top_author".to_string()];
+        validate_columns(&columns).unwrap();
+        let error = validate_columns(&["repo".to_string(), "top_autor".to_string()]).unwrap_err();
+        assert!(
+            error.to_string().starts_with(
+                "unknown high contributor column \"top_autor\"; expected one of: repo, "
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn only_the_selected_columns_are_written_in_their_order() {
+        assert_eq!(selected_columns(&[]), COLUMNS);
+
+        let columns = vec!["top_author".to_string(), "repo".to_string()];
+        let mut csv = vec![];
+        write_high_contributor_rows(
+            &mut csv,
+            &CsvOptions::default(),
+            &selected_columns(&columns),
+            &[row()],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "top_author,repo\nalice,rust\n"
+        );
+    }
+
+    #[test]
+    fn the_comparison_decides_whether_the_exact_threshold_is_enough() {
+        let names: Vec<String> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let counts = [5, 3, 2];
+        let saturating = |threshold, comparison| {
+            let participants = saturating_participants(
+                counts.iter().copied().zip(&names),
+                10,
+                threshold,
+                comparison,
+            );
+            logins(&participants).join(" ")
+        };
+
+        // alice alone has exactly 50%
+        assert_eq!(saturating(50.0, SaturationComparison::AtLeast), "alice");
+        assert_eq!(saturating(50.0, SaturationComparison::Exceed), "alice bob");
+        // alice and bob have exactly 80%
+        assert_eq!(saturating(80.0, SaturationComparison::AtLeast), "alice bob");
+        assert_eq!(
+            saturating(80.0, SaturationComparison::Exceed),
+            "alice bob carol"
+        );
+        // nobody is needed for 0%, and 100% can't be exceeded
+        assert_eq!(saturating(0.0, SaturationComparison::AtLeast), "");
+        assert_eq!(
+            saturating(100.0, SaturationComparison::Exceed),
+            "alice bob carol"
+        );
+    }
+}
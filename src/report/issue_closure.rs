@@ -1,5 +1,4 @@
 use std::fs::File;
-use std::io::Write;
 
 use fehler::throws;
 use stable_eyre::eyre::{Error, WrapErr};
@@ -8,24 +7,37 @@ use super::{Report, ReportConfig, ReportData};
 
 impl Report {
     #[throws]
-    pub(super) fn write_issue_closures(&self, _config: &ReportConfig, data: &ReportData) {
+    pub(super) fn write_issue_closures(&self, config: &ReportConfig, data: &ReportData) {
         let output = self.output_dir().join("issue-closures.csv");
-        let output =
-            &mut File::create(output).wrap_err("Failed to create file 'issue-closures.csv'")?;
-        writeln!(output, "Organization,Repo,Opened,Closed,Delta,Time Period").unwrap();
+        let output = File::create(output).wrap_err("Failed to create file 'issue-closures.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing issue closures")?;
+        csv.write_record([
+            "Organization",
+            "Repo",
+            "Opened",
+            "Closed",
+            "Delta",
+            "Time Period",
+        ])
+        .wrap_err("Failed to write headers while writing issue closures")?;
         // TODO: collapse issue closures with the same org/repo into one row
-        for (_, d) in &data.repo_infos.repos {
-            writeln!(
-                output,
-                "{},{},{},{},{},{}",
-                d.org,
-                d.repo,
-                d.num_opened,
-                d.num_closed,
-                (d.num_opened as i64 - d.num_closed as i64),
-                format!("{}<>{}", d.start, d.end)
-            )
-            .unwrap();
+        let mut repo_infos: Vec<_> = data.repo_infos.repos.values().collect();
+        repo_infos.sort_by(|a, b| a.repo.cmp(&b.repo));
+        for d in repo_infos {
+            csv.write_record(&[
+                d.org.clone(),
+                d.repo.clone(),
+                config.csv.format_number(d.num_opened.to_string()),
+                config.csv.format_number(d.num_closed.to_string()),
+                config
+                    .csv
+                    .format_number((d.num_opened as i64 - d.num_closed as i64).to_string()),
+                format!("{}<>{}", d.start, d.end),
+            ])
+            .wrap_err_with(|| format!("Failed to write issue closures for {}", d.repo))?;
         }
     }
 }
@@ -4,20 +4,17 @@ use fehler::throws;
 use serde::Deserialize;
 use stable_eyre::eyre::{Error, WrapErr};
 
-use crate::{metrics, util::percentage};
+use crate::{metrics, util::percentage_f64};
 
-use super::{repo_participant::RepoParticipant, Report, ReportConfig};
+use super::{read_records, repo_participant::RepoParticipant, OutputFormat, Report, ReportConfig};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct RepoInfos {
     pub repos: HashMap<String, RepoInfo>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct RepoInfo {
-    /// row number
-    #[serde(rename = "#")]
-    pub row: usize,
     /// name of the GitHub Organization to
     /// which this Repo belongs
     #[serde(rename = "Organization")]
@@ -34,6 +31,10 @@ pub struct RepoInfo {
     /// number of issues closed in the relevant time span
     #[serde(rename = "Issues Closed")]
     pub num_closed: u64,
+    /// number of PRs opened in the relevant time span that are still open
+    /// and haven't been updated since it ended
+    #[serde(rename = "Stale PRs", default)]
+    pub num_stale_prs: u64,
     /// the starting date of the relevant time span
     #[serde(rename = "Start Date")]
     pub start: String,
@@ -47,24 +48,30 @@ impl Report {
     /// will be used as input data in several metrics.
     #[throws]
     pub(super) async fn repo_infos(&self, config: &ReportConfig) -> RepoInfos {
-        let repo_infos = self.input_dir().join("repo-infos.csv");
-
-        let graphql = self.graphql("repo-infos");
-
-        self.produce_input(
-            &repo_infos,
-            metrics::ListReposForOrg::new(
-                graphql,
-                config.github.org.clone(),
-                config.github.repos.clone(),
-                config.data_source.start_date.clone(),
-                config.data_source.end_date.clone(),
-            ),
-        )
-        .await
-        .wrap_err("Failed to produce input data for repo-infos.csv")?;
+        // stale PRs are those still open now, so their counts change whether or not the
+        // time span has ended
+        let graphql = self.graphql("repo-infos").without_cache();
+
+        let mut list_repos = metrics::ListReposForOrg::new(
+            graphql,
+            config.github.org.clone(),
+            config.github.repos.clone(),
+            config.data_source.start_date.clone(),
+            config.data_source.end_date.clone(),
+        );
+        // when analyzing every repository, searching the whole organization
+        // takes far fewer queries than searching each repository
+        if config.github.all_repos {
+            list_repos = list_repos.org_wide();
+        }
+
+        let repo_infos = self
+            .produce_input(config, "repo-infos", list_repos)
+            .await
+            .wrap_err("Failed to produce input data for repo-infos")?;
 
-        tokio::task::spawn_blocking(move || RepoInfos::parse_repo_infos(&repo_infos.clone()))
+        let format = self.format;
+        tokio::task::spawn_blocking(move || RepoInfos::parse_repo_infos(&repo_infos, format))
             .await
             .wrap_err("Failed to spawn blocking task")?
             .wrap_err("Failed to parse repo information")?
@@ -73,20 +80,20 @@ impl Report {
 
 impl RepoInfos {
     #[throws]
-    fn parse_repo_infos(repo_infos: &Path) -> RepoInfos {
-        let mut rdr = csv::Reader::from_path(repo_infos)
-            .wrap_err_with(|| format!("Failed to create reader from path: {:?}", &repo_infos))?;
-        let mut map = HashMap::new();
-        for result in rdr.deserialize() {
-            let record: RepoInfo =
-                result.wrap_err("Failed to deserialize while parsing repo info")?;
-            map.insert(record.repo.clone(), record);
-        }
+    fn parse_repo_infos(repo_infos: &Path, format: OutputFormat) -> RepoInfos {
+        let records: Vec<RepoInfo> =
+            read_records(repo_infos, format).wrap_err("Failed to parse repo info")?;
+        let map = records
+            .into_iter()
+            .map(|record| (record.repo.clone(), record))
+            .collect();
         RepoInfos { repos: map }
     }
 
-    pub(super) fn get(&self, repo: &str) -> &RepoInfo {
-        &self.repos[repo]
+    /// The information about `repo`, or `None` if it has no row, e.g. because
+    /// gathering it failed.
+    pub(super) fn get(&self, repo: &str) -> Option<&RepoInfo> {
+        self.repos.get(repo)
     }
 }
 
@@ -98,10 +105,12 @@ impl RepoInfo {
     ) -> bool {
         let hc = &config.high_contributor;
 
-        let participated_in_percentage = percentage(participant.participated_in, self.num_prs);
-        let authored_percentage = percentage(participant.authored, self.num_prs);
+        // Compare against the unrounded percentages so that, e.g., 10.4% counts
+        // as exceeding a 10% threshold.
+        let participated_in_percentage = percentage_f64(participant.participated_in, self.num_prs);
+        let authored_percentage = percentage_f64(participant.authored, self.num_prs);
         let reviewed_or_resolved_percentage =
-            percentage(participant.reviewed_or_resolved(), self.num_prs);
+            percentage_f64(participant.reviewed_or_resolved(), self.num_prs);
 
         // Identify "high" reviewers or active people.
         let high_reviewer = reviewed_or_resolved_percentage > hc.high_reviewer_min_percentage
@@ -116,3 +125,79 @@ impl RepoInfo {
         high_total >= hc.high_contributor_categories_threshold
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::HighContributorConfig;
+    use super::*;
+
+    fn repo_info(num_prs: u64) -> RepoInfo {
+        RepoInfo {
+            org: "rust-lang".to_string(),
+            repo: "rust".to_string(),
+            num_prs,
+            num_opened: 0,
+            num_closed: 0,
+            num_stale_prs: 0,
+            start: "2024-01-01".to_string(),
+            end: "2024-01-31".to_string(),
+        }
+    }
+
+    fn author(prs: u64) -> RepoParticipant {
+        RepoParticipant {
+            participant: "alice".to_string(),
+            repo: "rust".to_string(),
+            participated_in: prs,
+            authored: prs,
+            reviewed: 0,
+            resolved: 0,
+        }
+    }
+
+    fn config(min_percentage: f64) -> ReportConfig {
+        ReportConfig::builder(
+            "rust-lang",
+            "2024-01-01".parse().unwrap(),
+            "2024-01-31".parse().unwrap(),
+        )
+        .high_contributor(HighContributorConfig {
+            high_participant_min_percentage: min_percentage,
+            high_author_min_percentage: min_percentage,
+            ..Default::default()
+        })
+        .build()
+    }
+
+    #[test]
+    fn thresholds_are_compared_with_unrounded_percentages() {
+        let repo_info = repo_info(1000);
+        // 10.4% rounds to 10%, which wouldn't exceed a 10% threshold.
+        assert!(repo_info.is_high_contributor(&config(10.0), &author(104)));
+        assert!(!repo_info.is_high_contributor(&config(10.0), &author(100)));
+        // 12.5% exactly doesn't exceed a 12.5% threshold, but 12.6% does.
+        assert!(!repo_info.is_high_contributor(&config(12.5), &author(125)));
+        assert!(repo_info.is_high_contributor(&config(12.5), &author(126)));
+    }
+
+    #[test]
+    fn thresholds_can_be_integers_or_fractions() {
+        let config: HighContributorConfig = toml::from_str(
+            r#"
+                high_reviewer_min_percentage = 10
+                high_reviewer_min_prs = 2
+                reviewer_saturation_threshold = 50
+                author_saturation_threshold = 33.3
+                high_participant_min_percentage = 12.5
+                high_participant_min_prs = 2
+                high_author_min_percentage = 10
+                high_author_min_prs = 2
+                high_contributor_categories_threshold = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.high_reviewer_min_percentage, 10.0);
+        assert_eq!(config.author_saturation_threshold, 33.3);
+        assert_eq!(config.high_participant_min_percentage, 12.5);
+    }
+}
@@ -1,24 +1,24 @@
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
-use crate::metrics;
+use crate::metrics::{self, GithubContext};
 use crate::report::repo_info::RepoInfo;
-use crate::report::Report;
-use crate::util::percentage;
+use crate::report::{read_records, OutputFormat, Report};
+use crate::util::{is_bot, percentage, ranking};
 use fehler::throws;
 use serde::Deserialize;
 use stable_eyre::eyre::{Error, WrapErr};
 
 use super::ReportConfig;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct RepoParticipants {
     pub participants: Vec<RepoParticipant>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RepoParticipant {
-    #[serde(rename = "#")]
-    pub row: usize,
     #[serde(rename = "Participant")]
     pub participant: String,
     #[serde(rename = "Repository")]
@@ -35,26 +35,35 @@ pub struct RepoParticipant {
 
 impl Report {
     #[throws]
-    pub(super) async fn repo_participants(&self, config: &ReportConfig) -> RepoParticipants {
-        let input_dir = self.input_dir();
-        let repo_participants = input_dir.join("repo-participants.csv");
+    pub(super) async fn repo_participants(
+        &self,
+        config: &ReportConfig,
+        context: &Arc<GithubContext>,
+    ) -> RepoParticipants {
         let graphql = self.graphql("repo-participants");
 
-        self.produce_input(
-            &repo_participants,
-            metrics::RepoParticipants::new(
-                graphql,
-                config.github.org.clone(),
-                config.github.repos.clone(),
-                config.data_source.start_date.clone(),
-                config.data_source.end_date.clone(),
-            ),
-        )
-        .await
-        .wrap_err_with(|| format!("Failed to produce input data for {:?}", &repo_participants))?;
+        let repo_participants = self
+            .produce_input(
+                config,
+                "repo-participants",
+                metrics::RepoParticipants::new(
+                    graphql,
+                    config.github.org.clone(),
+                    config.github.repos.clone(),
+                    config.data_source.start_date.clone(),
+                    config.data_source.end_date.clone(),
+                )
+                .with_aliases(config.aliases.clone())
+                .with_affiliations(context.clone(), config.github.bots.clone()),
+            )
+            .await
+            .wrap_err("Failed to produce input data for repo-participants")?;
 
+        let bots = config.github.bots.clone();
+        let aliases = config.aliases.clone();
+        let format = self.format;
         tokio::task::spawn_blocking(move || {
-            RepoParticipants::parse_participants(&repo_participants)
+            RepoParticipants::parse_participants(&repo_participants, format, &bots, &aliases)
         })
         .await
         .wrap_err("Failed to parse repo participants")??
@@ -62,29 +71,51 @@ impl Report {
 }
 
 impl RepoParticipants {
+    /// Reads the participants from `repo_participants`, leaving out `bots` and merging
+    /// the rows of aliased logins into those of their canonical logins by summing them.
     #[throws]
-    fn parse_participants(repo_participants: &Path) -> Self {
-        let mut rdr = csv::Reader::from_path(repo_participants).wrap_err_with(|| {
-            format!("Failed to create reader from path {:?}", &repo_participants)
-        })?;
-        let mut vec = Vec::new();
-        for result in rdr.deserialize() {
-            let record: RepoParticipant =
-                result.wrap_err("Failed to deserialize while parsing repo participants")?;
-            if !is_robot(&record.participant) {
-                vec.push(record);
+    fn parse_participants(
+        repo_participants: &Path,
+        format: OutputFormat,
+        bots: &[String],
+        aliases: &HashMap<String, String>,
+    ) -> Self {
+        let mut records: Vec<RepoParticipant> = read_records(repo_participants, format)
+            .wrap_err("Failed to parse repo participants")?;
+        records.retain(|record| !is_bot(&record.participant, false, bots));
+
+        let mut participants: Vec<RepoParticipant> = vec![];
+        let mut index: HashMap<(String, String), usize> = HashMap::new();
+        for mut record in records {
+            if let Some(canonical) = aliases.get(&record.participant) {
+                record.participant = canonical.clone();
+            }
+            match index.get(&(record.participant.clone(), record.repo.clone())) {
+                Some(&i) => participants[i].merge(&record),
+                None => {
+                    index.insert(
+                        (record.participant.clone(), record.repo.clone()),
+                        participants.len(),
+                    );
+                    participants.push(record);
+                }
             }
         }
-        Self { participants: vec }
+
+        Self { participants }
     }
 
-    /// Finds the participant with the maximum value for `key`.
+    /// Finds the participant with the maximum value for `key`; of several tied
+    /// participants, the first by name.
     pub fn top_participant(
         &self,
         repo_info: &RepoInfo,
         key: impl Fn(&RepoParticipant) -> u64,
     ) -> (String, u64) {
-        match self.in_repo(repo_info).max_by_key(|p| key(p)) {
+        match self
+            .in_repo(repo_info)
+            .min_by_key(|p| ranking(key(p), &p.participant))
+        {
             Some(p) => (p.participant.clone(), percentage(key(p), repo_info.num_prs)),
             None => ("N/A".to_string(), 0),
         }
@@ -102,21 +133,15 @@ impl RepoParticipants {
 }
 
 impl RepoParticipant {
+    /// Adds the counts of `other`, e.g. an alias of the same person.
+    fn merge(&mut self, other: &RepoParticipant) {
+        self.participated_in += other.participated_in;
+        self.authored += other.authored;
+        self.reviewed += other.reviewed;
+        self.resolved += other.resolved;
+    }
+
     pub(super) fn reviewed_or_resolved(&self) -> u64 {
         self.reviewed.max(self.resolved)
     }
 }
-
-fn is_robot(login: &str) -> bool {
-    // FIXME: move to configuration
-    const ROBOTS: &[&str] = &[
-        "rust-highfive",
-        "bors",
-        "rustbot",
-        "rust-log-analyzer",
-        "rust-timer",
-        "rfcbot",
-    ];
-
-    ROBOTS.contains(&login)
-}
@@ -0,0 +1,68 @@
+use std::fs::File;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use super::{Report, ReportConfig, ReportData};
+use crate::util::percentage;
+
+impl Report {
+    /// Writes, for each repository, how concentrated reviewing is: the share of all
+    /// reviews done by the top reviewer and by the top three reviewers.
+    ///
+    /// A review here is a PR that a participant reviewed or resolved, as in the
+    /// high-contributor metrics, so a PR reviewed by two people counts twice.
+    #[throws]
+    pub(super) fn write_reviewer_concentration(&self, config: &ReportConfig, data: &ReportData) {
+        let output = self.output_dir().join("reviewer-concentration.csv");
+        let output =
+            File::create(output).wrap_err("Failed to create file 'reviewer-concentration.csv'")?;
+        let mut csv = config
+            .csv
+            .writer(output)
+            .wrap_err("Failed to start writing reviewer concentration")?;
+        csv.write_record(["Repository", "Top reviewer share %", "Top-3 share %"])
+            .wrap_err("Failed to write headers while writing reviewer concentration")?;
+
+        for repo in &config.github.repos {
+            let (top, top_3) = top_shares(
+                data.repo_participants
+                    .participants
+                    .iter()
+                    .filter(|p| &p.repo == repo)
+                    .map(|p| p.reviewed_or_resolved()),
+            );
+            csv.write_record(&[
+                repo.clone(),
+                config.csv.format_number(top.to_string()),
+                config.csv.format_number(top_3.to_string()),
+            ])
+            .wrap_err_with(|| format!("Failed to write reviewer concentration for {}", repo))?;
+        }
+    }
+}
+
+/// The percentages of all of `reviews` done by the top reviewer and by the top three.
+fn top_shares(reviews: impl Iterator<Item = u64>) -> (u64, u64) {
+    let mut reviews: Vec<u64> = reviews.collect();
+    reviews.sort_unstable_by(|a, b| b.cmp(a));
+
+    let total: u64 = reviews.iter().sum();
+    let top: u64 = reviews.iter().take(1).sum();
+    let top_3: u64 = reviews.iter().take(3).sum();
+    (percentage(top, total), percentage(top_3, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_reviewer_doing_most_reviews() {
+        assert_eq!(top_shares([2, 14, 1, 0, 3].into_iter()), (70, 95));
+        assert_eq!(top_shares([5, 5].into_iter()), (50, 100));
+        // no reviews at all
+        assert_eq!(top_shares([0, 0].into_iter()), (0, 0));
+        assert_eq!(top_shares(std::iter::empty()), (0, 0));
+    }
+}
@@ -1,11 +1,7 @@
 use super::{Report, ReportConfig, ReportData};
 use crate::util::percentage;
 
-use std::{
-    fs::File,
-    io::Read,
-    path::{Path, PathBuf},
-};
+use std::{fs::File, io::Read, path::Path};
 
 use fehler::throws;
 use rust_playground_top_crates::Modifications;
@@ -14,23 +10,29 @@ use stable_eyre::eyre::{Error, WrapErr};
 
 #[derive(Debug)]
 pub(super) struct TopCrateInfo {
-    name: String,
     version: semver::Version,
-    id: String,
 }
 
 impl Report {
     #[throws]
-    pub(super) async fn top_crates(&self, _config: &ReportConfig) -> Vec<TopCrateInfo> {
+    pub(super) async fn top_crates(&self, config: &ReportConfig) -> Vec<TopCrateInfo> {
         let path = self.data_dir.join("crate-information.json");
 
-        // if the `crate-information.json` doesn't exist, generate it
+        // if the `crate-information.json` doesn't exist, fetch it from the configured
+        // source, or else generate it from crates.io
         if !path.exists() {
-            let copy_data_dir = self.data_dir.to_path_buf();
-            tokio::task::spawn_blocking(move || generate_crate_information(&copy_data_dir))
-                .await
-                .wrap_err("Failed to spawn blocking task")?
-                .wrap_err("Failed to generate top-crates files")?;
+            match &config.top_crates.source {
+                Some(source) => fetch_crate_information(source, &path)
+                    .await
+                    .wrap_err_with(|| format!("Failed to get top crates from {}", source))?,
+                None => {
+                    let copy_data_dir = self.data_dir.to_path_buf();
+                    tokio::task::spawn_blocking(move || generate_crate_information(&copy_data_dir))
+                        .await
+                        .wrap_err("Failed to spawn blocking task")?
+                        .wrap_err("Failed to generate top-crates files")?;
+                }
+            }
         }
 
         tokio::task::spawn_blocking(move || load_top_crates(&path))
@@ -39,7 +41,7 @@ impl Report {
     }
 
     #[throws]
-    pub(super) fn write_top_crates(&self, _config: &ReportConfig, data: &ReportData) {
+    pub(super) fn write_top_crates(&self, config: &ReportConfig, data: &ReportData) {
         let stable_crates = data
             .top_crates
             .iter()
@@ -52,20 +54,23 @@ impl Report {
         let output_file = &mut File::create(output.clone())
             .wrap_err_with(|| format!("Failed to create file from path {:?}", &output))?;
 
-        let mut csv = csv::Writer::from_writer(output_file);
+        let mut csv = config
+            .csv
+            .writer(output_file)
+            .wrap_err("Failed to start writing top crates")?;
 
         csv.write_record(&["Measurement".to_string(), "Value".to_string()])
             .wrap_err("Failed to write headers while writing top crates")?;
 
         csv.write_record(&[
             "Total 'significant' crates".to_string(),
-            total_crates.to_string(),
+            config.csv.format_number(total_crates.to_string()),
         ])
         .wrap_err("Failed to write Total Significant Crates while writing top crates")?;
 
         csv.write_record(&[
             "crates at 1.0 or higher".to_string(),
-            percentage.to_string(),
+            config.csv.format_number(percentage.to_string()),
         ])
         .wrap_err("Failed to write High Percentage crates while writing top crates")?;
     }
@@ -78,25 +83,48 @@ impl Report {
 fn load_top_crates(path: &Path) -> Vec<TopCrateInfo> {
     #[derive(Deserialize)]
     struct TC {
-        name: String,
         version: String,
-        id: String,
     }
-    let data = std::fs::read_to_string(&path.clone())
+    let data = std::fs::read_to_string(path)
         .wrap_err_with(|| format!("Failed to read data from path {:?}", &path))?;
     let data: Vec<TC> = serde_json::from_str(&data)
         .wrap_err_with(|| format!("Failed to serialize data: {}", data))?;
     let mut vec = Vec::new();
-    for TC { name, version, id } in data {
+    for TC { version } in data {
         let version = semver::Version::parse(&version.clone())
             .wrap_err_with(|| format!("Failed to parse version from {}", version))?;
-        vec.push(TopCrateInfo { name, version, id });
+        vec.push(TopCrateInfo { version });
     }
     vec
 }
 
+/// Copies `crate-information.json` from `source`, a local file or an `http(s)://` URL,
+/// to `path`.
 #[throws]
-pub fn generate_crate_information(base_directory: &PathBuf) {
+async fn fetch_crate_information(source: &str, path: &Path) {
+    let data = if source.starts_with("http://") || source.starts_with("https://") {
+        let response = reqwest::get(source)
+            .await
+            .wrap_err("Failed to reach the top crates source")?
+            .error_for_status()
+            .wrap_err("The top crates source returned an error")?;
+        response
+            .bytes()
+            .await
+            .wrap_err("Failed to download the top crates")?
+            .to_vec()
+    } else {
+        tokio::fs::read(source)
+            .await
+            .wrap_err("Failed to read the top crates file")?
+    };
+    tokio::fs::write(path, data)
+        .await
+        .wrap_err_with(|| format!("Unable to write {}", path.display()))?;
+}
+
+#[throws]
+pub fn generate_crate_information(base_directory: &Path) {
     let mut f = File::open("crate-modifications.toml")
         .wrap_err("unable to open crate modifications file")?;
 
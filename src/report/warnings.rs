@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::path::Path;
+
+use fehler::throws;
+use stable_eyre::eyre::{Error, WrapErr};
+
+use crate::metrics::{CsvOptions, Warning};
+
+/// Writes `warnings.csv`, listing everything that made the report incomplete, e.g. metrics
+/// that failed or repositories that were skipped. It is written even if there are none,
+/// so that an empty list can be told apart from a report that didn't get that far.
+#[throws]
+pub(super) fn write_warnings(path: &Path, csv_options: &CsvOptions, warnings: &[Warning]) {
+    let output = File::create(path).wrap_err("Failed to create file 'warnings.csv'")?;
+    let mut csv = csv_options
+        .writer(output)
+        .wrap_err("Failed to start writing warnings")?;
+    csv.write_record(["Metric", "Repository", "Reason"])
+        .wrap_err("Failed to write headers while writing warnings")?;
+    for warning in warnings {
+        csv.write_record([
+            warning.metric.as_str(),
+            warning.repo.as_deref().unwrap_or_default(),
+            warning.reason.as_str(),
+        ])
+        .wrap_err_with(|| format!("Failed to write warning {:?}", warning))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_row_per_warning() {
+        let path =
+            std::env::temp_dir().join(format!("optopodi-warnings-{}.csv", std::process::id()));
+        let warnings = [
+            Warning {
+                metric: "config".to_string(),
+                repo: None,
+                reason: "--limit 1 applied".to_string(),
+            },
+            Warning {
+                metric: "high-contributors".to_string(),
+                repo: Some("rust".to_string()),
+                reason: "skipped, as it has no repo info, e.g. \"renamed\"".to_string(),
+            },
+        ];
+        write_warnings(&path, &CsvOptions::default(), &warnings).unwrap();
+
+        let records: Vec<Vec<String>> = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(&path)
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ["Metric", "Repository", "Reason"],
+                ["config", "", "--limit 1 applied"],
+                [
+                    "high-contributors",
+                    "rust",
+                    "skipped, as it has no repo info, e.g. \"renamed\""
+                ],
+            ]
+        );
+    }
+}
@@ -1,29 +1,152 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use fehler::throws;
+use serde::{Deserialize, Serialize};
 use stable_eyre::eyre;
 use stable_eyre::eyre::{Error, WrapErr};
 
-/// Finds the token in the user's environment, panicking if no suitable token
+/// How long before an installation token lapses we mint a fresh one.
+/// Installation tokens are valid for one hour.
+const APP_TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(50 * 60);
+
+/// The credentials used to authenticate against GitHub.
+pub enum GithubAuth {
+    /// A static personal access token.
+    PersonalToken(String),
+    /// A GitHub App installation; short-lived tokens are minted from these credentials.
+    App(AppCredentials),
+}
+
+pub struct AppCredentials {
+    app_id: u64,
+    private_key: String,
+    installation_id: u64,
+}
+
+/// Finds the credentials in the user's environment, preferring GitHub App
+/// credentials if `GITHUB_APP_ID`, `GITHUB_APP_PRIVATE_KEY` and
+/// `GITHUB_APP_INSTALLATION_ID` are all set, and falling back to a personal
+/// access token otherwise.
+#[throws]
+pub fn github_auth() -> GithubAuth {
+    if let Some(app) =
+        get_app_credentials_from_env().wrap_err("Failed to read GitHub App config")?
+    {
+        return GithubAuth::App(app);
+    }
+
+    GithubAuth::PersonalToken(github_token()?)
+}
+
+/// Finds the token in the user's environment, failing if no suitable token
 /// can be found.
+///
+/// In order of precedence, the token is taken from:
+/// - `GITHUB_TOKEN`; if it starts with `@`, the rest is the path of a file holding the token
+/// - the file named by `GITHUB_TOKEN_FILE`
+/// - the `github.oauth-token` git config setting
+/// - `gh auth token`, if the GitHub CLI is installed and logged in
 #[throws]
 pub fn github_token() -> String {
-    if let Some(s) = get_token_from_env() {
-        return s;
-    }
+    first_token(&[
+        ("GITHUB_TOKEN", &get_token_from_env),
+        ("GITHUB_TOKEN_FILE", &get_token_from_file_env),
+        ("Git Config", &get_token_from_git_config),
+        ("the gh CLI", &get_token_from_gh_cli),
+    ])?
+}
+
+/// A named place a token may be found, e.g. `("GITHUB_TOKEN", &get_token_from_env)`.
+type TokenSource<'a> = (&'a str, &'a dyn Fn() -> Result<Option<String>, Error>);
 
-    if let Some(s) = get_token_from_git_config().wrap_err("Failed to get token from Git Config")? {
-        return s;
+/// The token from the first of `sources` that has one; later sources aren't consulted.
+#[throws]
+fn first_token(sources: &[TokenSource<'_>]) -> String {
+    for (name, source) in sources {
+        if let Some(s) = source().wrap_err_with(|| format!("Failed to get token from {}", name))? {
+            return s;
+        }
     }
 
     eyre::bail!("could not find github token");
 }
 
+/// Initializes the static octocrab instance from `auth`.
+///
+/// For GitHub App credentials, this also spawns a background task that
+/// re-initializes octocrab with a fresh installation token before the current one lapses.
+/// Each request for an installation token is given up after `timeout`, as GraphQL
+/// queries are.
+#[throws]
+pub async fn initialise_octocrab(auth: GithubAuth, timeout: Duration) {
+    match auth {
+        GithubAuth::PersonalToken(token) => {
+            octocrab::initialise(octocrab::Octocrab::builder().personal_token(token))
+                .wrap_err("Failed to initialize static instance of Octocrab")?;
+        }
+        GithubAuth::App(app) => {
+            let token = app
+                .installation_token(timeout)
+                .await
+                .wrap_err("Failed to mint GitHub App installation token")?;
+            octocrab::initialise(octocrab::Octocrab::builder().personal_token(token))
+                .wrap_err("Failed to initialize static instance of Octocrab")?;
+
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(APP_TOKEN_REFRESH_INTERVAL).await;
+                    let result = app.installation_token(timeout).await.and_then(|token| {
+                        octocrab::initialise(octocrab::Octocrab::builder().personal_token(token))
+                            .wrap_err("Failed to re-initialize static instance of Octocrab")
+                    });
+                    if let Err(e) = result {
+                        tracing::error!("Failed to refresh GitHub App installation token: {:?}", e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[throws]
 fn get_token_from_env() -> Option<String> {
-    match std::env::var("GITHUB_TOKEN") {
-        Ok(v) => Some(v),
+    token_from_env_value(std::env::var("GITHUB_TOKEN").ok())?
+}
+
+/// The token given by the value of `GITHUB_TOKEN`, if it's set: either the value itself,
+/// or if it starts with `@`, the contents of the file named by the rest.
+#[throws]
+fn token_from_env_value(value: Option<String>) -> Option<String> {
+    match value {
+        Some(token) => match token.strip_prefix('@') {
+            Some(path) => Some(read_token_file(path)?),
+            None => Some(token),
+        },
+        None => None,
+    }
+}
+
+#[throws]
+fn get_token_from_file_env() -> Option<String> {
+    match std::env::var("GITHUB_TOKEN_FILE") {
+        Ok(path) => Some(read_token_file(&path)?),
         Err(_) => None,
     }
 }
 
+/// Reads a token from the file at `path`, ignoring surrounding whitespace
+/// (such as the trailing newline most editors add).
+#[throws]
+fn read_token_file(path: &str) -> String {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read token file {:?}", path))?;
+    let token = contents.trim();
+    if token.is_empty() {
+        eyre::bail!("token file {:?} is empty", path);
+    }
+    token.to_string()
+}
+
 #[throws]
 fn get_token_from_git_config() -> Option<String> {
     let output = std::process::Command::new("git")
@@ -39,3 +162,284 @@ fn get_token_from_git_config() -> Option<String> {
         None
     }
 }
+
+#[throws]
+fn get_token_from_gh_cli() -> Option<String> {
+    token_from_gh_output(
+        std::process::Command::new("gh")
+            .arg("auth")
+            .arg("token")
+            .output(),
+    )?
+}
+
+/// The token printed by `gh auth token`, given the result of running it: none if
+/// `gh` isn't installed, isn't logged in, or prints nothing.
+#[throws]
+fn token_from_gh_output(output: std::io::Result<std::process::Output>) -> Option<String> {
+    let output = match output {
+        Ok(output) => output,
+        // `gh` isn't installed
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => Err(e).wrap_err("Failed to run `gh auth token`")?,
+    };
+    if output.status.success() {
+        let gh_token = String::from_utf8(output.stdout)?.trim().to_string();
+        Some(gh_token).filter(|token| !token.is_empty())
+    } else {
+        None
+    }
+}
+
+#[throws]
+fn get_app_credentials_from_env() -> Option<AppCredentials> {
+    let (app_id, private_key, installation_id) = match (
+        std::env::var("GITHUB_APP_ID"),
+        std::env::var("GITHUB_APP_PRIVATE_KEY"),
+        std::env::var("GITHUB_APP_INSTALLATION_ID"),
+    ) {
+        (Ok(a), Ok(k), Ok(i)) => (a, k, i),
+        _ => return None,
+    };
+
+    Some(AppCredentials {
+        app_id: app_id
+            .trim()
+            .parse()
+            .wrap_err_with(|| format!("Invalid GITHUB_APP_ID {:?}", app_id))?,
+        private_key,
+        installation_id: installation_id.trim().parse().wrap_err_with(|| {
+            format!("Invalid GITHUB_APP_INSTALLATION_ID {:?}", installation_id)
+        })?,
+    })
+}
+
+/// The claims of the JWT used to authenticate as the GitHub App itself.
+#[derive(Debug, Serialize)]
+struct AppClaims {
+    /// Issued-at time, backdated to allow for clock drift.
+    iat: u64,
+    /// Expiration time; GitHub allows at most 10 minutes.
+    exp: u64,
+    /// The GitHub App id.
+    iss: String,
+}
+
+impl AppClaims {
+    fn new(app_id: u64, now: u64) -> Self {
+        AppClaims {
+            iat: now - 60,
+            exp: now + 9 * 60,
+            iss: app_id.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+    token: String,
+}
+
+impl AppCredentials {
+    /// Mints a JWT signed with the App's private key.
+    #[throws]
+    fn jwt(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("System clock is before the unix epoch")?
+            .as_secs();
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(self.private_key.as_bytes())
+            .wrap_err("Failed to parse GITHUB_APP_PRIVATE_KEY as an RSA PEM key")?;
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &AppClaims::new(self.app_id, now),
+            &key,
+        )
+        .wrap_err("Failed to sign GitHub App JWT")?
+    }
+
+    /// Exchanges the App's JWT for an installation access token, giving up after `timeout`.
+    #[throws]
+    async fn installation_token(&self, timeout: Duration) -> String {
+        let jwt = self.jwt()?;
+        let app_client = octocrab::Octocrab::builder()
+            .personal_token(jwt)
+            .build()
+            .wrap_err("Failed to build GitHub App client")?;
+        self.exchange_jwt(&app_client, timeout).await?
+    }
+
+    /// Asks GitHub for an installation access token through `app_client`, which is
+    /// authenticated with the App's JWT, giving up after `timeout`.
+    #[throws]
+    async fn exchange_jwt(&self, app_client: &octocrab::Octocrab, timeout: Duration) -> String {
+        let request = app_client.post(
+            format!("app/installations/{}/access_tokens", self.installation_id),
+            None::<&()>,
+        );
+        let response: InstallationToken = tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| crate::metrics::TimedOut { timeout })?
+            .wrap_err_with(|| {
+                format!(
+                    "Failed to create access token for installation {}",
+                    self.installation_id
+                )
+            })?;
+        response.token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn installation_token_request_times_out() {
+        // a server that accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let _connections: Vec<_> = listener.incoming().collect();
+        });
+        let app_client = octocrab::Octocrab::builder()
+            .base_url(url)
+            .unwrap()
+            .build()
+            .unwrap();
+        let app = AppCredentials {
+            app_id: 1,
+            private_key: String::new(),
+            installation_id: 2,
+        };
+
+        let timeout = Duration::from_millis(200);
+        let error = app.exchange_jwt(&app_client, timeout).await.unwrap_err();
+        let timed_out = error
+            .downcast_ref::<crate::metrics::TimedOut>()
+            .expect("a TimedOut error");
+        assert_eq!(timed_out.timeout, timeout);
+    }
+
+    /// Writes `contents` to a token file unique to this test, returning its path.
+    fn token_file(name: &str, contents: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("optopodi-token-{}-{}", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn token_from_the_env_value_or_a_file_it_names() {
+        assert_eq!(token_from_env_value(None).unwrap(), None);
+        assert_eq!(
+            token_from_env_value(Some("ghp_abc".to_string())).unwrap(),
+            Some("ghp_abc".to_string())
+        );
+
+        let path = token_file("at-path", "ghp_from_file");
+        assert_eq!(
+            token_from_env_value(Some(format!("@{}", path))).unwrap(),
+            Some("ghp_from_file".to_string())
+        );
+    }
+
+    #[test]
+    fn token_file_whitespace_is_ignored() {
+        let path = token_file("newline", "ghp_abc\n");
+        assert_eq!(read_token_file(&path).unwrap(), "ghp_abc");
+    }
+
+    #[test]
+    fn empty_token_files_are_rejected() {
+        let path = token_file("empty", "");
+        let error = read_token_file(&path).unwrap_err();
+        assert!(error.to_string().contains("is empty"), "{}", error);
+
+        let path = token_file("blank", " \n");
+        assert!(read_token_file(&path).is_err());
+        assert!(token_from_env_value(Some(format!("@{}", path))).is_err());
+    }
+
+    #[test]
+    fn missing_token_files_are_reported() {
+        let error = read_token_file("/nonexistent/optopodi-token").unwrap_err();
+        assert!(
+            error.to_string().contains("Failed to read token file"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn the_first_source_with_a_token_wins() {
+        let none = || Ok(None);
+        let token = |s: &'static str| move || Ok(Some(s.to_string()));
+        let unreachable = || -> Result<Option<String>, Error> { panic!("consulted too late") };
+
+        let (first, second) = (token("first"), token("second"));
+        assert_eq!(
+            first_token(&[("a", &none), ("b", &first), ("c", &second)]).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            first_token(&[("a", &second), ("b", &unreachable)]).unwrap(),
+            "second"
+        );
+
+        let error = first_token(&[("a", &none), ("b", &none)]).unwrap_err();
+        assert_eq!(error.to_string(), "could not find github token");
+
+        let failing = || Err(eyre::eyre!("unreadable"));
+        let error = first_token(&[("a", &none), ("b", &failing), ("c", &first)]).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to get token from b");
+    }
+
+    #[test]
+    fn gh_not_installed_has_no_token() {
+        let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(token_from_gh_output(Err(not_found)).unwrap(), None);
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(token_from_gh_output(Err(denied)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gh_output() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = |code: i32, stdout: &str| {
+            Ok(std::process::Output {
+                status: std::process::ExitStatus::from_raw(code << 8),
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: vec![],
+            })
+        };
+        assert_eq!(
+            token_from_gh_output(output(0, "gho_abc\n")).unwrap(),
+            Some("gho_abc".to_string())
+        );
+        // logged out
+        assert_eq!(token_from_gh_output(output(1, "")).unwrap(), None);
+        assert_eq!(token_from_gh_output(output(0, "\n")).unwrap(), None);
+    }
+
+    #[test]
+    fn app_claims() {
+        let now = 1_700_000_000;
+        let claims = AppClaims::new(12345, now);
+        // backdated for clock drift, but still in the recent past
+        assert!(claims.iat < now);
+        assert!(now - claims.iat <= 60);
+        // GitHub rejects JWTs that expire more than 10 minutes after they're issued
+        assert!(claims.exp > now);
+        assert!(claims.exp - claims.iat <= 10 * 60);
+        assert_eq!(claims.iss, "12345");
+
+        assert_eq!(
+            serde_json::to_value(&claims).unwrap(),
+            serde_json::json!({ "iat": now - 60, "exp": now + 9 * 60, "iss": "12345" })
+        );
+    }
+}
@@ -1,7 +1,308 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use fehler::throws;
+use stable_eyre::eyre::{self, Error};
+use toml::value::{Datetime, Offset};
+
+/// Computes `numerator` as a percentage of `denominator`, rounded half-up to
+/// the nearest whole percent.
+///
+/// The intermediate product is computed in `u128`, so large inputs cannot
+/// overflow. A zero `denominator` yields `0`.
 pub fn percentage(numerator: u64, denominator: u64) -> u64 {
-    if denominator != 0 {
-        (numerator * 100) / denominator
+    if denominator == 0 {
+        return 0;
+    }
+    let numerator = numerator as u128 * 100;
+    let denominator = denominator as u128;
+    ((numerator + denominator / 2) / denominator) as u64
+}
+
+/// Computes `numerator` as an unrounded, fractional percentage of `denominator`.
+///
+/// A zero `denominator` yields `0.0`.
+pub fn percentage_f64(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        return 0.0;
+    }
+    numerator as f64 * 100.0 / denominator as f64
+}
+
+/// The key for ranking rows: the largest `count` first, with ties broken by `name`
+/// in ascending order, so that rows with equal counts always come out in the same order.
+pub fn ranking<N: Ord>(count: u64, name: N) -> (Reverse<u64>, N) {
+    (Reverse(count), name)
+}
+
+/// Converts a TOML date or datetime into a UTC timestamp, checking that it is a real
+/// calendar date and time.
+///
+/// Plain dates are taken to mean midnight UTC, and times without an offset are taken to be UTC.
+#[throws]
+pub fn timestamp(datetime: &Datetime) -> DateTime<Utc> {
+    let date = match &datetime.date {
+        Some(date) => date,
+        None => eyre::bail!("`{}` has no date component", datetime),
+    };
+    let date = NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+        .ok_or_else(|| eyre::eyre!("`{}` is not a valid calendar date", datetime))?;
+
+    let time = match &datetime.time {
+        Some(time) => NaiveTime::from_hms_nano_opt(
+            time.hour.into(),
+            time.minute.into(),
+            time.second.into(),
+            time.nanosecond,
+        )
+        .ok_or_else(|| eyre::eyre!("`{}` is not a valid time of day", datetime))?,
+        None => NaiveTime::from_hms_opt(0, 0, 0).expect("midnight is a valid time"),
+    };
+
+    let offset_seconds = match &datetime.offset {
+        None | Some(Offset::Z) => 0,
+        Some(Offset::Custom { hours, minutes }) => {
+            let sign = if *hours < 0 { -1 } else { 1 };
+            i32::from(*hours) * 3600 + sign * i32::from(*minutes) * 60
+        }
+    };
+    let offset = FixedOffset::east_opt(offset_seconds)
+        .ok_or_else(|| eyre::eyre!("`{}` has an invalid offset", datetime))?;
+
+    offset
+        .from_local_datetime(&date.and_time(time))
+        .single()
+        .ok_or_else(|| eyre::eyre!("`{}` is not a valid timestamp", datetime))?
+        .with_timezone(&Utc)
+}
+
+/// The number of hours from `start` to `end`, negative if `end` is earlier.
+pub fn hours_between(start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    (end - start).num_seconds() as f64 / 3600.0
+}
+
+/// Whether activity by `login` is a bot's, and so left out of the metrics: either it
+/// is a GitHub App (`is_app`, i.e. the account's `__typename` is `Bot`, or a login
+/// ending in `[bot]`, which is how the REST API and commits name Apps), or it is one
+/// of the configured `bots`.
+pub fn is_bot(login: &str, is_app: bool, bots: &[String]) -> bool {
+    is_app || login.ends_with("[bot]") || bots.iter().any(|bot| bot == login)
+}
+
+/// The local hours, from the start of the first to the end of the last, that count as working hours.
+pub const WORKING_HOURS: std::ops::Range<u32> = 9..17;
+
+/// When people are at work, for measuring durations in business hours: the
+/// `WORKING_HOURS` of weekdays that aren't `holidays`, in `timezone`.
+#[derive(Clone, Debug)]
+pub struct BusinessCalendar {
+    pub timezone: Tz,
+    pub holidays: HashSet<NaiveDate>,
+}
+
+/// The number of business hours of `calendar` from `start` to `end`; zero if `end` is
+/// earlier. Days are taken in the calendar's timezone, with daylight saving time taken
+/// into account, so e.g. Friday 4pm to Monday 10am is 2 hours.
+pub fn business_hours_between(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    calendar: &BusinessCalendar,
+) -> f64 {
+    if end <= start {
+        return 0.0;
+    }
+
+    let local_hour = |day: NaiveDate, hour: u32| {
+        let time = NaiveTime::from_hms_opt(hour, 0, 0).expect("a valid hour");
+        // clocks change in the small hours, so this is only `None` for a (very odd) timezone
+        // that skips the opening or closing hour; that day is then not counted
+        calendar
+            .timezone
+            .from_local_datetime(&day.and_time(time))
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+    };
+
+    let mut seconds = 0;
+    let last_day = end.with_timezone(&calendar.timezone).date_naive();
+    let mut day = start.with_timezone(&calendar.timezone).date_naive();
+    while day <= last_day {
+        let working_day = !matches!(day.weekday(), Weekday::Sat | Weekday::Sun)
+            && !calendar.holidays.contains(&day);
+        if working_day {
+            let opening = local_hour(day, WORKING_HOURS.start);
+            let closing = local_hour(day, WORKING_HOURS.end);
+            if let (Some(opening), Some(closing)) = (opening, closing) {
+                let from = opening.max(start);
+                let to = closing.min(end);
+                if from < to {
+                    seconds += (to - from).num_seconds();
+                }
+            }
+        }
+        day = day.succ_opt().expect("not the last day ever");
+    }
+
+    seconds as f64 / 3600.0
+}
+
+/// The median of `values`, or `None` if there are none.
+pub fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
     } else {
-        0
+        values[mid]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bots() {
+        let bots = vec!["bors".to_string()];
+        assert!(is_bot("dependabot", true, &bots));
+        assert!(is_bot("bors", false, &bots));
+        assert!(!is_bot("alice", false, &bots));
+        assert!(!is_bot("bors-fan", false, &bots));
+        assert!(!is_bot("alice", false, &[]));
+    }
+
+    #[test]
+    fn bot_suffixes() {
+        assert!(is_bot("dependabot[bot]", false, &[]));
+        assert!(is_bot("github-actions[bot]", false, &[]));
+        assert!(!is_bot("robot", false, &[]));
+        assert!(!is_bot("[bot]alice", false, &[]));
+    }
+
+    #[test]
+    fn ties_are_ranked_by_name() {
+        let mut rows = vec![(2, "carol"), (5, "bob"), (2, "alice"), (5, "alice")];
+        rows.sort_by_key(|&(count, name)| ranking(count, name));
+        assert_eq!(
+            rows,
+            vec![(5, "alice"), (5, "bob"), (2, "alice"), (2, "carol")]
+        );
+    }
+
+    #[test]
+    fn percentage_rounds_half_up() {
+        assert_eq!(percentage(1, 3), 33);
+        assert_eq!(percentage(2, 3), 67);
+        assert_eq!(percentage(1, 200), 1);
+        assert_eq!(percentage(1, 201), 0);
+        assert_eq!(percentage(3, 3), 100);
+    }
+
+    #[test]
+    fn percentage_of_zero() {
+        assert_eq!(percentage(0, 0), 0);
+        assert_eq!(percentage(5, 0), 0);
+        assert_eq!(percentage(0, 5), 0);
+    }
+
+    #[test]
+    fn percentage_of_large_numbers() {
+        assert_eq!(percentage(u64::MAX, u64::MAX), 100);
+        assert_eq!(percentage(u64::MAX / 2, u64::MAX), 50);
+        assert_eq!(percentage(u64::MAX, u64::MAX / 2), 200);
+    }
+
+    fn calendar(timezone: Tz) -> BusinessCalendar {
+        BusinessCalendar {
+            timezone,
+            holidays: HashSet::new(),
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn business_hours_within_a_day() {
+        let utc = calendar(chrono_tz::UTC);
+        // Wednesday
+        let hours =
+            business_hours_between(at("2024-01-03T10:00:00Z"), at("2024-01-03T12:30:00Z"), &utc);
+        assert_eq!(hours, 2.5);
+    }
+
+    #[test]
+    fn business_hours_skip_weekends() {
+        let utc = calendar(chrono_tz::UTC);
+        // Friday 4pm to Monday 10am
+        let hours =
+            business_hours_between(at("2024-01-05T16:00:00Z"), at("2024-01-08T10:00:00Z"), &utc);
+        assert_eq!(hours, 2.0);
+        // Saturday to Sunday
+        let hours =
+            business_hours_between(at("2024-01-06T09:00:00Z"), at("2024-01-07T17:00:00Z"), &utc);
+        assert_eq!(hours, 0.0);
+    }
+
+    #[test]
+    fn business_hours_outside_working_hours() {
+        let utc = calendar(chrono_tz::UTC);
+        // from before opening to after closing counts only the working hours
+        let hours =
+            business_hours_between(at("2024-01-03T06:00:00Z"), at("2024-01-03T20:00:00Z"), &utc);
+        assert_eq!(hours, 8.0);
+        // from after closing to before the next opening counts nothing
+        let hours =
+            business_hours_between(at("2024-01-03T18:00:00Z"), at("2024-01-04T08:00:00Z"), &utc);
+        assert_eq!(hours, 0.0);
+    }
+
+    #[test]
+    fn business_hours_over_several_days() {
+        let utc = calendar(chrono_tz::UTC);
+        // Monday noon to Thursday noon
+        let hours =
+            business_hours_between(at("2024-01-01T12:00:00Z"), at("2024-01-04T12:00:00Z"), &utc);
+        assert_eq!(hours, 5.0 + 8.0 + 8.0 + 3.0);
+
+        let mut holidays = calendar(chrono_tz::UTC);
+        holidays
+            .holidays
+            .insert(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap());
+        let hours = business_hours_between(
+            at("2024-01-01T12:00:00Z"),
+            at("2024-01-04T12:00:00Z"),
+            &holidays,
+        );
+        assert_eq!(hours, 5.0 + 8.0 + 3.0);
+    }
+
+    #[test]
+    fn business_hours_in_the_calendar_timezone() {
+        let new_york = calendar(chrono_tz::America::New_York);
+        // 9am to 5pm in New York (UTC-5 in January)
+        let hours = business_hours_between(
+            at("2024-01-03T12:00:00Z"),
+            at("2024-01-03T23:00:00Z"),
+            &new_york,
+        );
+        assert_eq!(hours, 8.0);
+    }
+
+    #[test]
+    fn business_hours_of_an_empty_span() {
+        let utc = calendar(chrono_tz::UTC);
+        let noon = at("2024-01-03T12:00:00Z");
+        assert_eq!(business_hours_between(noon, noon, &utc), 0.0);
+        assert_eq!(
+            business_hours_between(noon, at("2024-01-03T11:00:00Z"), &utc),
+            0.0
+        );
     }
 }